@@ -0,0 +1,310 @@
+use crate::module_bindings::SignedChat;
+use crate::protocol::chat::{SChat, SChatSessionUpdate, LAST_SEEN_MESSAGES, SIGNATURE_LENGTH};
+use rsa::pkcs1v15::{Signature, VerifyingKey};
+use rsa::pkcs8::DecodePublicKey;
+use rsa::signature::Verifier;
+use rsa::RsaPublicKey;
+use sha1::Sha1;
+use sha2::Sha256;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+pub const INVALID_PUBLIC_KEY_SIGNATURE: &str =
+    "multiplayer.disconnect.invalid_public_key_signature";
+pub const EXPIRED_PUBLIC_KEY: &str = "multiplayer.disconnect.expired_public_key";
+pub const CHAT_VALIDATION_FAILED: &str = "multiplayer.disconnect.chat_validation_failed";
+pub const OUT_OF_ORDER_CHAT: &str = "multiplayer.disconnect.out_of_order_chat";
+pub const TOO_MANY_PENDING_CHATS: &str = "multiplayer.disconnect.too_many_pending_chats";
+pub const INVALID_SIGNATURE: &str = "chat.disabled.invalid_signature";
+pub const MISSING_PROFILE_KEY: &str = "chat.disabled.missingProfileKey";
+pub const EXPIRED_PROFILE_KEY: &str = "chat.disabled.expiredProfileKey";
+
+/// Version of the signed message format, the first thing signed.
+const MESSAGE_VERSION: i32 = 1;
+/// Messages shown to a client that did not acknowledge them yet, beyond which vanilla disconnects
+/// it.
+const MAX_TRACKED_MESSAGES: usize = 4096;
+
+type MessageSignature = [u8; SIGNATURE_LENGTH];
+
+/// Why a message was refused, as translation keys.
+#[derive(Debug)]
+pub enum ChatError {
+    /// The player is only told, as for an expired key
+    Refused(&'static str),
+    /// The client can not be trusted anymore
+    Disconnect(&'static str),
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_millis() as i64)
+}
+
+/// Checks the key was issued by Mojang to this player, as vanilla does. Returns the translation
+/// key to disconnect the player with otherwise.
+pub fn verify_profile_key(
+    mojang_keys: &[RsaPublicKey],
+    profile_id: Uuid,
+    update: &SChatSessionUpdate,
+) -> Result<(), &'static str> {
+    if update.expires_at < now_millis() {
+        return Err(EXPIRED_PUBLIC_KEY);
+    }
+    let mut payload = Vec::with_capacity(24 + update.public_key.len());
+    payload.extend_from_slice(profile_id.as_bytes());
+    payload.extend_from_slice(&update.expires_at.to_be_bytes());
+    payload.extend_from_slice(&update.public_key);
+    let signature = Signature::try_from(update.key_signature.as_slice())
+        .map_err(|_| INVALID_PUBLIC_KEY_SIGNATURE)?;
+    let signed_by_mojang = mojang_keys.iter().any(|key| {
+        VerifyingKey::<Sha1>::new(key.clone())
+            .verify(&payload, &signature)
+            .is_ok()
+    });
+    if !signed_by_mojang {
+        return Err(INVALID_PUBLIC_KEY_SIGNATURE);
+    }
+    RsaPublicKey::from_public_key_der(&update.public_key)
+        .map(|_| ())
+        .map_err(|_| INVALID_PUBLIC_KEY_SIGNATURE)
+}
+
+/// A verified key the client signs with.
+struct Session {
+    id: Uuid,
+    key: VerifyingKey<Sha256>,
+    /// Milliseconds since the epoch
+    expires_at: i64,
+}
+
+/// A message shown to the client, which it must acknowledge or ignore.
+#[derive(Clone, Copy)]
+struct Tracked {
+    signature: MessageSignature,
+    pending: bool,
+}
+
+/// Follows the chain of messages a client signs, and the messages it acknowledges, as vanilla's
+/// `SignedMessageChain` and `LastSeenMessagesValidator` do.
+pub struct ChatValidator {
+    profile_id: Uuid,
+    session: Option<Session>,
+    /// Index of the next message within the session
+    next_index: i32,
+    last_timestamp: i64,
+    /// Messages shown to the client, the first [LAST_SEEN_MESSAGES] being those it may
+    /// acknowledge
+    tracked: Vec<Option<Tracked>>,
+    last_pending: Option<MessageSignature>,
+}
+
+impl ChatValidator {
+    pub fn new(profile_id: Uuid) -> Self {
+        Self {
+            profile_id,
+            session: None,
+            next_index: 0,
+            last_timestamp: 0,
+            tracked: vec![None; LAST_SEEN_MESSAGES],
+            last_pending: None,
+        }
+    }
+
+    /// Starts a new chain, once [verify_profile_key] accepted the key.
+    pub fn set_session(&mut self, update: &SChatSessionUpdate) -> Result<(), &'static str> {
+        let key = RsaPublicKey::from_public_key_der(&update.public_key)
+            .map_err(|_| INVALID_PUBLIC_KEY_SIGNATURE)?;
+        self.session = Some(Session {
+            id: update.session_id,
+            key: VerifyingKey::new(key),
+            expires_at: update.expires_at,
+        });
+        self.next_index = 0;
+        self.last_timestamp = 0;
+        Ok(())
+    }
+
+    /// Remembers a signed message shown to the client, failing with the translation key to
+    /// disconnect it with once it left too many unacknowledged.
+    pub fn track(&mut self, signature: &[u8]) -> Result<(), &'static str> {
+        let Ok(signature) = MessageSignature::try_from(signature) else {
+            return Ok(());
+        };
+        if self.last_pending != Some(signature) {
+            self.tracked.push(Some(Tracked {
+                signature,
+                pending: true,
+            }));
+            self.last_pending = Some(signature);
+        }
+        if self.tracked.len() > MAX_TRACKED_MESSAGES {
+            return Err(TOO_MANY_PENDING_CHATS);
+        }
+        Ok(())
+    }
+
+    /// Forgets the messages the client will not acknowledge anymore.
+    pub fn acknowledge(&mut self, offset: i32) -> Result<(), String> {
+        let trackable = self.tracked.len() - LAST_SEEN_MESSAGES;
+        match usize::try_from(offset) {
+            Ok(offset) if offset <= trackable => {
+                self.tracked.drain(..offset);
+                Ok(())
+            }
+            _ => Err(format!(
+                "Advanced last seen window by {offset}, beyond the {trackable} tracked messages"
+            )),
+        }
+    }
+
+    /// Applies what the client acknowledged along with its message, returning the signatures it
+    /// saw, oldest first.
+    fn last_seen(&mut self, chat: &SChat) -> Result<Vec<MessageSignature>, String> {
+        self.acknowledge(chat.message_count)?;
+        let mut last_seen = Vec::new();
+        for index in 0..LAST_SEEN_MESSAGES {
+            let acknowledged = chat.acknowledged[index / 8] & (1 << (index % 8)) != 0;
+            let entry = self.tracked[index];
+            if acknowledged {
+                let Some(tracked) = entry else {
+                    return Err(format!(
+                        "Acknowledged unknown or previously ignored message at {index}"
+                    ));
+                };
+                self.tracked[index] = Some(Tracked {
+                    pending: false,
+                    ..tracked
+                });
+                last_seen.push(tracked.signature);
+            } else {
+                if entry.is_some_and(|tracked| !tracked.pending) {
+                    return Err(format!(
+                        "Ignored previously acknowledged message at {index}"
+                    ));
+                }
+                self.tracked[index] = None;
+            }
+        }
+        if chat.checksum != 0 && chat.checksum != checksum(&last_seen) {
+            return Err("Last seen checksum mismatch".into());
+        }
+        Ok(last_seen)
+    }
+
+    /// Returns what other clients need to verify the message, `None` unless it must be signed.
+    pub fn accept(&mut self, chat: &SChat, secure: bool) -> Result<Option<SignedChat>, ChatError> {
+        if !secure {
+            return Ok(None);
+        }
+        let last_seen = self.last_seen(chat).map_err(|e| {
+            log::info!("Chat of {} failed validation : {e}", self.profile_id);
+            ChatError::Disconnect(CHAT_VALIDATION_FAILED)
+        })?;
+        let (Some(session), Some(signature)) = (&self.session, &chat.signature) else {
+            return Err(ChatError::Refused(MISSING_PROFILE_KEY));
+        };
+        if session.expires_at < now_millis() {
+            return Err(ChatError::Refused(EXPIRED_PROFILE_KEY));
+        }
+        if chat.timestamp < self.last_timestamp {
+            return Err(ChatError::Disconnect(OUT_OF_ORDER_CHAT));
+        }
+        self.last_timestamp = chat.timestamp;
+
+        let payload = signed_payload(
+            self.profile_id,
+            session.id,
+            self.next_index,
+            chat,
+            &last_seen,
+        );
+        let valid = Signature::try_from(signature.as_slice())
+            .is_ok_and(|signature| session.key.verify(&payload, &signature).is_ok());
+        if !valid {
+            return Err(ChatError::Disconnect(INVALID_SIGNATURE));
+        }
+
+        let index = self.next_index;
+        self.next_index += 1;
+        Ok(Some(SignedChat {
+            index,
+            timestamp: chat.timestamp,
+            salt: chat.salt,
+            signature: signature.to_vec(),
+            last_seen: last_seen.iter().map(|seen| seen.to_vec()).collect(),
+        }))
+    }
+}
+
+/// What the client signed : the format version, its place in the chain, then the message.
+fn signed_payload(
+    sender: Uuid,
+    session_id: Uuid,
+    index: i32,
+    chat: &SChat,
+    last_seen: &[MessageSignature],
+) -> Vec<u8> {
+    let mut payload = Vec::new();
+    payload.extend_from_slice(&MESSAGE_VERSION.to_be_bytes());
+    payload.extend_from_slice(sender.as_bytes());
+    payload.extend_from_slice(session_id.as_bytes());
+    payload.extend_from_slice(&index.to_be_bytes());
+    payload.extend_from_slice(&chat.salt.to_be_bytes());
+    // Signed in seconds, sent in milliseconds
+    payload.extend_from_slice(&chat.timestamp.div_euclid(1000).to_be_bytes());
+    payload.extend_from_slice(&(chat.message.len() as i32).to_be_bytes());
+    payload.extend_from_slice(chat.message.as_bytes());
+    payload.extend_from_slice(&(last_seen.len() as i32).to_be_bytes());
+    for signature in last_seen {
+        payload.extend_from_slice(signature);
+    }
+    payload
+}
+
+/// Java's `Arrays.hashCode` of each signature, folded the same way, never 0.
+fn checksum(last_seen: &[MessageSignature]) -> u8 {
+    let checksum = last_seen.iter().fold(1i32, |checksum, signature| {
+        let hash = signature.iter().fold(1i32, |hash, &byte| {
+            hash.wrapping_mul(31).wrapping_add(i32::from(byte as i8))
+        });
+        checksum.wrapping_mul(31).wrapping_add(hash)
+    });
+    match checksum as u8 {
+        0 => 1,
+        checksum => checksum,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signature(index: usize) -> MessageSignature {
+        let mut signature = [0; SIGNATURE_LENGTH];
+        signature[..8].copy_from_slice(&index.to_be_bytes());
+        signature
+    }
+
+    #[test]
+    fn too_many_pending_messages_disconnect() {
+        let mut validator = ChatValidator::new(Uuid::nil());
+        for index in LAST_SEEN_MESSAGES..MAX_TRACKED_MESSAGES {
+            assert_eq!(validator.track(&signature(index)), Ok(()));
+        }
+        assert_eq!(
+            validator.track(&signature(MAX_TRACKED_MESSAGES)),
+            Err(TOO_MANY_PENDING_CHATS)
+        );
+    }
+
+    #[test]
+    fn acknowledged_messages_are_not_pending() {
+        let mut validator = ChatValidator::new(Uuid::nil());
+        for index in 0..MAX_TRACKED_MESSAGES {
+            validator.acknowledge(1).ok();
+            assert_eq!(validator.track(&signature(index)), Ok(()));
+        }
+    }
+}
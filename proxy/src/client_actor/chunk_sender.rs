@@ -0,0 +1,234 @@
+use crate::world::subscription::ChunkWatch;
+use crate::world::{ChunkPos, World};
+use bytes::Bytes;
+use std::collections::{HashMap, HashSet};
+use tokio::sync::mpsc;
+use tokio_util::task::TaskTracker;
+
+// Same pacing as vanilla : the client tells how many chunks per tick it can take after each batch
+const START_CHUNKS_PER_TICK: f32 = 9.0;
+const MIN_CHUNKS_PER_TICK: f32 = 0.01;
+const MAX_CHUNKS_PER_TICK: f32 = 64.0;
+const START_MAX_UNACKNOWLEDGED_BATCHES: u32 = 1;
+const MAX_UNACKNOWLEDGED_BATCHES: u32 = 10;
+
+/// Chunks loaded or encoded at once for a single player, so a teleport does not queue the whole
+/// view at once.
+const MAX_IN_FLIGHT: usize = 32;
+
+/// Chunk coordinates within the player's dimension.
+pub type Column = (i32, i32);
+
+/// An encoded Chunk Data and Update Light packet along with the watch keeping the chunk in the
+/// client cache, or why the chunk could not be loaded.
+pub type ChunkResult = (Column, Result<(Bytes, ChunkWatch), String>);
+
+/// Which chunks a player sees and what is left to send them.
+pub struct ChunkSender {
+    world: World,
+    dimension: String,
+    center: Column,
+    view_distance: i32,
+    /// Sent to the client
+    loaded: HashSet<Column>,
+    /// Being loaded or encoded, until handed to [ChunkSender::loaded]
+    in_flight: HashSet<Column>,
+    /// Encoded, waiting for the send budget
+    ready: HashMap<Column, Bytes>,
+    /// Not retried until they leave the view
+    failed: HashSet<Column>,
+    /// Chunks sent or ready, kept in the client cache while in view
+    watches: HashMap<Column, ChunkWatch>,
+    chunks_per_tick: f32,
+    batch_quota: f32,
+    unacknowledged_batches: u32,
+    max_unacknowledged_batches: u32,
+    results: mpsc::Sender<ChunkResult>,
+    tracker: TaskTracker,
+}
+
+impl ChunkSender {
+    /// Loaded chunks come back through the receiver, to be handed to [ChunkSender::loaded].
+    pub fn new(
+        world: World,
+        dimension: String,
+        center: Column,
+        view_distance: u8,
+        tracker: TaskTracker,
+    ) -> (Self, mpsc::Receiver<ChunkResult>) {
+        let (results, receiver) = mpsc::channel(MAX_IN_FLIGHT);
+        let sender = Self {
+            world,
+            dimension,
+            center,
+            view_distance: i32::from(view_distance),
+            loaded: HashSet::new(),
+            in_flight: HashSet::new(),
+            ready: HashMap::new(),
+            failed: HashSet::new(),
+            watches: HashMap::new(),
+            chunks_per_tick: START_CHUNKS_PER_TICK,
+            batch_quota: 0.0,
+            unacknowledged_batches: 0,
+            max_unacknowledged_batches: START_MAX_UNACKNOWLEDGED_BATCHES,
+            results,
+            tracker,
+        };
+        (sender, receiver)
+    }
+
+    pub fn center(&self) -> Column {
+        self.center
+    }
+
+    /// Returns the chunks the client must forget, `None` if the center did not change.
+    pub fn set_center(&mut self, center: Column) -> Option<Vec<Column>> {
+        if center == self.center {
+            return None;
+        }
+        self.center = center;
+        Some(self.out_of_view())
+    }
+
+    /// Returns the chunks the client must forget.
+    pub fn set_view_distance(&mut self, view_distance: u8) -> Vec<Column> {
+        self.view_distance = i32::from(view_distance);
+        self.out_of_view()
+    }
+
+    fn in_view(&self, column: Column) -> bool {
+        in_view(self.center, self.view_distance, column)
+    }
+
+    fn distance(&self, (x, z): Column) -> i32 {
+        let (dx, dz) = (x - self.center.0, z - self.center.1);
+        dx * dx + dz * dz
+    }
+
+    fn out_of_view(&mut self) -> Vec<Column> {
+        let forget = self
+            .loaded
+            .iter()
+            .copied()
+            .filter(|&column| !self.in_view(column))
+            .collect::<Vec<_>>();
+        for column in &forget {
+            self.loaded.remove(column);
+        }
+        // Results of chunks in flight are dropped when they arrive
+        let (center, view_distance) = (self.center, self.view_distance);
+        self.ready
+            .retain(|&column, _| in_view(center, view_distance, column));
+        self.failed
+            .retain(|&column| in_view(center, view_distance, column));
+        self.watches
+            .retain(|&column, _| in_view(center, view_distance, column));
+        forget
+    }
+
+    /// Starts loading the nearest chunks that are neither sent nor on their way.
+    pub fn request(&mut self) {
+        let free = MAX_IN_FLIGHT.saturating_sub(self.in_flight.len());
+        if free == 0 {
+            return;
+        }
+        let radius = self.view_distance + 1;
+        let mut wanted = (self.center.0 - radius..=self.center.0 + radius)
+            .flat_map(|x| (self.center.1 - radius..=self.center.1 + radius).map(move |z| (x, z)))
+            .filter(|&column| {
+                self.in_view(column)
+                    && !self.loaded.contains(&column)
+                    && !self.in_flight.contains(&column)
+                    && !self.ready.contains_key(&column)
+                    && !self.failed.contains(&column)
+            })
+            .collect::<Vec<_>>();
+        wanted.sort_by_key(|&column| self.distance(column));
+
+        for column in wanted.into_iter().take(free) {
+            self.in_flight.insert(column);
+            let world = self.world.clone();
+            let position = ChunkPos::new(self.dimension.as_str(), column.0, column.1);
+            let results = self.results.clone();
+            self.tracker.spawn(async move {
+                let packet = match world.watch(&position).await {
+                    Ok(watch) => world
+                        .chunk_packet(&position)
+                        .await
+                        .map(|packet| (packet, watch)),
+                    Err(e) => Err(e),
+                };
+                let _ = results.send((column, packet)).await;
+            });
+        }
+    }
+
+    pub fn loaded(&mut self, (column, packet): ChunkResult) {
+        self.in_flight.remove(&column);
+        if !self.in_view(column) {
+            return;
+        }
+        match packet {
+            Ok((packet, watch)) => {
+                self.ready.insert(column, packet);
+                self.watches.insert(column, watch);
+            }
+            Err(e) => {
+                log::error!(
+                    "Failed to load chunk {column:?} of {} : {e}",
+                    self.dimension
+                );
+                self.failed.insert(column);
+            }
+        }
+    }
+
+    /// The packets to send this tick, nearest first, within what the client can take.
+    pub fn next_batch(&mut self) -> Vec<Bytes> {
+        if self.unacknowledged_batches >= self.max_unacknowledged_batches {
+            return Vec::new();
+        }
+        self.batch_quota =
+            (self.batch_quota + self.chunks_per_tick).min(self.chunks_per_tick.max(1.0));
+        if self.batch_quota < 1.0 || self.ready.is_empty() {
+            return Vec::new();
+        }
+
+        let mut columns = self.ready.keys().copied().collect::<Vec<_>>();
+        columns.sort_by_key(|&column| self.distance(column));
+        columns.truncate(self.batch_quota as usize);
+        self.batch_quota -= columns.len() as f32;
+        self.unacknowledged_batches += 1;
+
+        columns
+            .into_iter()
+            .filter_map(|column| {
+                self.loaded.insert(column);
+                self.ready.remove(&column)
+            })
+            .collect()
+    }
+
+    /// The client received a batch and can now take `chunks_per_tick`.
+    pub fn acknowledge(&mut self, chunks_per_tick: f32) {
+        self.unacknowledged_batches = self.unacknowledged_batches.saturating_sub(1);
+        self.chunks_per_tick = if chunks_per_tick.is_nan() {
+            MIN_CHUNKS_PER_TICK
+        } else {
+            chunks_per_tick.clamp(MIN_CHUNKS_PER_TICK, MAX_CHUNKS_PER_TICK)
+        };
+        if self.unacknowledged_batches == 0 {
+            self.batch_quota = 1.0;
+        }
+        self.max_unacknowledged_batches = MAX_UNACKNOWLEDGED_BATCHES;
+    }
+}
+
+/// Whether the chunk is close enough to the center to be seen, as vanilla computes it.
+fn in_view(center: Column, view_distance: i32, (x, z): Column) -> bool {
+    let dx = ((x - center.0).abs() - 1).max(0);
+    let dz = ((z - center.1).abs() - 1).max(0);
+    let far = i64::from((dx.max(dz) - 1).max(0));
+    let near = i64::from(dx.min(dz));
+    far * far + near * near < i64::from(view_distance * view_distance)
+}
@@ -1,14 +1,39 @@
 use crate::client_actor::mc_socket;
 use crate::client_actor::net::MCCodec;
+use crate::client_actor::play::PlayHandler;
+use crate::client_actor::session::{disconnect_message, ConnectionMessage, SessionGuard};
 use crate::client_actor::stream_actor::StreamActor;
+use crate::protocol::config::CConfigDisconnect;
 use crate::server_actor::actor::Server;
+use crate::server_actor::connection_cache::CachedBranding;
+use futures::StreamExt;
 use pumpkin::net::GameProfile;
+use pumpkin_data::tag::RegistryKey;
+use pumpkin_protocol::client::config::{
+    CFinishConfig, CKnownPacks, CRegistryData, CUpdateTags, KnownPack,
+};
+use pumpkin_protocol::ser::packet::Packet;
+use pumpkin_protocol::server::config::{
+    SAcknowledgeFinishConfig, SClientInformationConfig, SKnownPacks, SPluginMessage,
+};
+use pumpkin_protocol::RawPacket;
+use pumpkin_registry::Registry;
 use std::fmt::{Debug, Formatter};
 use std::net::SocketAddr;
+use std::sync::LazyLock;
+use std::time::Duration;
 use tokio::net::TcpStream;
+use tokio::select;
+use tokio::time::sleep;
 use tokio_util::codec::Framed;
 use tokio_util::task::TaskTracker;
 
+static BRANDING: LazyLock<CachedBranding> = LazyLock::new(CachedBranding::new);
+static REGISTRIES: LazyLock<Vec<Registry>> = LazyLock::new(Registry::get_synced);
+
+/// View distance assumed until the client sends its information.
+const DEFAULT_CLIENT_VIEW_DISTANCE: u8 = 8;
+
 pub struct ConfigurationHandler;
 
 impl ConfigurationHandler {
@@ -19,6 +44,7 @@ impl ConfigurationHandler {
         tracker: TaskTracker,
         server: Server,
         profile: GameProfile,
+        session: SessionGuard,
     ) {
         let config_actor = ConfigurationActor {
             id,
@@ -27,6 +53,8 @@ impl ConfigurationHandler {
             server,
             tracker: tracker.clone(),
             profile,
+            session,
+            client_view_distance: DEFAULT_CLIENT_VIEW_DISTANCE,
         };
         tracker.spawn(config_actor.run());
     }
@@ -50,6 +78,8 @@ struct ConfigurationActor {
     server: Server,
     tracker: TaskTracker,
     profile: GameProfile,
+    session: SessionGuard,
+    client_view_distance: u8,
 }
 
 impl StreamActor<Framed<MCSocket, MCCodec>> for ConfigurationActor {
@@ -58,10 +88,150 @@ impl StreamActor<Framed<MCSocket, MCCodec>> for ConfigurationActor {
     }
 }
 
+/// What to do after handling a configuration packet
+enum Step {
+    Continue,
+    Finished,
+    Failed,
+}
+
 impl ConfigurationActor {
     async fn run(mut self) {
         log::debug!("{self:?} initialized");
 
+        self.send(BRANDING.get_branding()).await;
+        self.send(CKnownPacks::new(&[KnownPack {
+            namespace: "minecraft",
+            id: "core",
+            version: "1.21",
+        }]))
+        .await;
+
+        loop {
+            let packet = select! {
+                biased;
+                message = self.session.recv() => match message {
+                    Some(ConnectionMessage::Kick { reason }) => {
+                        self.disconnect(&reason).await;
+                        break;
+                    }
+                    // Picked up when entering the play state
+                    Some(
+                        ConnectionMessage::SetOpLevel(_)
+                        | ConnectionMessage::ConfigUpdated(_)
+                        | ConnectionMessage::InventoryChanged(_)
+                    ) => continue,
+                    // Not in game yet
+                    Some(
+                        ConnectionMessage::Chat(_)
+                        | ConnectionMessage::Feedback(_)
+                        | ConnectionMessage::CommandFeedback(_)
+                        | ConnectionMessage::ChatSessionStarted { .. }
+                        | ConnectionMessage::ChatSessionEnded(_)
+                    ) => continue,
+                    None => break,
+                },
+                _ = sleep(Duration::from_secs(5)) => {
+                    log::debug!("{self:?} timeout reached");
+                    break;
+                }
+                frame = self.framer.next() => match frame {
+                    Some(Ok(packet)) => packet,
+                    Some(Err(e)) => {
+                        log::error!("{self:?} stream error : {e:?}");
+                        break;
+                    }
+                    None => {
+                        log::debug!("{self:?} stream closed");
+                        break;
+                    }
+                },
+            };
+
+            match self.handle_packet(packet).await {
+                Step::Continue => {}
+                Step::Finished => return self.transition_play().await,
+                Step::Failed => break,
+            }
+        }
+
         self.shutdown().await
     }
+
+    async fn handle_packet(&mut self, packet: RawPacket) -> Step {
+        match packet.id {
+            id if id == SClientInformationConfig::PACKET_ID => {
+                match self.decode::<SClientInformationConfig>(packet) {
+                    Some(information) => {
+                        self.client_view_distance = information.view_distance.max(2) as u8;
+                        Step::Continue
+                    }
+                    None => Step::Failed,
+                }
+            }
+            id if id == SPluginMessage::PACKET_ID => {
+                log::trace!("{self:?} ignoring plugin message");
+                Step::Continue
+            }
+            id if id == SKnownPacks::PACKET_ID => {
+                if self.send_registries().await {
+                    Step::Continue
+                } else {
+                    Step::Failed
+                }
+            }
+            id if id == SAcknowledgeFinishConfig::PACKET_ID => Step::Finished,
+            id => {
+                log::debug!("{self:?} ignoring unexpected packet {id}");
+                Step::Continue
+            }
+        }
+    }
+
+    async fn send_registries(&mut self) -> bool {
+        for registry in REGISTRIES.iter() {
+            if !self
+                .send(CRegistryData::new(
+                    &registry.registry_id,
+                    &registry.registry_entries,
+                ))
+                .await
+            {
+                return false;
+            }
+        }
+
+        self.send(CUpdateTags::new(&[
+            RegistryKey::Block,
+            RegistryKey::Fluid,
+            RegistryKey::Enchantment,
+            RegistryKey::WorldgenBiome,
+            RegistryKey::Item,
+            RegistryKey::EntityType,
+        ]))
+        .await
+            && self.send(CFinishConfig::new()).await
+    }
+
+    async fn disconnect(&mut self, reason: &str) -> bool {
+        self.send(CConfigDisconnect {
+            reason: &disconnect_message(reason),
+        })
+        .await
+    }
+
+    async fn transition_play(self) {
+        log::info!("{self:?} transitioning to play state");
+        PlayHandler::spawn(
+            self.id,
+            self.client_address,
+            self.framer,
+            self.tracker,
+            self.server,
+            self.profile,
+            self.session,
+            self.client_view_distance,
+        )
+        .await
+    }
 }
@@ -2,11 +2,13 @@ use crate::actor_ref::ActorRef;
 use crate::client_actor::configuration::ConfigurationHandler;
 use crate::client_actor::mc_socket;
 use crate::client_actor::net::MCCodec;
+use crate::client_actor::session::{disconnect_message, Connection, SessionGuard};
 use crate::client_actor::stream_actor::StreamActor;
 use crate::server_actor::actor::{Server, ServerMessage};
 use pumpkin::net::GameProfile;
-use pumpkin_protocol::client::login::{CEncryptionRequest, CLoginSuccess};
+use pumpkin_protocol::client::login::{CEncryptionRequest, CLoginDisconnect, CLoginSuccess};
 use pumpkin_protocol::server::login::{SEncryptionResponse, SLoginAcknowledged, SLoginStart};
+use pumpkin_util::text::TextComponent;
 use std::fmt::{Debug, Formatter};
 use std::net::SocketAddr;
 use tokio::net::TcpStream;
@@ -100,7 +102,14 @@ impl LoginActor {
 
         // TODO : compression
 
+        // The module refuses players the whitelist or a ban keeps out
         let profile = self.profile.take().unwrap();
+        let (session, connection) =
+            SessionGuard::new(profile.id, self.id as u64, self.server.clone());
+        if !self.join(&profile, connection).await {
+            return self.shutdown().await;
+        }
+
         self.send(CLoginSuccess {
             uuid: &profile.id,
             username: &profile.name,
@@ -120,6 +129,7 @@ impl LoginActor {
                     self.tracker,
                     self.server,
                     profile,
+                    session,
                 )
                 .await
             }
@@ -151,9 +161,9 @@ impl LoginActor {
             login.name
         );
 
+        // Usernames, free space and duplicate sessions are checked by the module when joining, see
+        // `join`
         // TODO :
-        //  - check there is free space
-        //  - check username validity
         //  - support offline mode
         //  - velocity/bungeecord ?
 
@@ -239,4 +249,41 @@ impl LoginActor {
 
         <[u8; 16]>::try_from(&shared_secret[..16]).ok()
     }
+
+    /// Asks the module to mark the player online, disconnecting them if it refuses.
+    async fn join(&mut self, profile: &GameProfile, connection: Connection) -> bool {
+        let outcome = match self
+            .server
+            .ask(|reply_to| ServerMessage::PlayerJoin {
+                profile_id: profile.id,
+                username: profile.name.clone(),
+                connection_id: self.id as u64,
+                address: self.client_address.ip(),
+                connection,
+                reply_to,
+            })
+            .await
+        {
+            Ok(recv) => recv,
+            Err(_) => return false,
+        };
+
+        match outcome.await {
+            Ok(Ok(())) => true,
+            Ok(Err(reason)) => {
+                log::info!("{self:?} join rejected : {reason}");
+                self.disconnect(disconnect_message(&reason)).await;
+                false
+            }
+            Err(_) => {
+                log::error!("{self:?} lost the join outcome");
+                false
+            }
+        }
+    }
+
+    async fn disconnect(&mut self, reason: TextComponent) -> bool {
+        let json_reason = serde_json::to_string(&reason.0).unwrap_or_default();
+        self.send(CLoginDisconnect::new(&json_reason)).await
+    }
 }
@@ -1,7 +1,12 @@
+pub mod chat;
+pub mod chunk_sender;
 pub mod configuration;
 pub mod handshake;
 pub mod login;
 pub mod mc_socket;
+pub mod movement;
 pub mod net;
+pub mod play;
+pub mod session;
 pub mod status;
 pub mod stream_actor;
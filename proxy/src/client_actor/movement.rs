@@ -0,0 +1,260 @@
+use crate::module_bindings::Violation;
+use crate::world::{encode, Location, World};
+use pumpkin_util::math::vector3::Vector3;
+use pumpkin_util::GameMode;
+use std::time::{Duration, Instant};
+
+/// Farther than this within a single packet is refused, as vanilla does.
+const MAX_MOVE_DISTANCE: f64 = 10.0;
+
+/// Horizontal blocks per second, with room for sprint jumping on ice and speed effects.
+const MAX_SPEED: f64 = 14.0;
+/// Flying creative players sprint faster than anyone walks.
+const MAX_FLYING_SPEED: f64 = 30.0;
+/// Movement packets arrive in bursts, so the speed is measured over this long.
+const SPEED_WINDOW: Duration = Duration::from_secs(1);
+/// Allowed on top of the speed, so a burst right after a pause is not refused.
+const SPEED_SLACK: f64 = 2.0;
+
+/// Moves in the air without falling before the player is considered flying. Vanilla allows 80
+/// ticks, at one move per tick.
+const MAX_HOVERING_MOVES: u32 = 80;
+/// Vanilla only counts a move as falling once it drops more than this.
+const MIN_FALL: f64 = 0.03125;
+
+/// Half the width of a player's hitbox, centered on their position.
+const HALF_WIDTH: f64 = 0.3;
+/// Height of a standing player's hitbox.
+const HEIGHT: f64 = 1.8;
+/// How far around their hitbox blocks keep players up, as vanilla's `noBlocksAround`.
+const SUPPORT_MARGIN: f64 = 0.0625;
+const SUPPORT_DEPTH: f64 = 0.55;
+
+/// Checks the moves a client claims to make against what its game mode allows.
+pub struct MovementChecker {
+    window_start: Instant,
+    /// Horizontal distance moved since [MovementChecker::window_start]
+    window_distance: f64,
+    hovering_moves: u32,
+}
+
+impl Default for MovementChecker {
+    fn default() -> Self {
+        Self {
+            window_start: Instant::now(),
+            window_distance: 0.0,
+            hovering_moves: 0,
+        }
+    }
+}
+
+impl MovementChecker {
+    /// Starts over, e.g. once the player was teleported.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Returns why the move from `from` to `to` is refused, along with what was measured.
+    pub fn check(
+        &mut self,
+        world: &World,
+        game_mode: GameMode,
+        from: &Location,
+        to: Vector3<f64>,
+    ) -> Result<(), (Violation, String)> {
+        // Spectators fly through anything
+        if game_mode == GameMode::Spectator {
+            return Ok(());
+        }
+
+        let (dx, dy, dz) = (
+            to.x - from.position.x,
+            to.y - from.position.y,
+            to.z - from.position.z,
+        );
+        let distance = (dx * dx + dy * dy + dz * dz).sqrt();
+        if distance > MAX_MOVE_DISTANCE {
+            return Err((
+                Violation::Teleport,
+                format!("moved {distance:.2} blocks at once"),
+            ));
+        }
+
+        let now = Instant::now();
+        if now.duration_since(self.window_start) > SPEED_WINDOW {
+            self.window_start = now;
+            self.window_distance = 0.0;
+        }
+        self.window_distance += (dx * dx + dz * dz).sqrt();
+        let max_speed = if game_mode == GameMode::Creative {
+            MAX_FLYING_SPEED
+        } else {
+            MAX_SPEED
+        };
+        let allowed = max_speed * SPEED_WINDOW.as_secs_f64() + SPEED_SLACK;
+        if self.window_distance > allowed {
+            return Err((
+                Violation::Speed,
+                format!(
+                    "moved {:.2} blocks within {SPEED_WINDOW:?}",
+                    self.window_distance
+                ),
+            ));
+        }
+
+        // Neither the ground flag nor small drops can be trusted, only the blocks around
+        if game_mode != GameMode::Creative {
+            if dy < -MIN_FALL || has_blocks_around(world, &from.dimension, to) {
+                self.hovering_moves = 0;
+            } else {
+                self.hovering_moves += 1;
+                if self.hovering_moves > MAX_HOVERING_MOVES {
+                    return Err((
+                        Violation::Flying,
+                        format!("{} moves without falling", self.hovering_moves),
+                    ));
+                }
+            }
+        }
+
+        // Players stuck in a block, e.g. one placed on them, may still move out of it
+        if is_inside_block(world, &from.dimension, to)
+            && !is_inside_block(world, &from.dimension, from.position)
+        {
+            return Err((
+                Violation::InsideBlock,
+                format!("moved into a block at {:.2} {:.2} {:.2}", to.x, to.y, to.z),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Axis aligned box, from its lowest to its highest corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Hitbox {
+    min: Vector3<f64>,
+    max: Vector3<f64>,
+}
+
+impl Hitbox {
+    /// The box of a standing player whose feet are at `position`.
+    fn player(position: Vector3<f64>) -> Self {
+        Self {
+            min: Vector3::new(position.x - HALF_WIDTH, position.y, position.z - HALF_WIDTH),
+            max: Vector3::new(
+                position.x + HALF_WIDTH,
+                position.y + HEIGHT,
+                position.z + HALF_WIDTH,
+            ),
+        }
+    }
+
+    /// Grown by `margin` on every side, and by `depth` more downwards.
+    fn grow(self, margin: f64, depth: f64) -> Self {
+        Self {
+            min: Vector3::new(
+                self.min.x - margin,
+                self.min.y - margin - depth,
+                self.min.z - margin,
+            ),
+            max: Vector3::new(
+                self.max.x + margin,
+                self.max.y + margin,
+                self.max.z + margin,
+            ),
+        }
+    }
+
+    /// Blocks the box overlaps, those it only touches the faces of excluded.
+    fn blocks(&self) -> impl Iterator<Item = (i32, i32, i32)> {
+        let low = |coordinate: f64| (coordinate + 1e-7).floor() as i32;
+        let high = |coordinate: f64| (coordinate - 1e-7).floor() as i32;
+        let (xs, ys, zs) = (
+            low(self.min.x)..=high(self.max.x),
+            low(self.min.y)..=high(self.max.y),
+            low(self.min.z)..=high(self.max.z),
+        );
+        xs.flat_map(move |x| {
+            let zs = zs.clone();
+            ys.clone()
+                .flat_map(move |y| zs.clone().map(move |z| (x, y, z)))
+        })
+    }
+}
+
+/// Unknown blocks, e.g. of chunks that are not stored yet, are never considered solid.
+fn is_inside_block(world: &World, dimension: &str, position: Vector3<f64>) -> bool {
+    Hitbox::player(position).blocks().any(|(x, y, z)| {
+        world
+            .block_at(dimension, x, y, z)
+            .is_some_and(|block| encode::is_full_block(&block))
+    })
+}
+
+/// Whether anything but air is right around the player, which may hold them up : ground, ladders,
+/// fluids, cobwebs... Unknown blocks are given the benefit of the doubt.
+fn has_blocks_around(world: &World, dimension: &str, position: Vector3<f64>) -> bool {
+    Hitbox::player(position)
+        .grow(SUPPORT_MARGIN, SUPPORT_DEPTH)
+        .blocks()
+        .any(|(x, y, z)| {
+            world
+                .block_at(dimension, x, y, z)
+                .is_none_or(|block| !encode::is_air(&block))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocks(hitbox: Hitbox) -> Vec<(i32, i32, i32)> {
+        let mut blocks = hitbox.blocks().collect::<Vec<_>>();
+        blocks.sort();
+        blocks
+    }
+
+    #[test]
+    fn hitbox_covers_every_block_it_overlaps() {
+        // Straddling four columns, from the feet block to the head block
+        let hitbox = Hitbox::player(Vector3::new(10.0, 64.5, -3.0));
+        assert_eq!(
+            blocks(hitbox),
+            [
+                (9, 64, -4),
+                (9, 64, -3),
+                (9, 65, -4),
+                (9, 65, -3),
+                (9, 66, -4),
+                (9, 66, -3),
+                (10, 64, -4),
+                (10, 64, -3),
+                (10, 65, -4),
+                (10, 65, -3),
+                (10, 66, -4),
+                (10, 66, -3),
+            ]
+        );
+    }
+
+    #[test]
+    fn hitbox_excludes_blocks_it_only_touches() {
+        // Standing on top of a block and against walls
+        let hitbox = Hitbox::player(Vector3::new(0.3, 64.0, 0.5));
+        assert_eq!(blocks(hitbox), [(0, 64, 0), (0, 65, 0)]);
+    }
+
+    #[test]
+    fn support_reaches_below_the_feet() {
+        let hitbox =
+            Hitbox::player(Vector3::new(0.5, 64.5, 0.5)).grow(SUPPORT_MARGIN, SUPPORT_DEPTH);
+        assert!(hitbox.blocks().any(|(_, y, _)| y == 63));
+        assert!(hitbox.blocks().all(|(x, _, z)| x == 0 && z == 0));
+
+        // Half a block up, the block below is out of reach
+        let hitbox =
+            Hitbox::player(Vector3::new(0.5, 64.7, 0.5)).grow(SUPPORT_MARGIN, SUPPORT_DEPTH);
+        assert!(hitbox.blocks().all(|(_, y, _)| y >= 64));
+    }
+}
@@ -0,0 +1,1053 @@
+use crate::actor_ref::ActorRef;
+use crate::client_actor::chat::{ChatError, ChatValidator, CHAT_VALIDATION_FAILED};
+use crate::client_actor::chunk_sender::{ChunkResult, ChunkSender, Column};
+use crate::client_actor::mc_socket;
+use crate::client_actor::movement::MovementChecker;
+use crate::client_actor::net::MCCodec;
+use crate::client_actor::session::{disconnect_message, ConnectionMessage, SessionGuard};
+use crate::client_actor::stream_actor::StreamActor;
+use crate::database::InventoryAction;
+use crate::module_bindings::{
+    BasicConfiguration, ChatMessage, ChatSession, PermissionLvl, Violation,
+};
+use crate::protocol::chat::{
+    player_info_chat_session, player_info_remove, PlayerChat, SChat, SChatSessionUpdate,
+};
+use crate::protocol::command::{command_suggestions, CommandTree};
+use crate::protocol::item::{container_content, read_last_slot};
+use crate::protocol::play::{
+    CChangeDifficulty, CChunkBatchFinished, CChunkBatchStart, CForgetLevelChunk, CGameEvent,
+    CPlayerPosition, CSetChunkCacheCenter, CSetChunkCacheRadius, CSetHeldSlot,
+    CSetSimulationDistance, SAcceptTeleportation, SChatAck, SChatCommand, SChunkBatchReceived,
+    SCommandSuggestion, SContainerClick, SContainerClose, SMovePlayerPos, SMovePlayerPosRot,
+    SMovePlayerRot, SMovePlayerStatusOnly, SSetCarriedItem, ON_GROUND, PLAYER_INVENTORY,
+    PLAYER_INVENTORY_SLOTS, START_WAITING_FOR_CHUNKS,
+};
+use crate::server_actor::actor::{JoinInfo, Server, ServerMessage};
+use crate::world::{dimension_type, ChunkPos, Location, OVERWORLD};
+use bytes::Buf;
+use futures::StreamExt;
+use pumpkin::net::GameProfile;
+use pumpkin_data::packet::clientbound::{
+    PLAY_COMMANDS, PLAY_COMMAND_SUGGESTIONS, PLAY_CONTAINER_SET_CONTENT,
+    PLAY_LEVEL_CHUNK_WITH_LIGHT, PLAY_PLAYER_CHAT, PLAY_PLAYER_INFO_REMOVE,
+    PLAY_PLAYER_INFO_UPDATE,
+};
+use pumpkin_data::packet::serverbound::{
+    PLAY_CHAT, PLAY_CHAT_SESSION_UPDATE, PLAY_SET_CREATIVE_MODE_SLOT,
+};
+use pumpkin_protocol::client::play::{
+    CEntityStatus, CKeepAlive, CLogin, CPlayDisconnect, CSetExperience, CSetHealth,
+    CSystemChatMessage,
+};
+use pumpkin_protocol::codec::var_int::VarInt;
+use pumpkin_protocol::ser::packet::Packet;
+use pumpkin_protocol::server::play::SKeepAlive;
+use pumpkin_protocol::RawPacket;
+use pumpkin_util::math::vector3::Vector3;
+use pumpkin_util::permission::PermissionLvl as PumpkinPermissionLvl;
+use pumpkin_util::resource_location::ResourceLocation;
+use pumpkin_util::text::color::NamedColor;
+use pumpkin_util::text::TextComponent;
+use pumpkin_util::Difficulty as PumpkinDifficulty;
+use pumpkin_util::GameMode as PumpkinGameMode;
+use std::collections::HashSet;
+use std::fmt::{Debug, Formatter};
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::TcpStream;
+use tokio::select;
+use tokio::sync::mpsc;
+use tokio::time::{interval, MissedTickBehavior};
+use tokio_util::codec::Framed;
+use tokio_util::task::TaskTracker;
+use uuid::Uuid;
+
+/// How often keep alive packets are sent, the client must answer before the next one.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Entity status telling the client its op level is 0, levels 1 to 4 follow.
+const OP_LEVEL_STATUS: i8 = 24;
+
+/// Chunks are sent at most once per tick, within the budget the client asks for.
+const CHUNK_TICK: Duration = Duration::from_millis(50);
+
+/// Players spawn on top of the highest block in the middle of this chunk.
+const SPAWN_CHUNK: Column = (0, 0);
+/// Used when the spawn chunk can not be loaded.
+const FALLBACK_SPAWN_Y: i32 = 64;
+
+/// How often the location of a moving player is saved to the module.
+const SAVE_INTERVAL: Duration = Duration::from_millis(250);
+
+pub struct PlayHandler;
+
+impl PlayHandler {
+    #[allow(clippy::too_many_arguments)]
+    pub async fn spawn(
+        id: usize,
+        client_address: SocketAddr,
+        framer: Framed<MCSocket, MCCodec>,
+        tracker: TaskTracker,
+        server: Server,
+        profile: GameProfile,
+        session: SessionGuard,
+        client_view_distance: u8,
+    ) {
+        let join_info = match server
+            .ask(|reply_to| ServerMessage::GetJoinInfo {
+                profile_id: profile.id,
+                reply_to,
+            })
+            .await
+        {
+            Ok(recv) => recv.await.ok().flatten(),
+            Err(_) => None,
+        };
+        let Some(join_info) = join_info else {
+            log::info!("No session for {}, not spawning PlayActor {id}", profile.id);
+            return;
+        };
+
+        // Returning players are placed where they logged out, new ones at spawn once it is loaded
+        let placed = join_info.state.as_ref().filter(|state| state.placed);
+        let location = placed.map(|state| Location {
+            dimension: state.dimension.clone(),
+            position: Vector3::new(state.x, state.y, state.z),
+            yaw: state.yaw,
+            pitch: state.pitch,
+            on_ground: state.on_ground,
+        });
+        let game_mode = PumpkinGameMode::from(
+            join_info
+                .state
+                .as_ref()
+                .map_or(join_info.config.default_gamemode, |state| state.game_mode),
+        );
+        let (dimension, center) = location.as_ref().map_or_else(
+            || (OVERWORLD.to_string(), SPAWN_CHUNK),
+            |location| (location.dimension.clone(), location.column()),
+        );
+        let (chunks, chunk_results) = ChunkSender::new(
+            join_info.world.clone(),
+            dimension.clone(),
+            center,
+            client_view_distance.min(join_info.config.view_distance),
+            tracker.clone(),
+        );
+        let chat = ChatValidator::new(profile.id);
+        let play_actor = PlayActor {
+            id,
+            client_address,
+            framer,
+            server,
+            tracker: tracker.clone(),
+            profile,
+            session,
+            client_view_distance,
+            join_info,
+            pending_keep_alive: None,
+            chunks,
+            chunk_results,
+            location: location.unwrap_or(Location {
+                dimension,
+                position: Vector3::new(0.0, 0.0, 0.0),
+                yaw: 0.0,
+                pitch: 0.0,
+                on_ground: false,
+            }),
+            moved: false,
+            game_mode,
+            movement: MovementChecker::default(),
+            inventory_state_id: 0,
+            chat,
+            chat_senders: HashSet::new(),
+            chat_index: 0,
+            next_teleport_id: 0,
+            awaiting_teleport: None,
+        };
+        tracker.spawn(play_actor.run());
+    }
+}
+
+impl Debug for PlayActor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PlayActor")
+            .field("id", &self.id)
+            // .field("client_address", &self.client_address)
+            .finish()
+    }
+}
+
+type MCSocket = mc_socket::MCSocket<TcpStream>;
+
+struct PlayActor {
+    id: usize,
+    client_address: SocketAddr,
+    framer: Framed<MCSocket, MCCodec>,
+    server: Server,
+    tracker: TaskTracker,
+    profile: GameProfile,
+    session: SessionGuard,
+    client_view_distance: u8,
+    join_info: JoinInfo,
+    /// Id of the last keep alive sent, until the client answers it
+    pending_keep_alive: Option<i64>,
+    chunks: ChunkSender,
+    chunk_results: mpsc::Receiver<ChunkResult>,
+    location: Location,
+    /// Whether the player moved since their location was last saved
+    moved: bool,
+    game_mode: PumpkinGameMode,
+    movement: MovementChecker,
+    /// Last state id of the inventory sent to the client
+    inventory_state_id: i32,
+    chat: ChatValidator,
+    /// Players whose chat session the client knows, so it can verify their messages
+    chat_senders: HashSet<Uuid>,
+    /// Signed messages sent to the client so far
+    chat_index: i32,
+    next_teleport_id: i32,
+    /// Moves are ignored until the client confirms this teleport
+    awaiting_teleport: Option<i32>,
+}
+
+impl StreamActor<Framed<MCSocket, MCCodec>> for PlayActor {
+    fn get_stream(&mut self) -> &mut Framed<MCSocket, MCCodec> {
+        &mut self.framer
+    }
+}
+
+impl PlayActor {
+    async fn run(mut self) {
+        log::debug!("{self:?} initialized");
+
+        if !self.join().await {
+            return self.shutdown().await;
+        }
+
+        let mut keep_alive = interval(KEEP_ALIVE_INTERVAL);
+        keep_alive.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut chunk_tick = interval(CHUNK_TICK);
+        chunk_tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut save_tick = interval(SAVE_INTERVAL);
+        save_tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            let packet = select! {
+                biased;
+                message = self.session.recv() => match message {
+                    Some(message) => {
+                        if self.handle_message(message).await {
+                            continue;
+                        }
+                        break;
+                    }
+                    None => break,
+                },
+                _ = keep_alive.tick() => {
+                    if self.send_keep_alive().await {
+                        continue;
+                    }
+                    break;
+                }
+                Some(result) = self.chunk_results.recv() => {
+                    self.chunks.loaded(result);
+                    self.chunks.request();
+                    continue;
+                }
+                _ = chunk_tick.tick() => {
+                    if self.send_chunks().await {
+                        continue;
+                    }
+                    break;
+                }
+                _ = save_tick.tick() => {
+                    self.save_location().await;
+                    continue;
+                }
+                frame = self.framer.next() => match frame {
+                    Some(Ok(packet)) => packet,
+                    Some(Err(e)) => {
+                        log::error!("{self:?} stream error : {e:?}");
+                        break;
+                    }
+                    None => {
+                        log::debug!("{self:?} stream closed");
+                        break;
+                    }
+                },
+            };
+
+            if !self.handle_packet(packet).await {
+                break;
+            }
+        }
+
+        // Before the session is dropped, which ends it in the module
+        self.save_location().await;
+        self.shutdown().await
+    }
+
+    async fn join(&mut self) -> bool {
+        let config = &self.join_info.config;
+        let state = self.join_info.state.clone();
+        let game_mode = self.game_mode;
+        let dimension = ResourceLocation::vanilla(
+            self.location
+                .dimension
+                .strip_prefix("minecraft:")
+                .unwrap_or(&self.location.dimension),
+        );
+        let login = CLogin::new(
+            self.join_info.entity_id as i32,
+            config.hardcore,
+            &[
+                ResourceLocation::vanilla("overworld"),
+                ResourceLocation::vanilla("the_nether"),
+                ResourceLocation::vanilla("the_end"),
+            ],
+            VarInt(config.max_players as i32),
+            VarInt(i32::from(config.view_distance)),
+            VarInt(i32::from(config.simulation_distance)),
+            false,
+            true,
+            false,
+            VarInt(dimension_type(&self.location.dimension)),
+            dimension,
+            0,
+            game_mode as u8,
+            -1,
+            false,
+            false,
+            None,
+            VarInt(0),
+            VarInt(63),
+            self.secure_chat(),
+        );
+        if !self.send(login).await {
+            return false;
+        }
+        log::info!("{self:?} joined as {}", self.profile.name);
+
+        if let Some(state) = &state {
+            if !self
+                .send(CSetHealth::new(
+                    state.health,
+                    VarInt(state.food),
+                    state.saturation,
+                ))
+                .await
+                || !self
+                    .send(CSetExperience::new(
+                        state.xp_progress,
+                        VarInt(state.xp_level),
+                        VarInt(state.xp_total),
+                    ))
+                    .await
+            {
+                return false;
+            }
+        }
+        let held_slot = VarInt(i32::from(self.join_info.held_slot));
+        if !self.send(CSetHeldSlot { slot: held_slot }).await || !self.send_inventory().await {
+            return false;
+        }
+        for (name, session) in std::mem::take(&mut self.join_info.chat_sessions) {
+            if !self.show_chat_session(&name, &session).await {
+                return false;
+            }
+        }
+
+        if !self.send_op_level(self.join_info.op_level).await
+            || !self.send_commands().await
+            || !self
+                .send(CGameEvent {
+                    event: START_WAITING_FOR_CHUNKS,
+                    value: 0.0,
+                })
+                .await
+        {
+            return false;
+        }
+
+        let (position, yaw, pitch) = match state.as_ref().filter(|state| state.placed) {
+            Some(_) => (
+                self.location.position,
+                self.location.yaw,
+                self.location.pitch,
+            ),
+            None => (self.spawn_position().await, 0.0, 0.0),
+        };
+        let (chunk_x, chunk_z) = self.chunks.center();
+        if !self
+            .send(CSetChunkCacheCenter {
+                chunk_x: VarInt(chunk_x),
+                chunk_z: VarInt(chunk_z),
+            })
+            .await
+        {
+            return false;
+        }
+        self.chunks.request();
+        self.teleport(position, yaw, pitch).await
+    }
+
+    /// On top of the highest block in the middle of the spawn chunk.
+    async fn spawn_position(&self) -> Vector3<f64> {
+        let (x, z) = SPAWN_CHUNK;
+        let world = &self.join_info.world;
+        let position = ChunkPos::new(OVERWORLD, x, z);
+        let chunk = match world.watch(&position).await {
+            Ok(_watch) => world.load(&position).await,
+            Err(e) => Err(e),
+        };
+        let y = match chunk {
+            Ok(chunk) => chunk.surface_height(8, 8),
+            Err(e) => {
+                log::error!("{self:?} failed to load the spawn chunk : {e}");
+                None
+            }
+        };
+        Vector3::new(
+            f64::from(x * 16) + 8.5,
+            f64::from(y.unwrap_or(FALLBACK_SPAWN_Y)),
+            f64::from(z * 16) + 8.5,
+        )
+    }
+
+    async fn teleport(&mut self, position: Vector3<f64>, yaw: f32, pitch: f32) -> bool {
+        self.next_teleport_id = self.next_teleport_id.wrapping_add(1);
+        self.awaiting_teleport = Some(self.next_teleport_id);
+        self.location.position = position;
+        self.location.yaw = yaw;
+        self.location.pitch = pitch;
+        self.moved = true;
+        self.movement.reset();
+        self.send(CPlayerPosition {
+            teleport_id: VarInt(self.next_teleport_id),
+            x: position.x,
+            y: position.y,
+            z: position.z,
+            velocity_x: 0.0,
+            velocity_y: 0.0,
+            velocity_z: 0.0,
+            yaw,
+            pitch,
+            flags: 0,
+        })
+        .await
+    }
+
+    /// Follows the player, loading the chunks they get close to and unloading the ones they left.
+    /// Only what the packet carries changes : a position, a rotation, or neither.
+    async fn move_to(
+        &mut self,
+        position: Option<Vector3<f64>>,
+        rotation: Option<(f32, f32)>,
+        flags: u8,
+    ) -> bool {
+        // Sent before the client got the teleport, so from where it was
+        if self.awaiting_teleport.is_some() {
+            return true;
+        }
+        let on_ground = flags & ON_GROUND != 0;
+        if let Some(position) = position {
+            let checked = self.movement.check(
+                &self.join_info.world,
+                self.game_mode,
+                &self.location,
+                position,
+            );
+            if let Err((violation, detail)) = checked {
+                return self.set_back(violation, detail).await;
+            }
+        }
+        if let Some((yaw, pitch)) = rotation {
+            self.location.yaw = yaw;
+            self.location.pitch = pitch;
+        }
+        self.location.on_ground = on_ground;
+        self.moved = true;
+        let Some(position) = position else {
+            return true;
+        };
+        self.location.position = position;
+        let column = self.location.column();
+        let Some(forget) = self.chunks.set_center(column) else {
+            return true;
+        };
+        if !self
+            .send(CSetChunkCacheCenter {
+                chunk_x: VarInt(column.0),
+                chunk_z: VarInt(column.1),
+            })
+            .await
+        {
+            return false;
+        }
+        self.chunks.request();
+        self.forget_chunks(forget).await
+    }
+
+    /// Sends the player back where they last were, as the move they sent is refused.
+    async fn set_back(&mut self, violation: Violation, detail: String) -> bool {
+        log::info!("{self:?} set back : {violation:?} ({detail})",);
+        let _ = self
+            .server
+            .send(ServerMessage::MovementViolation {
+                profile_id: self.session.profile_id(),
+                connection_id: self.session.connection_id(),
+                violation,
+                detail,
+            })
+            .await;
+        let (position, yaw, pitch) = (
+            self.location.position,
+            self.location.yaw,
+            self.location.pitch,
+        );
+        self.teleport(position, yaw, pitch).await
+    }
+
+    async fn forget_chunks(&mut self, columns: Vec<Column>) -> bool {
+        for (chunk_x, chunk_z) in columns {
+            if !self.send(CForgetLevelChunk { chunk_z, chunk_x }).await {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Sends the chunks the client can take this tick, as one batch.
+    async fn send_chunks(&mut self) -> bool {
+        let batch = self.chunks.next_batch();
+        if batch.is_empty() {
+            return true;
+        }
+        let batch_size = VarInt(batch.len() as i32);
+        if !self.send(CChunkBatchStart).await {
+            return false;
+        }
+        for packet in batch {
+            if !self
+                .send_bytes(Ok(packet), PLAY_LEVEL_CHUNK_WITH_LIGHT)
+                .await
+            {
+                return false;
+            }
+        }
+        self.send(CChunkBatchFinished { batch_size }).await
+    }
+
+    /// Returns whether the connection should be kept open
+    async fn handle_message(&mut self, message: ConnectionMessage) -> bool {
+        match message {
+            ConnectionMessage::Kick { reason } => {
+                self.disconnect(disconnect_message(&reason)).await;
+                false
+            }
+            ConnectionMessage::SetOpLevel(level) => {
+                self.join_info.op_level = level;
+                self.send_op_level(level).await && self.send_commands().await
+            }
+            ConnectionMessage::ConfigUpdated(config) => self.apply_config(*config).await,
+            ConnectionMessage::InventoryChanged(inventory) => {
+                self.join_info.inventory = Some(*inventory);
+                self.send_inventory().await
+            }
+            ConnectionMessage::Chat(message) => self.show_chat(&message).await,
+            ConnectionMessage::Feedback(message) => self.send_feedback(&message).await,
+            ConnectionMessage::CommandFeedback(message) => {
+                self.send(CSystemChatMessage::new(
+                    &TextComponent::text(message),
+                    false,
+                ))
+                .await
+            }
+            ConnectionMessage::ChatSessionStarted { name, session } => {
+                self.show_chat_session(&name, &session).await
+            }
+            ConnectionMessage::ChatSessionEnded(profile_id) => {
+                if !self.chat_senders.remove(&profile_id) {
+                    return true;
+                }
+                self.send_bytes(Ok(player_info_remove(profile_id)), PLAY_PLAYER_INFO_REMOVE)
+                    .await
+            }
+        }
+    }
+
+    /// Tells the client about the settings that changed since it joined or last got an update.
+    async fn apply_config(&mut self, config: BasicConfiguration) -> bool {
+        let old = std::mem::replace(&mut self.join_info.config, config);
+        let difficulty = self.join_info.config.default_difficulty.clone();
+        let view_distance = self.join_info.config.view_distance;
+        let simulation_distance = self.join_info.config.simulation_distance;
+
+        if old.default_difficulty != difficulty
+            && !self
+                .send(CChangeDifficulty {
+                    difficulty: PumpkinDifficulty::from(difficulty) as u8,
+                    locked: false,
+                })
+                .await
+        {
+            return false;
+        }
+        if old.view_distance != view_distance {
+            if !self
+                .send(CSetChunkCacheRadius {
+                    radius: VarInt(i32::from(view_distance)),
+                })
+                .await
+            {
+                return false;
+            }
+            let forget = self
+                .chunks
+                .set_view_distance(self.client_view_distance.min(view_distance));
+            self.chunks.request();
+            if !self.forget_chunks(forget).await {
+                return false;
+            }
+        }
+        if old.simulation_distance != simulation_distance
+            && !self
+                .send(CSetSimulationDistance {
+                    distance: VarInt(i32::from(simulation_distance)),
+                })
+                .await
+        {
+            return false;
+        }
+
+        true
+    }
+
+    /// Returns whether the connection should be kept open
+    async fn handle_packet(&mut self, packet: RawPacket) -> bool {
+        match packet.id {
+            id if id == SKeepAlive::PACKET_ID => match self.decode::<SKeepAlive>(packet) {
+                Some(answer) => {
+                    if self.pending_keep_alive == Some(answer.keep_alive_id) {
+                        self.pending_keep_alive = None;
+                    }
+                    true
+                }
+                None => false,
+            },
+            id if id == SChunkBatchReceived::PACKET_ID => {
+                match self.decode::<SChunkBatchReceived>(packet) {
+                    Some(received) => {
+                        self.chunks.acknowledge(received.chunks_per_tick);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            id if id == SAcceptTeleportation::PACKET_ID => {
+                match self.decode::<SAcceptTeleportation>(packet) {
+                    Some(accepted) => {
+                        if self.awaiting_teleport == Some(accepted.teleport_id.0) {
+                            self.awaiting_teleport = None;
+                        }
+                        true
+                    }
+                    None => false,
+                }
+            }
+            id if id == SMovePlayerPos::PACKET_ID => match self.decode::<SMovePlayerPos>(packet) {
+                Some(moved) => {
+                    let position = Vector3::new(moved.x, moved.y, moved.z);
+                    self.move_to(Some(position), None, moved.flags).await
+                }
+                None => false,
+            },
+            id if id == SMovePlayerPosRot::PACKET_ID => {
+                match self.decode::<SMovePlayerPosRot>(packet) {
+                    Some(moved) => {
+                        let position = Vector3::new(moved.x, moved.y, moved.z);
+                        let rotation = (moved.yaw, moved.pitch);
+                        self.move_to(Some(position), Some(rotation), moved.flags)
+                            .await
+                    }
+                    None => false,
+                }
+            }
+            id if id == SMovePlayerRot::PACKET_ID => match self.decode::<SMovePlayerRot>(packet) {
+                Some(moved) => {
+                    self.move_to(None, Some((moved.yaw, moved.pitch)), moved.flags)
+                        .await
+                }
+                None => false,
+            },
+            id if id == SMovePlayerStatusOnly::PACKET_ID => {
+                match self.decode::<SMovePlayerStatusOnly>(packet) {
+                    Some(moved) => self.move_to(None, None, moved.flags).await,
+                    None => false,
+                }
+            }
+            id if id == SContainerClick::PACKET_ID => {
+                match self.decode::<SContainerClick>(packet) {
+                    Some(click) if click.window_id.0 == PLAYER_INVENTORY => {
+                        let Ok(mode) = u8::try_from(click.mode.0) else {
+                            return self.send_inventory().await;
+                        };
+                        self.inventory_action(InventoryAction::Click {
+                            slot: click.slot,
+                            button: click.button,
+                            mode,
+                        })
+                        .await;
+                        true
+                    }
+                    Some(_) => true,
+                    None => false,
+                }
+            }
+            id if id == SContainerClose::PACKET_ID => {
+                match self.decode::<SContainerClose>(packet) {
+                    Some(close) => {
+                        if close.window_id.0 == PLAYER_INVENTORY {
+                            self.inventory_action(InventoryAction::Close).await;
+                        }
+                        true
+                    }
+                    None => false,
+                }
+            }
+            id if id == SSetCarriedItem::PACKET_ID => {
+                match self.decode::<SSetCarriedItem>(packet) {
+                    Some(carried) => match u8::try_from(carried.slot) {
+                        Ok(slot) if slot < 9 => {
+                            self.join_info.held_slot = slot;
+                            self.inventory_action(InventoryAction::SetHeldSlot(slot))
+                                .await;
+                            true
+                        }
+                        _ => {
+                            log::info!("{self:?} selected invalid slot {}", carried.slot);
+                            true
+                        }
+                    },
+                    None => false,
+                }
+            }
+            // Decoded by hand, as the stack it ends with carries raw data components
+            id if id == PLAY_SET_CREATIVE_MODE_SLOT => {
+                let mut payload = packet.payload;
+                if payload.remaining() < 2 {
+                    log::error!("{self:?} sent a truncated creative slot");
+                    return false;
+                }
+                let slot = payload.get_i16();
+                match read_last_slot(&mut payload) {
+                    Ok(stack) => {
+                        self.inventory_action(InventoryAction::SetCreativeSlot { slot, stack })
+                            .await;
+                        true
+                    }
+                    Err(e) => {
+                        log::error!("{self:?} sent an invalid creative slot : {e}");
+                        false
+                    }
+                }
+            }
+            id if id == PLAY_CHAT => {
+                let mut payload = packet.payload;
+                match SChat::read(&mut payload) {
+                    Ok(chat) => self.chat(chat).await,
+                    Err(e) => {
+                        log::error!("{self:?} sent an invalid chat message : {e}");
+                        false
+                    }
+                }
+            }
+            id if id == PLAY_CHAT_SESSION_UPDATE => {
+                let mut payload = packet.payload;
+                match SChatSessionUpdate::read(&mut payload) {
+                    Ok(update) => self.start_chat_session(update).await,
+                    Err(e) => {
+                        log::error!("{self:?} sent an invalid chat session : {e}");
+                        false
+                    }
+                }
+            }
+            id if id == SChatAck::PACKET_ID => match self.decode::<SChatAck>(packet) {
+                Some(ack) => {
+                    if !self.secure_chat() {
+                        return true;
+                    }
+                    if let Err(e) = self.chat.acknowledge(ack.offset.0) {
+                        log::info!("{self:?} failed chat validation : {e}");
+                        self.disconnect(TextComponent::translate(CHAT_VALIDATION_FAILED, []))
+                            .await;
+                        return false;
+                    }
+                    true
+                }
+                None => false,
+            },
+            id if id == SChatCommand::PACKET_ID => match self.decode::<SChatCommand>(packet) {
+                Some(command) => {
+                    let _ = self
+                        .server
+                        .send(ServerMessage::RunCommand {
+                            profile_id: self.session.profile_id(),
+                            connection_id: self.session.connection_id(),
+                            command: command.command,
+                        })
+                        .await;
+                    true
+                }
+                None => false,
+            },
+            id if id == SCommandSuggestion::PACKET_ID => {
+                match self.decode::<SCommandSuggestion>(packet) {
+                    Some(request) => self.suggest(request).await,
+                    None => false,
+                }
+            }
+            id => {
+                log::trace!("{self:?} ignoring packet {id}");
+                true
+            }
+        }
+    }
+
+    /// Sends the whole inventory as the module last stored it, replacing what the client predicted.
+    async fn send_inventory(&mut self) -> bool {
+        // Wrapped as vanilla does, the client echoes it back with its clicks
+        self.inventory_state_id = (self.inventory_state_id + 1) & 0x7FFF;
+        let (mut slots, carried) = match &self.join_info.inventory {
+            Some(inventory) => (inventory.slots.clone(), inventory.carried.clone()),
+            None => (Vec::new(), None),
+        };
+        slots.resize(PLAYER_INVENTORY_SLOTS, None);
+        let packet = container_content(
+            PLAYER_INVENTORY,
+            self.inventory_state_id,
+            &slots,
+            carried.as_ref(),
+        );
+        self.send_bytes(Ok(packet), PLAY_CONTAINER_SET_CONTENT)
+            .await
+    }
+
+    async fn inventory_action(&mut self, action: InventoryAction) {
+        let _ = self
+            .server
+            .send(ServerMessage::InventoryAction {
+                profile_id: self.session.profile_id(),
+                connection_id: self.session.connection_id(),
+                action,
+            })
+            .await;
+    }
+
+    /// Sends the location to the server actor if the player moved since it was last saved.
+    async fn save_location(&mut self) {
+        if !self.moved {
+            return;
+        }
+        self.moved = false;
+        let _ = self
+            .server
+            .send(ServerMessage::PlayerMoved {
+                profile_id: self.session.profile_id(),
+                connection_id: self.session.connection_id(),
+                location: self.location.clone(),
+            })
+            .await;
+    }
+
+    async fn send_keep_alive(&mut self) -> bool {
+        if self.pending_keep_alive.is_some() {
+            log::info!("{self:?} did not answer keep alive in time");
+            self.disconnect(TextComponent::translate("disconnect.timeout", []))
+                .await;
+            return false;
+        }
+
+        let id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_millis() as i64);
+        self.pending_keep_alive = Some(id);
+        self.send(CKeepAlive::new(id)).await
+    }
+
+    /// Lets the client know which commands it may see, see [OP_LEVEL_STATUS]
+    async fn send_op_level(&mut self, level: PermissionLvl) -> bool {
+        let level = PumpkinPermissionLvl::from(level) as i8;
+        self.send(CEntityStatus::new(
+            self.join_info.entity_id as i32,
+            OP_LEVEL_STATUS + level,
+        ))
+        .await
+    }
+
+    /// Sends the commands the player's level allows, the client neither suggests nor runs others.
+    async fn send_commands(&mut self) -> bool {
+        let level = PumpkinPermissionLvl::from(self.join_info.op_level) as i8;
+        let tree = CommandTree::new(
+            self.join_info
+                .commands
+                .iter()
+                .filter(|command| PumpkinPermissionLvl::from(command.level) as i8 <= level),
+        );
+        self.send_bytes(Ok(tree.encode()), PLAY_COMMANDS).await
+    }
+
+    /// Only player arguments ask the server, so the word being typed is completed with the names
+    /// of online players.
+    async fn suggest(&mut self, request: SCommandSuggestion) -> bool {
+        let start = request.text.rfind(' ').map_or(0, |space| space + 1);
+        let prefix = request.text[start..].to_string();
+        let names = match self
+            .server
+            .ask(|reply_to| ServerMessage::SuggestPlayers {
+                prefix: prefix.clone(),
+                reply_to,
+            })
+            .await
+        {
+            Ok(recv) => recv.await.unwrap_or_default(),
+            // The server is going away
+            Err(_) => return false,
+        };
+        // Positions count UTF-16 units, as Java strings do
+        let packet = command_suggestions(
+            request.id.0,
+            request.text[..start].encode_utf16().count(),
+            prefix.encode_utf16().count(),
+            &names,
+        );
+        self.send_bytes(Ok(packet), PLAY_COMMAND_SUGGESTIONS).await
+    }
+
+    /// Shown as vanilla formats unsigned messages, `<name> message`.
+    async fn show_chat(&mut self, message: &ChatMessage) -> bool {
+        let sender = Uuid::from_u128(message.sender);
+        if let Some(signed) = &message.signed {
+            if self.secure_chat() && self.chat_senders.contains(&sender) {
+                let packet = PlayerChat {
+                    sender,
+                    sender_name: &message.sender_name,
+                    index: signed.index,
+                    signature: &signed.signature,
+                    message: &message.content,
+                    timestamp: signed.timestamp,
+                    salt: signed.salt,
+                    last_seen: &signed.last_seen,
+                }
+                .encode(self.chat_index);
+                self.chat_index += 1;
+                if let Err(reason) = self.chat.track(&signed.signature) {
+                    log::info!("{self:?} left too many chat messages unacknowledged");
+                    self.disconnect(TextComponent::translate(reason, [])).await;
+                    return false;
+                }
+                return self.send_bytes(Ok(packet), PLAY_PLAYER_CHAT).await;
+            }
+        }
+
+        let content = TextComponent::translate(
+            "chat.type.text",
+            [
+                TextComponent::text(message.sender_name.clone()),
+                TextComponent::text(message.content.clone()),
+            ],
+        );
+        self.send(CSystemChatMessage::new(&content, false)).await
+    }
+
+    /// Signs and checks what the player said while chat reports are allowed, then hands it to the
+    /// module.
+    async fn chat(&mut self, chat: SChat) -> bool {
+        let signed = match self.chat.accept(&chat, self.secure_chat()) {
+            Ok(signed) => signed,
+            Err(ChatError::Refused(reason)) => return self.send_feedback(reason).await,
+            Err(ChatError::Disconnect(reason)) => {
+                log::info!("{self:?} sent a chat message failing validation : {reason}");
+                self.disconnect(TextComponent::translate(reason, [])).await;
+                return false;
+            }
+        };
+        let _ = self
+            .server
+            .send(ServerMessage::SendChat {
+                profile_id: self.session.profile_id(),
+                connection_id: self.session.connection_id(),
+                content: chat.message,
+                signed,
+            })
+            .await;
+        true
+    }
+
+    /// Has the server actor check the key was issued by Mojang before trusting it.
+    async fn start_chat_session(&mut self, update: SChatSessionUpdate) -> bool {
+        if !self.secure_chat() {
+            return true;
+        }
+        let verified = match self
+            .server
+            .ask(|reply_to| ServerMessage::StartChatSession {
+                profile_id: self.session.profile_id(),
+                connection_id: self.session.connection_id(),
+                update: Box::new(update.clone()),
+                reply_to,
+            })
+            .await
+        {
+            Ok(recv) => recv.await.ok(),
+            Err(_) => None,
+        };
+        let reason = match verified {
+            Some(Ok(())) => match self.chat.set_session(&update) {
+                Ok(()) => return true,
+                Err(reason) => reason,
+            },
+            Some(Err(reason)) => reason,
+            // The server is going away
+            None => return false,
+        };
+        log::info!("{self:?} sent a chat session failing validation : {reason}");
+        self.disconnect(TextComponent::translate(reason, [])).await;
+        false
+    }
+
+    /// Lets the client verify the messages signed with the session.
+    async fn show_chat_session(&mut self, name: &str, session: &ChatSession) -> bool {
+        if !self.secure_chat() {
+            return true;
+        }
+        let profile_id = Uuid::from_u128(session.profile_id);
+        let packet = player_info_chat_session(
+            profile_id,
+            name,
+            Uuid::from_u128(session.session_id),
+            session.expires_at.to_micros_since_unix_epoch() / 1000,
+            &session.public_key,
+            &session.key_signature,
+        );
+        self.chat_senders.insert(profile_id);
+        self.send_bytes(Ok(packet), PLAY_PLAYER_INFO_UPDATE).await
+    }
+
+    async fn send_feedback(&mut self, message: &str) -> bool {
+        let feedback = disconnect_message(message).color_named(NamedColor::Red);
+        self.send(CSystemChatMessage::new(&feedback, false)).await
+    }
+
+    /// Messages are signed and relayed as such only while chat reports are allowed, which needs
+    /// Mojang to issue the keys.
+    fn secure_chat(&self) -> bool {
+        self.join_info.config.allow_chat_reports && self.join_info.config.online_mode
+    }
+
+    async fn disconnect(&mut self, reason: TextComponent) -> bool {
+        self.send(CPlayDisconnect::new(&reason)).await
+    }
+}
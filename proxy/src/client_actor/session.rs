@@ -0,0 +1,129 @@
+use crate::actor_ref::ActorRef;
+use crate::err::{SendError, TrySendError};
+use crate::module_bindings::{
+    BasicConfiguration, ChatMessage, ChatSession, PermissionLvl, PlayerInventory,
+};
+use crate::server_actor::actor::{Server, ServerMessage};
+use pumpkin_util::text::TextComponent;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub enum ConnectionMessage {
+    /// Disconnect the player, `reason` being a translation key or plain text.
+    Kick {
+        reason: String,
+    },
+    SetOpLevel(PermissionLvl),
+    /// Settings players must be told about changed
+    ConfigUpdated(Box<BasicConfiguration>),
+    /// The module changed the inventory, or refused a change the client predicted
+    InventoryChanged(Box<PlayerInventory>),
+    /// Someone said something, possibly through another proxy
+    Chat(Box<ChatMessage>),
+    /// Shown to the player only, a translation key or plain text
+    Feedback(String),
+    /// What a command the player ran did
+    CommandFeedback(String),
+    /// A player started signing their messages with a new key
+    ChatSessionStarted {
+        name: String,
+        session: Box<ChatSession>,
+    },
+    ChatSessionEnded(Uuid),
+}
+
+/// Handle the server uses to reach whichever actor currently owns an authenticated connection.
+#[derive(Clone, Debug)]
+pub struct Connection {
+    profile_id: Uuid,
+    sender: mpsc::Sender<ConnectionMessage>,
+}
+
+impl Connection {
+    pub fn profile_id(&self) -> Uuid {
+        self.profile_id
+    }
+}
+
+impl ActorRef<ConnectionMessage> for Connection {
+    async fn send(&self, msg: ConnectionMessage) -> Result<(), SendError> {
+        self.sender.send(msg).await.map_err(SendError::from)
+    }
+
+    fn try_send(&self, msg: ConnectionMessage) -> Result<(), TrySendError> {
+        self.sender.try_send(msg).map_err(TrySendError::from)
+    }
+}
+
+/// Held by whichever actor currently owns an authenticated connection, along with its mailbox.
+///
+/// Dropping it, however the connection ended, tells the server the player left so the module can
+/// mark them offline.
+pub struct SessionGuard {
+    profile_id: Uuid,
+    connection_id: u64,
+    server: Server,
+    mailbox: mpsc::Receiver<ConnectionMessage>,
+}
+
+impl SessionGuard {
+    /// Creates the session along with the [Connection] the server reaches it through.
+    pub fn new(profile_id: Uuid, connection_id: u64, server: Server) -> (Self, Connection) {
+        let (sender, mailbox) = mpsc::channel(16);
+        let session = Self {
+            profile_id,
+            connection_id,
+            server,
+            mailbox,
+        };
+        (session, Connection { profile_id, sender })
+    }
+
+    pub fn profile_id(&self) -> Uuid {
+        self.profile_id
+    }
+
+    pub fn connection_id(&self) -> u64 {
+        self.connection_id
+    }
+
+    pub fn recv(&mut self) -> impl Future<Output = Option<ConnectionMessage>> + '_ {
+        self.mailbox.recv()
+    }
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        let leave = ServerMessage::PlayerLeave {
+            profile_id: self.profile_id,
+            connection_id: self.connection_id,
+        };
+        // Waits for room in the server mailbox, a lost leave would keep the player online
+        match Handle::try_current() {
+            Ok(runtime) => {
+                let server = self.server.clone();
+                runtime.spawn(async move {
+                    let _ = server.send(leave).await;
+                });
+            }
+            Err(_) => {
+                let _ = self.server.try_send(leave);
+            }
+        }
+    }
+}
+
+/// The module disconnects players or refuses their messages and commands with translation keys,
+/// anything else is an internal error message.
+pub fn disconnect_message(reason: &str) -> TextComponent {
+    if reason.starts_with("multiplayer.disconnect.")
+        || reason.starts_with("chat.disabled.")
+        || reason.starts_with("command.")
+    {
+        TextComponent::translate(reason.to_string(), [])
+    } else {
+        TextComponent::text(reason.to_string())
+    }
+}
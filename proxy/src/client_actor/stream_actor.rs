@@ -78,6 +78,17 @@ where
         self.read_custom::<_, P, P>(P::read)
     }
 
+    /// Decodes a frame already known to be a `P`, e.g. when dispatching on [RawPacket::id].
+    fn decode<P: Packet + ServerPacket>(&self, packet: RawPacket) -> Option<P> {
+        match P::read(packet.payload.reader()) {
+            Ok(decoded) => Some(decoded),
+            Err(e) => {
+                log::error!("{self:?} failed to read packet {} : {e:?}", packet.id);
+                None
+            }
+        }
+    }
+
     fn read_empty<P: Packet>(&mut self) -> impl Future<Output = Option<()>> {
         self.read_custom::<_, P, ()>(|_| Ok(()))
     }
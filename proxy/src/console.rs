@@ -0,0 +1,169 @@
+use crate::actor_ref::ActorRef;
+use crate::database::Database;
+use crate::server_actor::actor::{Server, ServerMessage};
+use crate::world::World;
+use rustyline_async::{Readline, ReadlineEvent, SharedWriter};
+use std::io::Write;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+/// Entries shown by `audit` when no count is given.
+const DEFAULT_AUDIT_ENTRIES: u32 = 10;
+
+/// Players shown by `violations`.
+const SHOWN_VIOLATIONS: usize = 10;
+
+const HELP: &str = "Commands :
+  audit [count]  show the most recent administrative changes
+  cache          show how the chunk packet cache performs
+  violations     show the players who were set back the most
+  workers        show how busy the chunk worker threads are
+  stop           stop the proxy
+  help           show this message";
+
+/// Reads commands from the terminal until the proxy stops.
+///
+/// The terminal no longer turns Ctrl-C into a signal while the console runs, so Ctrl-C and
+/// Ctrl-D stop the proxy as well.
+pub async fn run(database: Database, server: Server, world: World) {
+    let (mut readline, mut stdout) = match Readline::new("> ".into()) {
+        Ok(console) => console,
+        Err(e) => {
+            log::warn!("Console unavailable : {e}");
+            return;
+        }
+    };
+
+    loop {
+        match readline.readline().await {
+            Ok(ReadlineEvent::Line(line)) => {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                readline.add_history_entry(line.to_string());
+                if !execute(line, &database, &world, &mut stdout).await {
+                    break;
+                }
+            }
+            Ok(ReadlineEvent::Eof | ReadlineEvent::Interrupted) => break,
+            Err(e) => {
+                log::error!("Console error : {e}");
+                return;
+            }
+        }
+    }
+
+    let _ = readline.flush();
+    let _ = server.send(ServerMessage::StopListening).await;
+}
+
+/// Returns whether the console should keep running
+async fn execute(line: &str, database: &Database, world: &World, out: &mut SharedWriter) -> bool {
+    let mut args = line.split_whitespace();
+    let result = match args.next() {
+        Some("audit") => match args.next().map(str::parse::<u32>) {
+            None => print_audit_log(database, DEFAULT_AUDIT_ENTRIES, out).await,
+            Some(Ok(count)) => print_audit_log(database, count, out).await,
+            Some(Err(_)) => writeln!(out, "Usage : audit [count]"),
+        },
+        Some("cache") => print_cache_stats(world, out),
+        Some("violations") => print_violations(database, out),
+        Some("workers") => {
+            let stats = world.workers().stats();
+            writeln!(
+                out,
+                "{}/{} jobs queued or running on {} threads",
+                stats.jobs, stats.capacity, stats.threads
+            )
+        }
+        Some("stop") => return false,
+        Some("help") => writeln!(out, "{HELP}"),
+        Some(command) => writeln!(out, "Unknown command {command:?}, try help"),
+        None => Ok(()),
+    };
+    if let Err(e) = result {
+        log::error!("Failed to write to the console : {e}");
+    }
+    true
+}
+
+async fn print_audit_log(
+    database: &Database,
+    count: u32,
+    out: &mut SharedWriter,
+) -> std::io::Result<()> {
+    let (reply_to, outcome) = oneshot::channel();
+    database.request_audit_log(count, reply_to);
+    match outcome.await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => return writeln!(out, "Could not read the audit log : {e}"),
+        Err(_) => return writeln!(out, "Could not read the audit log : request superseded"),
+    }
+
+    let entries = database.audit_log();
+    if entries.is_empty() {
+        return writeln!(out, "The audit log is empty");
+    }
+
+    // Oldest first, so the most recent entry ends up right above the prompt
+    for entry in entries.iter().rev() {
+        write!(
+            out,
+            "#{} {} {} by {}",
+            entry.id, entry.timestamp, entry.reducer, entry.caller
+        )?;
+        if let Some(issuer) = &entry.issuer {
+            write!(out, " (player {issuer})")?;
+        }
+        if let Some(target) = &entry.target {
+            write!(out, " on {target}")?;
+        }
+        writeln!(
+            out,
+            " : {} -> {}",
+            entry.old_value.as_deref().unwrap_or("-"),
+            entry.new_value.as_deref().unwrap_or("-")
+        )?;
+    }
+    Ok(())
+}
+
+fn print_cache_stats(world: &World, out: &mut SharedWriter) -> std::io::Result<()> {
+    let stats = world.cache().stats();
+    writeln!(
+        out,
+        "{}/{} chunk packets cached ({:.1} MiB), {} hits and {} misses ({:.1}% hit rate), \
+        {} evicted, {} invalidated",
+        stats.packets,
+        stats.capacity,
+        stats.bytes as f64 / (1024.0 * 1024.0),
+        stats.hits,
+        stats.misses,
+        stats.hit_rate() * 100.0,
+        stats.evictions,
+        stats.invalidations
+    )
+}
+
+fn print_violations(database: &Database, out: &mut SharedWriter) -> std::io::Result<()> {
+    let violations = database.movement_violations();
+    if violations.is_empty() {
+        return writeln!(out, "No movement violations");
+    }
+    for player in violations.iter().take(SHOWN_VIOLATIONS) {
+        writeln!(
+            out,
+            "{} : {} speed, {} teleport, {} flying, {} inside block, last {:?} ({}) at {}",
+            Uuid::from_u128(player.profile_id),
+            player.speed,
+            player.teleport,
+            player.flying,
+            player.inside_block,
+            player.last_violation,
+            player.last_detail,
+            player.last_at
+        )?;
+    }
+    Ok(())
+}
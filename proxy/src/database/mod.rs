@@ -0,0 +1,618 @@
+pub mod pending;
+pub mod registry;
+
+use crate::database::pending::{PendingCalls, ReducerOutcome};
+use crate::module_bindings::ReducerEventContext;
+use crate::module_bindings::{
+    acknowledge_command_feedback, acknowledge_kick, add_player, click_container, close_container,
+    move_player, player_join, player_leave, report_violation, request_audit_log, run_command,
+    send_chat, set_chat_session, set_creative_slot, set_held_slot, AuditEntry,
+    AuditExcerptTableAccess, BlockEntityTableAccess, ChatMessageTableAccess, ChatSession,
+    ChatSessionTableAccess, Chunk, ChunkSectionTableAccess, ChunkTableAccess, Command,
+    CommandFeedbackTableAccess, CommandTableAccess, DbConnection, HeldSlotTableAccess, ItemStack,
+    MovementViolationTableAccess, MovementViolations, OperatorTableAccess, PermissionLvl,
+    PlayerInventory, PlayerInventoryTableAccess, PlayerKickTableAccess, PlayerSessionTableAccess,
+    PlayerState, PlayerStateTableAccess, PlayerTableAccess, SignedChat, Violation,
+};
+use crate::protocol::chat::SChatSessionUpdate;
+use crate::server_actor::actor::{ServerMessage, ServerRelay};
+use crate::world::index::ChunkIndex;
+use crate::world::{encode, ChunkPos, Location, StoredChunk, AIR};
+use spacetimedb_sdk::{DbContext, Status, Table, TableWithPrimaryKey, Timestamp};
+use std::net::IpAddr;
+use std::ops::Deref;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+/// What a player did with their inventory, for the module to apply.
+#[derive(Debug)]
+pub enum InventoryAction {
+    Click {
+        slot: i16,
+        button: i8,
+        mode: u8,
+    },
+    /// Only allowed in creative mode
+    SetCreativeSlot {
+        slot: i16,
+        stack: Option<ItemStack>,
+    },
+    Close,
+    SetHeldSlot(u8),
+}
+
+/// Shared handle to the database connection, along with the reducer calls awaiting an outcome.
+#[derive(Clone)]
+pub struct Database {
+    connection: Arc<DbConnection>,
+    /// Pending joins, keyed by connection id
+    joins: PendingCalls<u64>,
+    /// Pending audit log request of the console
+    audit_requests: PendingCalls<()>,
+    chunks: ChunkIndex,
+}
+
+impl Database {
+    pub fn new(connection: Arc<DbConnection>) -> Self {
+        let database = Self {
+            chunks: ChunkIndex::follow(&connection),
+            connection,
+            joins: Default::default(),
+            audit_requests: Default::default(),
+        };
+        database.register_callbacks();
+        database
+    }
+
+    fn register_callbacks(&self) {
+        let joins = self.joins.clone();
+        self.connection.reducers.on_player_join(
+            move |ctx, _username, _profile_id, connection_id, _address| {
+                // Connection ids are only unique within a proxy
+                if ctx.try_identity() == Some(ctx.event.caller_identity) {
+                    joins.complete_with_status(connection_id, &ctx.event.status)
+                }
+            },
+        );
+        let audit_requests = self.audit_requests.clone();
+        self.connection
+            .reducers
+            .on_request_audit_log(move |ctx, _limit| {
+                if ctx.try_identity() == Some(ctx.event.caller_identity) {
+                    audit_requests.complete_with_status(&(), &ctx.event.status)
+                }
+            });
+    }
+
+    /// Forwards the kicks addressed to this proxy to the server actor.
+    pub fn watch_kicks(&self, server: ServerRelay) {
+        self.connection
+            .db
+            .player_kick()
+            .on_insert(move |ctx, kick| {
+                if ctx.try_identity() != Some(kick.proxy) {
+                    return;
+                }
+                server.send(ServerMessage::Kick {
+                    kick_id: kick.id,
+                    connection_id: kick.connection_id,
+                    reason: kick.reason.clone(),
+                });
+            });
+    }
+
+    /// Notifies the server actor of permission level changes, so players can be told about them.
+    pub fn watch_operators(&self, server: ServerRelay) {
+        let operators = self.connection.db.operator();
+        let on_insert = server.clone();
+        operators.on_insert(move |_ctx, op| {
+            on_insert.send(ServerMessage::OpLevelChanged {
+                profile_id: Uuid::from_u128(op.profile_id),
+                level: op.level,
+            });
+        });
+        let on_update = server.clone();
+        operators.on_update(move |_ctx, _old, op| {
+            on_update.send(ServerMessage::OpLevelChanged {
+                profile_id: Uuid::from_u128(op.profile_id),
+                level: op.level,
+            });
+        });
+        operators.on_delete(move |_ctx, op| {
+            server.send(ServerMessage::OpLevelChanged {
+                profile_id: Uuid::from_u128(op.profile_id),
+                level: PermissionLvl::Zero,
+            });
+        });
+    }
+
+    /// Forwards inventory changes to the server actor, for players to be told about them.
+    ///
+    /// Refused actions send the inventory as well, so clients drop what they predicted.
+    pub fn watch_inventories(&self, server: ServerRelay) {
+        let inventories = self.connection.db.player_inventory();
+        let on_insert = server.clone();
+        inventories.on_insert(move |_ctx, inventory| {
+            on_insert.send(ServerMessage::InventoryChanged {
+                profile_id: Uuid::from_u128(inventory.profile_id),
+                inventory: Box::new(inventory.clone()),
+            });
+        });
+        let on_update = server.clone();
+        inventories.on_update(move |_ctx, _old, inventory| {
+            on_update.send(ServerMessage::InventoryChanged {
+                profile_id: Uuid::from_u128(inventory.profile_id),
+                inventory: Box::new(inventory.clone()),
+            });
+        });
+
+        let resync = move |ctx: &ReducerEventContext, profile_id: &str| {
+            if ctx.try_identity() != Some(ctx.event.caller_identity)
+                || !matches!(ctx.event.status, Status::Failed(_))
+            {
+                return;
+            }
+            let Ok(profile_id) = Uuid::parse_str(profile_id) else {
+                return;
+            };
+            if let Some(inventory) = ctx
+                .db
+                .player_inventory()
+                .profile_id()
+                .find(&profile_id.as_u128())
+            {
+                server.send(ServerMessage::InventoryChanged {
+                    profile_id,
+                    inventory: Box::new(inventory),
+                });
+            }
+        };
+        let reducers = &self.connection.reducers;
+        let on_click = resync.clone();
+        reducers.on_click_container(
+            move |ctx, profile_id, _connection_id, _slot, _button, _mode| on_click(ctx, profile_id),
+        );
+        let on_creative = resync.clone();
+        reducers.on_set_creative_slot(move |ctx, profile_id, _connection_id, _slot, _stack| {
+            on_creative(ctx, profile_id)
+        });
+        reducers.on_close_container(move |ctx, profile_id, _connection_id| resync(ctx, profile_id));
+    }
+
+    /// Forwards every chat message to the server actor, for players of this proxy to see them.
+    ///
+    /// Players whose message was refused are told why.
+    pub fn watch_chat(&self, server: ServerRelay) {
+        let on_insert = server.clone();
+        self.connection
+            .db
+            .chat_message()
+            .on_insert(move |_ctx, message| {
+                on_insert.send(ServerMessage::Chat(Box::new(message.clone())));
+            });
+        self.connection.reducers.on_send_chat(
+            move |ctx, profile_id, _connection_id, _content, _signed| {
+                let Status::Failed(reason) = &ctx.event.status else {
+                    return;
+                };
+                if ctx.try_identity() != Some(ctx.event.caller_identity) {
+                    return;
+                }
+                if let Ok(profile_id) = Uuid::parse_str(profile_id) {
+                    server.send(ServerMessage::Feedback {
+                        profile_id,
+                        message: reason.to_string(),
+                    });
+                }
+            },
+        );
+    }
+
+    /// The module shares the message with every proxy through [Database::watch_chat].
+    pub fn send_chat(
+        &self,
+        profile_id: Uuid,
+        connection_id: u64,
+        content: String,
+        signed: Option<SignedChat>,
+    ) {
+        if let Err(e) = self.connection.reducers.send_chat(
+            profile_id.to_string(),
+            connection_id,
+            content,
+            signed,
+        ) {
+            log::error!("Failed to send the chat message of player {profile_id} : {e}");
+        }
+    }
+
+    /// Notifies the server actor of chat sessions starting and ending, so clients can verify the
+    /// messages signed with them.
+    pub fn watch_chat_sessions(&self, server: ServerRelay) {
+        let sessions = self.connection.db.chat_session();
+        let on_insert = server.clone();
+        sessions.on_insert(move |_ctx, session| {
+            on_insert.send(ServerMessage::ChatSessionStarted(Box::new(session.clone())));
+        });
+        let on_update = server.clone();
+        sessions.on_update(move |_ctx, _old, session| {
+            on_update.send(ServerMessage::ChatSessionStarted(Box::new(session.clone())));
+        });
+        sessions.on_delete(move |_ctx, session| {
+            server.send(ServerMessage::ChatSessionEnded(Uuid::from_u128(
+                session.profile_id,
+            )));
+        });
+    }
+
+    /// Shares the key the player's client signs with, once verified.
+    pub fn set_chat_session(
+        &self,
+        profile_id: Uuid,
+        connection_id: u64,
+        update: &SChatSessionUpdate,
+    ) {
+        if let Err(e) = self.connection.reducers.set_chat_session(
+            profile_id.to_string(),
+            connection_id,
+            update.session_id.to_string(),
+            update.public_key.clone(),
+            update.key_signature.clone(),
+            Timestamp::from_micros_since_unix_epoch(update.expires_at.saturating_mul(1000)),
+        ) {
+            log::error!("Failed to start the chat session of player {profile_id} : {e}");
+        }
+    }
+
+    pub fn chat_sessions(&self) -> Vec<ChatSession> {
+        self.connection.db.chat_session().iter().collect()
+    }
+
+    pub fn username(&self, profile_id: Uuid) -> Option<String> {
+        self.connection
+            .db
+            .player()
+            .profile_id()
+            .find(&profile_id.as_u128())
+            .and_then(|player| player.last_known_username)
+    }
+
+    /// Whether a player with this username, ignoring case, joined before or was added.
+    pub fn is_known_username(&self, username: &str) -> bool {
+        self.connection.db.player().iter().any(|player| {
+            player
+                .last_known_username
+                .is_some_and(|name| name.eq_ignore_ascii_case(username))
+        })
+    }
+
+    /// Lets the module know about a player who never joined, e.g. to whitelist them by name.
+    pub fn add_player(&self, username: String, profile_id: Uuid) {
+        if let Err(e) = self
+            .connection
+            .reducers
+            .add_player(username, profile_id.to_string())
+        {
+            log::error!("Failed to add player {profile_id} : {e}");
+        }
+    }
+
+    /// Forwards the outcome of commands run by players of this proxy to the server actor, for
+    /// them to be told about it.
+    pub fn watch_commands(&self, server: ServerRelay) {
+        let on_insert = server.clone();
+        self.connection
+            .db
+            .command_feedback()
+            .on_insert(move |ctx, feedback| {
+                if ctx.try_identity() != Some(feedback.proxy) {
+                    return;
+                }
+                on_insert.send(ServerMessage::CommandFeedback {
+                    feedback_id: feedback.id,
+                    connection_id: feedback.connection_id,
+                    message: feedback.message.clone(),
+                });
+            });
+        self.connection.reducers.on_run_command(
+            move |ctx, profile_id, _connection_id, _command| {
+                let Status::Failed(reason) = &ctx.event.status else {
+                    return;
+                };
+                if ctx.try_identity() != Some(ctx.event.caller_identity) {
+                    return;
+                }
+                if let Ok(profile_id) = Uuid::parse_str(profile_id) {
+                    server.send(ServerMessage::Feedback {
+                        profile_id,
+                        message: reason.to_string(),
+                    });
+                }
+            },
+        );
+    }
+
+    /// The module runs the command on behalf of the player, see [Database::watch_commands].
+    pub fn run_command(&self, profile_id: Uuid, connection_id: u64, command: String) {
+        if let Err(e) =
+            self.connection
+                .reducers
+                .run_command(profile_id.to_string(), connection_id, command)
+        {
+            log::error!("Failed to run the command of player {profile_id} : {e}");
+        }
+    }
+
+    pub fn acknowledge_command_feedback(&self, feedback_id: u64) {
+        if let Err(e) = self
+            .connection
+            .reducers
+            .acknowledge_command_feedback(feedback_id)
+        {
+            log::error!("Failed to acknowledge command feedback {feedback_id} : {e}");
+        }
+    }
+
+    /// Every registered command, whatever the level it needs.
+    pub fn commands(&self) -> Vec<Command> {
+        let mut commands = self.connection.db.command().iter().collect::<Vec<_>>();
+        commands.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        commands
+    }
+
+    /// Usernames of the players online across all proxies starting with `prefix`, ignoring case.
+    pub fn online_usernames(&self, prefix: &str) -> Vec<String> {
+        let prefix = prefix.to_lowercase();
+        let mut names = self
+            .connection
+            .db
+            .player_session()
+            .iter()
+            .filter_map(|session| self.username(Uuid::from_u128(session.profile_id)))
+            .filter(|name| name.to_lowercase().starts_with(&prefix))
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+
+    /// Asks the module to apply what the player did, the outcome arrives through
+    /// [Database::watch_inventories].
+    pub fn inventory_action(&self, profile_id: Uuid, connection_id: u64, action: InventoryAction) {
+        let reducers = &self.connection.reducers;
+        let profile = profile_id.to_string();
+        let result = match action {
+            InventoryAction::Click { slot, button, mode } => {
+                reducers.click_container(profile, connection_id, slot, button, mode)
+            }
+            InventoryAction::SetCreativeSlot { slot, stack } => {
+                reducers.set_creative_slot(profile, connection_id, slot, stack)
+            }
+            InventoryAction::Close => reducers.close_container(profile, connection_id),
+            InventoryAction::SetHeldSlot(slot) => {
+                reducers.set_held_slot(profile, connection_id, slot)
+            }
+        };
+        if let Err(e) = result {
+            log::error!("Failed to update the inventory of player {profile_id} : {e}");
+        }
+    }
+
+    pub fn inventory(&self, profile_id: Uuid) -> Option<PlayerInventory> {
+        self.connection
+            .db
+            .player_inventory()
+            .profile_id()
+            .find(&profile_id.as_u128())
+    }
+
+    /// The hotbar slot the player holds, the first one unless they selected another.
+    pub fn held_slot(&self, profile_id: Uuid) -> u8 {
+        self.connection
+            .db
+            .held_slot()
+            .profile_id()
+            .find(&profile_id.as_u128())
+            .map_or(0, |held| held.slot)
+    }
+
+    /// Marks the player online, `reply_to` receives whether the module accepted them.
+    pub fn join_player(
+        &self,
+        profile_id: Uuid,
+        username: String,
+        connection_id: u64,
+        address: IpAddr,
+        reply_to: oneshot::Sender<ReducerOutcome>,
+    ) {
+        self.joins.call(connection_id, reply_to, || {
+            self.connection.reducers.player_join(
+                username,
+                profile_id.to_string(),
+                connection_id,
+                address.to_string(),
+            )
+        });
+    }
+
+    pub fn leave_player(&self, profile_id: Uuid, connection_id: u64) {
+        if let Err(e) = self
+            .connection
+            .reducers
+            .player_leave(profile_id.to_string(), connection_id)
+        {
+            log::error!("Failed to mark player {profile_id} offline : {e}");
+        }
+    }
+
+    /// Saves where the player is, so they come back there once they reconnect.
+    pub fn move_player(&self, profile_id: Uuid, connection_id: u64, location: &Location) {
+        if let Err(e) = self.connection.reducers.move_player(
+            profile_id.to_string(),
+            connection_id,
+            location.dimension.clone(),
+            location.position.x,
+            location.position.y,
+            location.position.z,
+            location.yaw,
+            location.pitch,
+            location.on_ground,
+        ) {
+            log::error!("Failed to save the position of player {profile_id} : {e}");
+        }
+    }
+
+    /// Records that the player was set back, for operators to look into.
+    pub fn report_violation(
+        &self,
+        profile_id: Uuid,
+        connection_id: u64,
+        violation: Violation,
+        detail: String,
+    ) {
+        if let Err(e) = self.connection.reducers.report_violation(
+            profile_id.to_string(),
+            connection_id,
+            violation,
+            detail,
+        ) {
+            log::error!("Failed to report the violation of player {profile_id} : {e}");
+        }
+    }
+
+    /// Players with movement violations, most violations first.
+    pub fn movement_violations(&self) -> Vec<MovementViolations> {
+        let mut violations = self
+            .connection
+            .db
+            .movement_violation()
+            .iter()
+            .collect::<Vec<_>>();
+        violations.sort_unstable_by_key(|violations| {
+            std::cmp::Reverse(
+                violations.speed
+                    + violations.teleport
+                    + violations.flying
+                    + violations.inside_block,
+            )
+        });
+        violations
+    }
+
+    /// Where the player logged out, `None` if they never moved.
+    pub fn player_state(&self, profile_id: Uuid) -> Option<PlayerState> {
+        self.connection
+            .db
+            .player_state()
+            .profile_id()
+            .find(&profile_id.as_u128())
+    }
+
+    pub fn acknowledge_kick(&self, kick_id: u64) {
+        if let Err(e) = self.connection.reducers.acknowledge_kick(kick_id) {
+            log::error!("Failed to acknowledge kick {kick_id} : {e}");
+        }
+    }
+
+    pub fn permission_level(&self, profile_id: Uuid) -> PermissionLvl {
+        self.connection
+            .db
+            .operator()
+            .profile_id()
+            .find(&profile_id.as_u128())
+            .map_or(PermissionLvl::Zero, |op| op.level)
+    }
+
+    /// Number of players online across all proxies.
+    pub fn online_players(&self) -> u32 {
+        self.connection.db.player_session().count() as u32
+    }
+
+    /// The entity id of an online player, as assigned by the module.
+    pub fn entity_id(&self, profile_id: Uuid) -> Option<u32> {
+        self.connection
+            .db
+            .player_session()
+            .profile_id()
+            .find(&profile_id.as_u128())
+            .map(|session| session.entity_id)
+    }
+
+    /// A stored chunk, `None` if it was never imported nor generated, or is not watched.
+    pub fn chunk(&self, position: &ChunkPos) -> Option<Chunk> {
+        self.connection.db.chunk().key().find(&position.key())
+    }
+
+    /// A stored chunk along with its sections and block entities.
+    pub fn stored_chunk(&self, position: &ChunkPos) -> Option<StoredChunk> {
+        let chunk = self.chunk(position)?;
+        let db = &self.connection.db;
+        let sections = self
+            .chunks
+            .sections(chunk.id)
+            .into_iter()
+            .filter_map(|id| db.chunk_section().id().find(&id))
+            .collect();
+        let block_entities = self
+            .chunks
+            .block_entities(chunk.id)
+            .into_iter()
+            .filter_map(|id| db.block_entity().id().find(&id))
+            .collect();
+        Some(StoredChunk {
+            chunk,
+            sections,
+            block_entities,
+        })
+    }
+
+    /// Palette entry of the block at the given coordinates, `None` if its chunk is not stored or
+    /// not watched.
+    pub fn block_at(&self, dimension: &str, x: i32, y: i32, z: i32) -> Option<String> {
+        let chunk = self.chunk(&ChunkPos::new(dimension, x >> 4, z >> 4))?;
+        let section = i8::try_from(y >> 4)
+            .ok()
+            .and_then(|section_y| self.chunks.section(chunk.id, section_y))
+            .and_then(|id| self.connection.db.chunk_section().id().find(&id));
+        let block = match &section {
+            Some(section) => encode::block_in_section(
+                section,
+                (x & 15) as usize,
+                (y & 15) as usize,
+                (z & 15) as usize,
+            ),
+            None => AIR,
+        };
+        Some(block.to_string())
+    }
+
+    /// Asks the module for the `limit` most recent audit log entries, `reply_to` receives whether
+    /// they are available through [Self::audit_log].
+    pub fn request_audit_log(&self, limit: u32, reply_to: oneshot::Sender<ReducerOutcome>) {
+        self.audit_requests.call((), reply_to, || {
+            self.connection.reducers.request_audit_log(limit)
+        });
+    }
+
+    /// The audit log entries last requested, newest first. Only those requested by this proxy are
+    /// visible to it.
+    pub fn audit_log(&self) -> Vec<AuditEntry> {
+        let mut entries = self
+            .connection
+            .db
+            .audit_excerpt()
+            .iter()
+            .map(|excerpt| excerpt.entry)
+            .collect::<Vec<_>>();
+        entries.sort_unstable_by(|a, b| b.id.cmp(&a.id));
+        entries
+    }
+}
+
+impl Deref for Database {
+    type Target = DbConnection;
+
+    fn deref(&self) -> &Self::Target {
+        &self.connection
+    }
+}
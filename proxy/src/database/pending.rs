@@ -0,0 +1,71 @@
+use spacetimedb_sdk::Status;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+/// Outcome of a reducer call : the error message returned by the reducer on failure.
+pub type ReducerOutcome = Result<(), String>;
+
+/// Reducer calls waiting for their outcome, keyed by what identifies the call (e.g. a profile id).
+///
+/// Reducer callbacks are registered once for the whole connection, so they complete the matching
+/// pending call through this map rather than capturing the caller.
+pub struct PendingCalls<K> {
+    pending: Arc<Mutex<HashMap<K, oneshot::Sender<ReducerOutcome>>>>,
+}
+
+impl<K> Clone for PendingCalls<K> {
+    fn clone(&self) -> Self {
+        Self {
+            pending: self.pending.clone(),
+        }
+    }
+}
+
+impl<K> Default for PendingCalls<K> {
+    fn default() -> Self {
+        Self {
+            pending: Default::default(),
+        }
+    }
+}
+
+impl<K: Eq + Hash> PendingCalls<K> {
+    /// Registers `reply_to` for `key`, then runs `call`.
+    /// If the call could not be sent, `reply_to` is completed right away with the error.
+    pub fn call<E, F>(&self, key: K, reply_to: oneshot::Sender<ReducerOutcome>, call: F)
+    where
+        K: Clone,
+        E: ToString,
+        F: FnOnce() -> Result<(), E>,
+    {
+        // A newer call for the same key replaces the older one, dropping its sender
+        self.lock().insert(key.clone(), reply_to);
+        if let Err(e) = call() {
+            self.complete(&key, Err(e.to_string()));
+        }
+    }
+
+    pub fn complete(&self, key: &K, outcome: ReducerOutcome) {
+        if let Some(reply_to) = self.lock().remove(key) {
+            let _ = reply_to.send(outcome);
+        }
+    }
+
+    pub fn complete_with_status(&self, key: &K, status: &Status) {
+        self.complete(key, outcome(status))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<K, oneshot::Sender<ReducerOutcome>>> {
+        self.pending.lock().expect("Pending reducer calls poisoned")
+    }
+}
+
+pub fn outcome(status: &Status) -> ReducerOutcome {
+    match status {
+        Status::Committed => Ok(()),
+        Status::Failed(reason) => Err(reason.to_string()),
+        Status::OutOfEnergy => Err("Database is out of energy".into()),
+    }
+}
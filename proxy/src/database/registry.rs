@@ -0,0 +1,78 @@
+use crate::module_bindings::{
+    proxy_heartbeat, register_proxy, DbConnection, ProxyInstanceTableAccess,
+};
+use spacetimedb_sdk::{DbContext, Status};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::select;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, MissedTickBehavior};
+
+/// How often this proxy reports itself alive to the database.
+/// Must stay well below the module's heartbeat timeout.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Registers this proxy in the `proxy_instance` table and keeps its heartbeat going.
+///
+/// If the registration disappears (e.g. the module swept us after a network hiccup), the proxy
+/// registers itself again on the next tick. It also registers again whenever the bound
+/// addresses change, so the registry always shows where players can reach this proxy.
+pub fn spawn_heartbeat(
+    db: Arc<DbConnection>,
+    mut addresses: watch::Receiver<String>,
+    version: String,
+) -> JoinHandle<()> {
+    db.reducers.on_register_proxy(|ctx, _address, _version| {
+        if let Status::Failed(reason) = &ctx.event.status {
+            log::error!(
+                "Proxy registration refused : {reason}. An admin can trust this proxy by calling \
+                the `trust_proxy` reducer with its identity"
+            );
+        }
+    });
+
+    tokio::spawn(async move {
+        let address = addresses.borrow_and_update().clone();
+        if let Err(e) = db.reducers.register_proxy(address, version.clone()) {
+            log::error!("Failed to register proxy : {e}");
+        }
+
+        let mut ticker = interval(HEARTBEAT_INTERVAL);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // The first tick completes immediately, we just registered
+        ticker.tick().await;
+
+        loop {
+            select! {
+                _ = ticker.tick() => {}
+                changed = addresses.changed() => {
+                    if changed.is_err() {
+                        // The server actor is gone, the proxy is shutting down
+                        break;
+                    }
+                    let address = addresses.borrow_and_update().clone();
+                    log::info!("Now reachable on {address}, updating registration");
+                    if let Err(e) = db.reducers.register_proxy(address, version.clone()) {
+                        log::error!("Failed to register proxy : {e}");
+                    }
+                    continue;
+                }
+            }
+
+            let registered = db.try_identity().is_some_and(|identity| {
+                db.db.proxy_instance().identity().find(&identity).is_some()
+            });
+            let result = if registered {
+                db.reducers.proxy_heartbeat()
+            } else {
+                log::warn!("Proxy registration is missing, registering again");
+                let address = addresses.borrow().clone();
+                db.reducers.register_proxy(address, version.clone())
+            };
+            if let Err(e) = result {
+                log::error!("Failed to send proxy heartbeat : {e}");
+            }
+        }
+    })
+}
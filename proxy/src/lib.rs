@@ -1,6 +1,11 @@
 pub mod actor_ref;
 pub mod client_actor;
+pub mod console;
+pub mod database;
 pub mod err;
 pub mod module_bindings;
 pub mod protocol;
 pub mod server_actor;
+pub mod vanilla;
+pub mod world;
+pub mod worker;
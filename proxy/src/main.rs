@@ -3,39 +3,101 @@ use pumpkin_data::packet::CURRENT_MC_PROTOCOL;
 use pumpkin_util::text::color::NamedColor;
 use pumpkin_util::text::TextComponent;
 use spacetimedb_sdk::{
-    DbConnectionBuilder, DbContext, Error, Identity, SubscriptionHandle, Table, TableWithPrimaryKey,
+    credentials, DbConnectionBuilder, DbContext, Error, Identity, SubscriptionHandle, Table,
+    TableWithPrimaryKey,
 };
 use spacetimemc_proxy::actor_ref::ActorRef;
+use spacetimemc_proxy::console;
+use spacetimemc_proxy::database::{registry, Database};
 use spacetimemc_proxy::module_bindings;
 use spacetimemc_proxy::module_bindings::{
-    BasicConfiguration, DbConnection, ErrorContext, ServerBasicConfigTableAccess,
+    BasicConfiguration, DbConnection, ErrorContext, GetWorldPath, ServerBasicConfigTableAccess,
     SubscriptionEventContext,
 };
 use spacetimemc_proxy::server_actor::actor::{Server, ServerMessage};
 use spacetimemc_proxy::server_actor::CURRENT_MC_VERSION;
+use spacetimemc_proxy::worker::WorkerPool;
+use spacetimemc_proxy::world::cache::{self, ChunkCache};
+use spacetimemc_proxy::world::generation::ChunkGenerator;
+use spacetimemc_proxy::world::World;
+use spacetimemc_proxy::{vanilla, world};
 use std::io;
+use std::path::PathBuf;
 use std::sync::mpsc::channel;
 use std::sync::{Arc, LazyLock};
 use std::time::Duration;
 use std::time::Instant;
 use tokio::io::AsyncWriteExt;
 use tokio::signal::unix::{signal, SignalKind};
-use tokio::sync::oneshot;
+use tokio::sync::{oneshot, watch};
 use tokio::task::yield_now;
 use tokio::time::sleep;
 
 const CARGO_PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
 const GIT_VERSION: &str = env!("GIT_VERSION");
 
+const USAGE: &str = "Usage : spacetimemc-proxy [import [<directory>] [--dry-run] \
+    | export <directory> | import-world [<world directory>]]";
+
+/// What the proxy was asked to do, from its arguments.
+enum Command {
+    /// Accept players, the default
+    Run,
+    /// Copy the settings and lists of a vanilla server into the database
+    Import { directory: PathBuf, dry_run: bool },
+    /// Write the settings and lists as a vanilla server would, overwriting existing files
+    Export { directory: PathBuf },
+    /// Upload the chunks of an anvil world, by default the configured level
+    ImportWorld { directory: Option<PathBuf> },
+}
+
+impl Command {
+    fn from_args() -> Result<Self, String> {
+        let mut args = std::env::args().skip(1);
+        match args.next().as_deref() {
+            None => Ok(Command::Run),
+            Some("import") => {
+                let mut directory = None;
+                let mut dry_run = false;
+                for arg in args {
+                    match arg.as_str() {
+                        "--dry-run" => dry_run = true,
+                        _ if directory.is_none() => directory = Some(PathBuf::from(arg)),
+                        _ => return Err(format!("Unexpected argument {arg:?}")),
+                    }
+                }
+                Ok(Command::Import {
+                    directory: directory.unwrap_or_else(|| PathBuf::from(".")),
+                    dry_run,
+                })
+            }
+            Some("export") => match (args.next(), args.next()) {
+                (Some(directory), None) => Ok(Command::Export {
+                    directory: PathBuf::from(directory),
+                }),
+                (None, _) => Err("Missing export directory".into()),
+                (_, Some(arg)) => Err(format!("Unexpected argument {arg:?}")),
+            },
+            Some("import-world") => match (args.next(), args.next()) {
+                (directory, None) => Ok(Command::ImportWorld {
+                    directory: directory.map(PathBuf::from),
+                }),
+                (_, Some(arg)) => Err(format!("Unexpected argument {arg:?}")),
+            },
+            Some(other) => Err(format!("Unknown command {other:?}")),
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() {
     let start_time = Instant::now();
     env_logger::builder().format_timestamp_millis().init();
+    let command = Command::from_args().unwrap_or_else(|e| {
+        eprintln!("{e}\n{USAGE}");
+        std::process::exit(2);
+    });
 
-    /*rayon::ThreadPoolBuilder::new()
-    .thread_name(|_| "rayon-worker".to_string())
-    .build_global()
-    .expect("Rayon thread pool can only be initialized once");*/
     log::info!(
         "Starting SpaceTimeMC {CARGO_PKG_VERSION} ({GIT_VERSION}) for Minecraft {CURRENT_MC_VERSION} (Protocol {CURRENT_MC_PROTOCOL})",
     );
@@ -52,7 +114,7 @@ async fn main() {
         }
     );
 
-    let db = connect_to_db();
+    let db = Arc::new(connect_to_db());
     // db.run_async().await.expect("Unable to connect to database");
     let db_handle = db.run_threaded();
     // tokio::spawn(async { db.run_async().await });
@@ -66,14 +128,46 @@ async fn main() {
     }
 
     let _config = &config.expect("Missing basic server configuration");
+    let result = match command {
+        Command::Run => None,
+        Command::Import { directory, dry_run } => {
+            Some(vanilla::import::run(&db, &directory, dry_run).await)
+        }
+        Command::Export { directory } => Some(vanilla::export::run(&db, &directory).await),
+        Command::ImportWorld { directory } => {
+            let directory = directory.unwrap_or_else(|| _config.get_world_path());
+            Some(world::import::run(&db, &directory).await)
+        }
+    };
+    if let Some(result) = result {
+        if let Err(e) = result {
+            log::error!("{e}");
+        }
+        db.disconnect()
+            .expect("Unable to cleanly disconnect from database.");
+        db_handle.join().unwrap();
+        return;
+    }
+
     /*let stserver = SpaceTimeServer::new(_config).await;
     stserver.init_plugins().await;*/
-    let server_actor = Server::spawn(_config).await;
+    let database = Database::new(db.clone());
+    let workers = WorkerPool::new(None).expect("Unable to start the worker pool");
+    let world = World::new(
+        database.clone(),
+        ChunkGenerator::spawn(&_config.seed, database.clone(), workers.clone()),
+        ChunkCache::follow(&db, cache::DEFAULT_CAPACITY),
+        workers,
+    );
+    let server_actor = Server::spawn(_config, database.clone(), world.clone()).await;
 
     let (death_sender, death_receiver) = oneshot::channel();
+    let (bound_sender, mut bound_addresses) = watch::channel(String::new());
     server_actor
-        .start(_config.server_address.clone(), death_sender)
+        .start(_config.server_address.clone(), bound_sender, death_sender)
         .await;
+    // Published once the first bind attempt is done
+    let _ = bound_addresses.changed().await;
 
     log::info!(
         "Started server; took {}ms",
@@ -81,8 +175,15 @@ async fn main() {
     );
     log::info!(
         "You can now connect to the server; listening on {}",
-        _config.server_address // TODO : this doesn't handle automatic port
+        *bound_addresses.borrow_and_update()
+    );
+
+    let heartbeat = registry::spawn_heartbeat(
+        db.clone(),
+        bound_addresses,
+        format!("{CARGO_PKG_VERSION} ({GIT_VERSION})"),
     );
+    let console = tokio::spawn(console::run(database, server_actor.clone(), world.clone()));
 
     db.db
         .server_basic_config()
@@ -106,6 +207,9 @@ async fn main() {
     let _ = death_receiver.await;
 
     log::info!("The server has stopped.");
+    heartbeat.abort();
+    console.abort();
+    // Disconnecting makes the module mark this proxy and its players offline
     db.disconnect()
         .expect("Unable to cleanly disconnect from database.");
     db_handle.join().unwrap();
@@ -116,7 +220,26 @@ fn subscribe_to_tables(ctx: &DbConnection) -> module_bindings::autogen::Subscrip
     ctx.subscription_builder()
         .on_applied(on_sub_applied)
         .on_error(on_sub_error)
-        .subscribe(["SELECT * FROM server_basic_config"])
+        .subscribe([
+            "SELECT * FROM server_basic_config",
+            "SELECT * FROM proxy_instance",
+            "SELECT * FROM player_kick",
+            "SELECT * FROM whitelist",
+            "SELECT * FROM operator",
+            "SELECT * FROM player_session",
+            "SELECT * FROM audit_excerpt",
+            "SELECT * FROM ban_excerpt",
+            "SELECT * FROM player",
+            "SELECT * FROM player_state",
+            "SELECT * FROM movement_violation",
+            "SELECT * FROM player_inventory",
+            "SELECT * FROM held_slot",
+            "SELECT * FROM chat_message",
+            "SELECT * FROM chat_session",
+            "SELECT * FROM command",
+            "SELECT * FROM command_feedback",
+            "SELECT * FROM imported_region",
+        ])
 }
 
 fn on_sub_applied(ctx: &SubscriptionEventContext) {
@@ -138,6 +261,9 @@ const HOST: &str = "http://localhost:3000";
 /// The database name we chose when we published our module.
 const DB_NAME: &str = "spacetimemc";
 
+/// Name under which the proxy's database token is stored.
+const CREDENTIALS_KEY: &str = "spacetimemc-proxy";
+
 /// Load credentials from a file and connect to the database.
 fn connect_to_db() -> DbConnection {
     DbConnection::builder()
@@ -150,7 +276,7 @@ fn connect_to_db() -> DbConnection {
         // If the user has previously connected, we'll have saved a token in the `on_connect` callback.
         // In that case, we'll load it and pass it to `with_token`,
         // so we can re-authenticate as the same `Identity`.
-        // .with_token(creds_store().load().expect("Error loading credentials"))
+        .with_token(creds_store().load().expect("Error loading credentials"))
         // Set the database name we chose when we called `spacetime publish`.
         .with_module_name(DB_NAME)
         // Set the URI of the SpacetimeDB host that's running our database.
@@ -160,12 +286,17 @@ fn connect_to_db() -> DbConnection {
         .expect("Failed to connect")
 }
 
+/// Keeping the same token keeps the same `Identity`, which admins must trust for this proxy to
+/// register (see the `trust_proxy` reducer).
+fn creds_store() -> credentials::File {
+    credentials::File::new(CREDENTIALS_KEY)
+}
+
 fn on_connected(_ctx: &DbConnection, _identity: Identity, token: &str) {
     log::info!("Connected to database as {_identity}");
-    // FIXME : creds store and whatnot to ensure authenticity of client
-    /*if let Err(e) = creds_store().save(token) {
-        eprintln!("Failed to save credentials: {:?}", e);
-    }*/
+    if let Err(e) = creds_store().save(token) {
+        log::error!("Failed to save credentials: {:?}", e);
+    }
 }
 
 /// Our `on_connect_error` callback: print the error, then exit the process.
@@ -1,3 +1,5 @@
+use pumpkin_util::permission::PermissionLvl as PumpkinPermissionLvl;
+use pumpkin_util::Difficulty as PumpkinDifficulty;
 use pumpkin_util::GameMode as PumpkinGameMode;
 use std::path::PathBuf;
 
@@ -24,3 +26,26 @@ impl From<GameMode> for PumpkinGameMode {
         }
     }
 }
+
+impl From<PermissionLvl> for PumpkinPermissionLvl {
+    fn from(value: PermissionLvl) -> Self {
+        match value {
+            PermissionLvl::Zero => PumpkinPermissionLvl::Zero,
+            PermissionLvl::One => PumpkinPermissionLvl::One,
+            PermissionLvl::Two => PumpkinPermissionLvl::Two,
+            PermissionLvl::Three => PumpkinPermissionLvl::Three,
+            PermissionLvl::Four => PumpkinPermissionLvl::Four,
+        }
+    }
+}
+
+impl From<Difficulty> for PumpkinDifficulty {
+    fn from(value: Difficulty) -> Self {
+        match value {
+            Difficulty::Peaceful => PumpkinDifficulty::Peaceful,
+            Difficulty::Easy => PumpkinDifficulty::Easy,
+            Difficulty::Normal => PumpkinDifficulty::Normal,
+            Difficulty::Hard => PumpkinDifficulty::Hard,
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! Chat packets, encoded by hand as their signatures are fixed size byte arrays.
+
+use crate::protocol::{get_string, get_var_int, put_var_int};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use pumpkin_data::packet::clientbound::{
+    PLAY_PLAYER_CHAT, PLAY_PLAYER_INFO_REMOVE, PLAY_PLAYER_INFO_UPDATE,
+};
+use uuid::Uuid;
+
+/// Longest message the vanilla client sends, in characters.
+pub const MAX_MESSAGE_LENGTH: usize = 256;
+pub const SIGNATURE_LENGTH: usize = 256;
+/// Messages a client acknowledges at most, one bit each.
+pub const LAST_SEEN_MESSAGES: usize = 20;
+/// Same limits as vanilla on the profile keys clients send.
+const MAX_PUBLIC_KEY_LENGTH: usize = 512;
+const MAX_KEY_SIGNATURE_LENGTH: usize = 4096;
+
+/// `minecraft:chat`, first of the chat types sent during configuration. Ids are shifted by one, 0
+/// meaning a chat type given inline.
+const CHAT_TYPE: i32 = 1;
+/// Actions of a Player Info Update, as bits.
+const ADD_PLAYER: u8 = 0x01;
+const INITIALIZE_CHAT: u8 = 0x02;
+
+/// A message the player typed, signed if the client has a chat session.
+#[derive(Debug)]
+pub struct SChat {
+    pub message: String,
+    /// Milliseconds since the epoch
+    pub timestamp: i64,
+    pub salt: i64,
+    pub signature: Option<Box<[u8; SIGNATURE_LENGTH]>>,
+    /// Messages seen since the last acknowledgement
+    pub message_count: i32,
+    pub acknowledged: [u8; LAST_SEEN_MESSAGES.div_ceil(8)],
+    /// Of the last seen signatures
+    pub checksum: u8,
+}
+
+impl SChat {
+    /// Reads the payload of a Chat packet.
+    pub fn read(buf: &mut Bytes) -> Result<Self, String> {
+        let message = get_string(buf, MAX_MESSAGE_LENGTH)?;
+        // Timestamp, salt and whether a signature follows
+        if buf.remaining() < 17 {
+            return Err("Chat message ends early".into());
+        }
+        let timestamp = buf.get_i64();
+        let salt = buf.get_i64();
+        let signature = if buf.get_u8() != 0 {
+            let mut signature = Box::new([0; SIGNATURE_LENGTH]);
+            if buf.remaining() < SIGNATURE_LENGTH {
+                return Err("Chat signature ends early".into());
+            }
+            buf.copy_to_slice(signature.as_mut_slice());
+            Some(signature)
+        } else {
+            None
+        };
+        let message_count = get_var_int(buf)?;
+        let mut acknowledged = [0; LAST_SEEN_MESSAGES.div_ceil(8)];
+        if buf.remaining() < acknowledged.len() + 1 {
+            return Err("Chat acknowledgements end early".into());
+        }
+        buf.copy_to_slice(&mut acknowledged);
+        let checksum = buf.get_u8();
+
+        Ok(Self {
+            message,
+            timestamp,
+            salt,
+            signature,
+            message_count,
+            acknowledged,
+            checksum,
+        })
+    }
+}
+
+/// The key the client signs its messages with, along with Mojang's signature of it.
+#[derive(Clone, Debug)]
+pub struct SChatSessionUpdate {
+    pub session_id: Uuid,
+    /// Milliseconds since the epoch
+    pub expires_at: i64,
+    /// X.509 encoded RSA key
+    pub public_key: Vec<u8>,
+    pub key_signature: Vec<u8>,
+}
+
+impl SChatSessionUpdate {
+    /// Reads the payload of a Chat Session Update packet.
+    pub fn read(buf: &mut Bytes) -> Result<Self, String> {
+        if buf.remaining() < 24 {
+            return Err("Chat session ends early".into());
+        }
+        let session_id = Uuid::from_u128(buf.get_u128());
+        let expires_at = buf.get_i64();
+        let public_key = get_byte_array(buf, MAX_PUBLIC_KEY_LENGTH)?;
+        let key_signature = get_byte_array(buf, MAX_KEY_SIGNATURE_LENGTH)?;
+        Ok(Self {
+            session_id,
+            expires_at,
+            public_key,
+            key_signature,
+        })
+    }
+}
+
+fn get_byte_array(buf: &mut Bytes, max_length: usize) -> Result<Vec<u8>, String> {
+    let length = get_var_int(buf)?;
+    match usize::try_from(length) {
+        Ok(length) if length <= max_length && length <= buf.remaining() => {
+            Ok(buf.copy_to_bytes(length).to_vec())
+        }
+        _ => Err(format!("Invalid byte array length {length}")),
+    }
+}
+
+fn put_string(buf: &mut BytesMut, string: &str) {
+    put_var_int(buf, string.len() as i32);
+    buf.put_slice(string.as_bytes());
+}
+
+/// A plain text component, as a bare NBT string. Fine for usernames, which are plain ASCII.
+fn put_text(buf: &mut BytesMut, text: &str) {
+    const STRING_TAG: u8 = 8;
+    buf.put_u8(STRING_TAG);
+    buf.put_u16(text.len() as u16);
+    buf.put_slice(text.as_bytes());
+}
+
+/// A message to relay as signed, as the sender's client signed it.
+pub struct PlayerChat<'a> {
+    pub sender: Uuid,
+    pub sender_name: &'a str,
+    /// Position within the sender's chat session
+    pub index: i32,
+    pub signature: &'a [u8],
+    pub message: &'a str,
+    pub timestamp: i64,
+    pub salt: i64,
+    /// Signatures the sender acknowledged, oldest first
+    pub last_seen: &'a [Vec<u8>],
+}
+
+impl PlayerChat<'_> {
+    /// The whole Player Chat packet, id included. `global_index` counts the messages sent to the
+    /// receiving client.
+    pub fn encode(&self, global_index: i32) -> Bytes {
+        let mut buf = BytesMut::new();
+        put_var_int(&mut buf, PLAY_PLAYER_CHAT);
+        put_var_int(&mut buf, global_index);
+        buf.put_u128(self.sender.as_u128());
+        put_var_int(&mut buf, self.index);
+        buf.put_u8(1);
+        buf.put_slice(self.signature);
+        put_string(&mut buf, self.message);
+        buf.put_i64(self.timestamp);
+        buf.put_i64(self.salt);
+        put_var_int(&mut buf, self.last_seen.len() as i32);
+        for signature in self.last_seen {
+            // Given in full rather than as ids of the client's signature cache
+            put_var_int(&mut buf, 0);
+            buf.put_slice(signature);
+        }
+        // No unsigned content, not filtered
+        buf.put_u8(0);
+        put_var_int(&mut buf, 0);
+        put_var_int(&mut buf, CHAT_TYPE);
+        put_text(&mut buf, self.sender_name);
+        // No target
+        buf.put_u8(0);
+        buf.freeze()
+    }
+}
+
+/// Player Info Update adding a player along with their chat session, for clients to verify their
+/// messages. They stay out of the player list.
+pub fn player_info_chat_session(
+    profile_id: Uuid,
+    name: &str,
+    session_id: Uuid,
+    expires_at: i64,
+    public_key: &[u8],
+    key_signature: &[u8],
+) -> Bytes {
+    let mut buf = BytesMut::new();
+    put_var_int(&mut buf, PLAY_PLAYER_INFO_UPDATE);
+    buf.put_u8(ADD_PLAYER | INITIALIZE_CHAT);
+    put_var_int(&mut buf, 1);
+    buf.put_u128(profile_id.as_u128());
+    put_string(&mut buf, name);
+    // No properties
+    put_var_int(&mut buf, 0);
+    buf.put_u8(1);
+    buf.put_u128(session_id.as_u128());
+    buf.put_i64(expires_at);
+    put_var_int(&mut buf, public_key.len() as i32);
+    buf.put_slice(public_key);
+    put_var_int(&mut buf, key_signature.len() as i32);
+    buf.put_slice(key_signature);
+    buf.freeze()
+}
+
+pub fn player_info_remove(profile_id: Uuid) -> Bytes {
+    let mut buf = BytesMut::new();
+    put_var_int(&mut buf, PLAY_PLAYER_INFO_REMOVE);
+    put_var_int(&mut buf, 1);
+    buf.put_u128(profile_id.as_u128());
+    buf.freeze()
+}
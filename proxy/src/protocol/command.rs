@@ -0,0 +1,182 @@
+//! Command packets, encoded by hand as Brigadier nodes refer to each other by index.
+
+use crate::module_bindings::{ArgumentType, Command};
+use crate::protocol::put_var_int;
+use bytes::{BufMut, Bytes, BytesMut};
+use pumpkin_data::packet::clientbound::{PLAY_COMMANDS, PLAY_COMMAND_SUGGESTIONS};
+
+/// Node types, in the lowest two bits of the flags.
+const ROOT: u8 = 0;
+const LITERAL: u8 = 1;
+const ARGUMENT: u8 = 2;
+const EXECUTABLE: u8 = 0x04;
+const HAS_SUGGESTIONS: u8 = 0x10;
+
+/// Ids of `brigadier:string` and `minecraft:game_profile` in the argument type registry.
+const STRING_PARSER: i32 = 5;
+const GAME_PROFILE_PARSER: i32 = 7;
+/// Modes of `brigadier:string`.
+const SINGLE_WORD: i32 = 0;
+const GREEDY_PHRASE: i32 = 2;
+/// Has the client send a Command Suggestion request while the argument is typed.
+const ASK_SERVER: &str = "minecraft:ask_server";
+
+enum NodeKind {
+    Root,
+    Literal(String),
+    Argument { name: String, kind: ArgumentType },
+}
+
+struct Node {
+    kind: NodeKind,
+    executable: bool,
+    children: Vec<usize>,
+}
+
+/// The command tree of a client, root first.
+#[derive(Default)]
+pub struct CommandTree {
+    nodes: Vec<Node>,
+}
+
+impl CommandTree {
+    /// Commands sharing their first words, such as `whitelist add` and `whitelist remove`, share
+    /// the literals for them.
+    pub fn new<'a>(commands: impl IntoIterator<Item = &'a Command>) -> Self {
+        let mut tree = Self {
+            nodes: vec![Node {
+                kind: NodeKind::Root,
+                executable: false,
+                children: Vec::new(),
+            }],
+        };
+        for command in commands {
+            tree.add(command);
+        }
+        tree
+    }
+
+    fn add(&mut self, command: &Command) {
+        let mut parent = 0;
+        for word in command.name.split(' ') {
+            parent = self.literal(parent, word);
+        }
+        // Runnable once every required argument is given
+        let mut executable = command
+            .arguments
+            .first()
+            .is_none_or(|argument| argument.optional);
+        self.nodes[parent].executable |= executable;
+        for (index, argument) in command.arguments.iter().enumerate() {
+            executable = command
+                .arguments
+                .get(index + 1)
+                .is_none_or(|next| next.optional);
+            parent = self.push(
+                parent,
+                Node {
+                    kind: NodeKind::Argument {
+                        name: argument.name.clone(),
+                        kind: argument.kind,
+                    },
+                    executable,
+                    children: Vec::new(),
+                },
+            );
+        }
+    }
+
+    /// The literal child of `parent` for `word`, created if needed.
+    fn literal(&mut self, parent: usize, word: &str) -> usize {
+        let existing = self.nodes[parent].children.iter().copied().find(|&child| {
+            matches!(&self.nodes[child].kind, NodeKind::Literal(literal) if literal == word)
+        });
+        existing.unwrap_or_else(|| {
+            self.push(
+                parent,
+                Node {
+                    kind: NodeKind::Literal(word.to_string()),
+                    executable: false,
+                    children: Vec::new(),
+                },
+            )
+        })
+    }
+
+    fn push(&mut self, parent: usize, node: Node) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(node);
+        self.nodes[parent].children.push(index);
+        index
+    }
+
+    /// The whole Commands packet, id included.
+    pub fn encode(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+        put_var_int(&mut buf, PLAY_COMMANDS);
+        put_var_int(&mut buf, self.nodes.len() as i32);
+        for node in &self.nodes {
+            let executable = if node.executable { EXECUTABLE } else { 0 };
+            let flags = match &node.kind {
+                NodeKind::Root => ROOT,
+                NodeKind::Literal(_) => LITERAL | executable,
+                NodeKind::Argument {
+                    kind: ArgumentType::Player,
+                    ..
+                } => ARGUMENT | executable | HAS_SUGGESTIONS,
+                NodeKind::Argument { .. } => ARGUMENT | executable,
+            };
+            buf.put_u8(flags);
+            put_var_int(&mut buf, node.children.len() as i32);
+            for &child in &node.children {
+                put_var_int(&mut buf, child as i32);
+            }
+            match &node.kind {
+                NodeKind::Root => {}
+                NodeKind::Literal(literal) => put_string(&mut buf, literal),
+                NodeKind::Argument { name, kind } => {
+                    put_string(&mut buf, name);
+                    match kind {
+                        ArgumentType::Player => {
+                            put_var_int(&mut buf, GAME_PROFILE_PARSER);
+                            put_string(&mut buf, ASK_SERVER);
+                        }
+                        ArgumentType::Word => {
+                            put_var_int(&mut buf, STRING_PARSER);
+                            put_var_int(&mut buf, SINGLE_WORD);
+                        }
+                        ArgumentType::GreedyString => {
+                            put_var_int(&mut buf, STRING_PARSER);
+                            put_var_int(&mut buf, GREEDY_PHRASE);
+                        }
+                    }
+                }
+            }
+        }
+        // Root index
+        put_var_int(&mut buf, 0);
+        buf.freeze()
+    }
+}
+
+fn put_string(buf: &mut BytesMut, string: &str) {
+    put_var_int(buf, string.len() as i32);
+    buf.put_slice(string.as_bytes());
+}
+
+/// Command Suggestions response, replacing `length` bytes of the typed text from `start`, which
+/// counts the leading slash.
+pub fn command_suggestions(id: i32, start: usize, length: usize, matches: &[String]) -> Bytes {
+    let mut buf = BytesMut::new();
+    put_var_int(&mut buf, PLAY_COMMAND_SUGGESTIONS);
+    put_var_int(&mut buf, id);
+    put_var_int(&mut buf, start as i32);
+    put_var_int(&mut buf, length as i32);
+    put_var_int(&mut buf, matches.len() as i32);
+    for suggestion in matches {
+        put_string(&mut buf, suggestion);
+        // No tooltip
+        buf.put_u8(0);
+    }
+    buf.freeze()
+}
@@ -0,0 +1,12 @@
+use pumpkin_data::packet::clientbound::CONFIG_DISCONNECT;
+use pumpkin_util::text::TextComponent;
+use serde::Serialize;
+use spacetimemc_proxy_macros::packet;
+
+/// Carries a text component, where `pumpkin-protocol` only takes plain text. Translation keys are
+/// then shown translated, as in the other states.
+#[derive(Serialize)]
+#[packet(CONFIG_DISCONNECT)]
+pub struct CConfigDisconnect<'a> {
+    pub reason: &'a TextComponent,
+}
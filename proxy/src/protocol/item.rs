@@ -0,0 +1,67 @@
+//! Item stacks as the protocol encodes them.
+
+use crate::module_bindings::ItemStack;
+use crate::protocol::{get_var_int, put_var_int};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use pumpkin_data::item::Item;
+use pumpkin_data::packet::clientbound::PLAY_CONTAINER_SET_CONTENT;
+
+fn strip_namespace(name: &str) -> &str {
+    name.strip_prefix("minecraft:").unwrap_or(name)
+}
+
+/// Appends a slot : its count, then the item and its data component patch unless it is empty.
+/// Items this version does not know are sent as empty slots.
+pub fn put_slot(buf: &mut BytesMut, stack: Option<&ItemStack>) {
+    let known = stack.and_then(|stack| {
+        let item = Item::from_registry_key(strip_namespace(&stack.item))?;
+        Some((stack, item.id))
+    });
+    match known {
+        Some((stack, id)) if stack.count > 0 => {
+            put_var_int(buf, i32::from(stack.count));
+            put_var_int(buf, i32::from(id));
+            buf.put_slice(&stack.components);
+        }
+        _ => put_var_int(buf, 0),
+    }
+}
+
+/// Reads a slot ending the packet, so its data component patch is whatever is left.
+pub fn read_last_slot(buf: &mut Bytes) -> Result<Option<ItemStack>, String> {
+    let count = get_var_int(buf)?;
+    if count <= 0 {
+        return Ok(None);
+    }
+    let id = get_var_int(buf)?;
+    let item = u16::try_from(id)
+        .ok()
+        .and_then(Item::from_id)
+        .ok_or_else(|| format!("Unknown item {id}"))?;
+    let count = u8::try_from(count).map_err(|_| format!("Invalid count {count}"))?;
+    Ok(Some(ItemStack {
+        item: format!("minecraft:{}", item.registry_key),
+        count,
+        max_stack_size: item.components.max_stack_size,
+        components: buf.copy_to_bytes(buf.remaining()).to_vec(),
+    }))
+}
+
+/// The whole Set Container Content packet, id included.
+pub fn container_content(
+    window_id: i32,
+    state_id: i32,
+    slots: &[Option<ItemStack>],
+    carried: Option<&ItemStack>,
+) -> Bytes {
+    let mut buf = BytesMut::new();
+    put_var_int(&mut buf, PLAY_CONTAINER_SET_CONTENT);
+    put_var_int(&mut buf, window_id);
+    put_var_int(&mut buf, state_id);
+    put_var_int(&mut buf, slots.len() as i32);
+    for slot in slots {
+        put_slot(&mut buf, slot.as_ref());
+    }
+    put_slot(&mut buf, carried);
+    buf.freeze()
+}
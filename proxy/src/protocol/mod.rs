@@ -1 +1,49 @@
+//! Packets missing from `pumpkin-protocol`, and what is encoded by hand.
 
+pub mod chat;
+pub mod command;
+pub mod config;
+pub mod item;
+pub mod play;
+
+use bytes::{Buf, BufMut, BytesMut};
+
+pub fn put_var_int(buf: &mut BytesMut, value: i32) {
+    let mut value = value as u32;
+    while value & !0x7F != 0 {
+        buf.put_u8((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.put_u8(value as u8);
+}
+
+pub fn get_var_int(buf: &mut impl Buf) -> Result<i32, String> {
+    let mut value = 0u32;
+    for position in 0..5 {
+        if !buf.has_remaining() {
+            return Err("VarInt ends early".into());
+        }
+        let byte = buf.get_u8();
+        value |= u32::from(byte & 0x7F) << (position * 7);
+        if byte & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+    Err("VarInt is too long".into())
+}
+
+/// Reads a string prefixed with its length in bytes, refusing more than `max_chars` characters.
+pub fn get_string(buf: &mut impl Buf, max_chars: usize) -> Result<String, String> {
+    let length = get_var_int(buf)?;
+    let length = usize::try_from(length).map_err(|_| format!("Invalid string length {length}"))?;
+    // A character takes up to 4 bytes in UTF-8
+    if length > max_chars * 4 || length > buf.remaining() {
+        return Err(format!("String of {length} bytes is too long"));
+    }
+    let string = String::from_utf8(buf.copy_to_bytes(length).to_vec())
+        .map_err(|e| format!("Invalid string : {e}"))?;
+    if string.chars().count() > max_chars {
+        return Err(format!("String is longer than {max_chars} characters"));
+    }
+    Ok(string)
+}
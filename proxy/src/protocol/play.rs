@@ -0,0 +1,202 @@
+use pumpkin_data::packet::clientbound::{
+    PLAY_CHANGE_DIFFICULTY, PLAY_CHUNK_BATCH_FINISHED, PLAY_CHUNK_BATCH_START,
+    PLAY_FORGET_LEVEL_CHUNK, PLAY_GAME_EVENT, PLAY_PLAYER_POSITION, PLAY_SET_CHUNK_CACHE_CENTER,
+    PLAY_SET_CHUNK_CACHE_RADIUS, PLAY_SET_HELD_SLOT, PLAY_SET_SIMULATION_DISTANCE,
+};
+use pumpkin_data::packet::serverbound::{
+    PLAY_ACCEPT_TELEPORTATION, PLAY_CHAT_ACK, PLAY_CHAT_COMMAND, PLAY_CHUNK_BATCH_RECEIVED,
+    PLAY_COMMAND_SUGGESTION, PLAY_CONTAINER_CLICK, PLAY_CONTAINER_CLOSE, PLAY_MOVE_PLAYER_POS,
+    PLAY_MOVE_PLAYER_POS_ROT, PLAY_MOVE_PLAYER_ROT, PLAY_MOVE_PLAYER_STATUS_ONLY,
+    PLAY_SET_CARRIED_ITEM,
+};
+use pumpkin_protocol::codec::var_int::VarInt;
+use serde::{Deserialize, Serialize};
+use spacetimemc_proxy_macros::packet;
+
+#[derive(Serialize)]
+#[packet(PLAY_CHANGE_DIFFICULTY)]
+pub struct CChangeDifficulty {
+    pub difficulty: u8,
+    pub locked: bool,
+}
+
+/// Changes the server view distance, the client renders the lowest of it and its own setting.
+#[derive(Serialize)]
+#[packet(PLAY_SET_CHUNK_CACHE_RADIUS)]
+pub struct CSetChunkCacheRadius {
+    pub radius: VarInt,
+}
+
+#[derive(Serialize)]
+#[packet(PLAY_SET_SIMULATION_DISTANCE)]
+pub struct CSetSimulationDistance {
+    pub distance: VarInt,
+}
+
+/// The chunk the player is in, the client discards chunks too far from it.
+#[derive(Serialize)]
+#[packet(PLAY_SET_CHUNK_CACHE_CENTER)]
+pub struct CSetChunkCacheCenter {
+    pub chunk_x: VarInt,
+    pub chunk_z: VarInt,
+}
+
+/// Unloads a chunk, note the reversed order of coordinates.
+#[derive(Serialize)]
+#[packet(PLAY_FORGET_LEVEL_CHUNK)]
+pub struct CForgetLevelChunk {
+    pub chunk_z: i32,
+    pub chunk_x: i32,
+}
+
+#[derive(Serialize)]
+#[packet(PLAY_CHUNK_BATCH_START)]
+pub struct CChunkBatchStart;
+
+/// Ends a batch of chunks, the client answers with [SChunkBatchReceived].
+#[derive(Serialize)]
+#[packet(PLAY_CHUNK_BATCH_FINISHED)]
+pub struct CChunkBatchFinished {
+    pub batch_size: VarInt,
+}
+
+/// Game event telling the client to wait for the chunk it stands in before leaving the loading
+/// screen.
+pub const START_WAITING_FOR_CHUNKS: u8 = 13;
+
+#[derive(Serialize)]
+#[packet(PLAY_GAME_EVENT)]
+pub struct CGameEvent {
+    pub event: u8,
+    pub value: f32,
+}
+
+/// Teleports the player, who must confirm with [SAcceptTeleportation].
+#[derive(Serialize)]
+#[packet(PLAY_PLAYER_POSITION)]
+pub struct CPlayerPosition {
+    pub teleport_id: VarInt,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub velocity_x: f64,
+    pub velocity_y: f64,
+    pub velocity_z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Which of the above are relative, none of them for an absolute teleport
+    pub flags: i32,
+}
+
+#[derive(Deserialize)]
+#[packet(PLAY_ACCEPT_TELEPORTATION)]
+pub struct SAcceptTeleportation {
+    pub teleport_id: VarInt,
+}
+
+/// How many chunks per tick the client can handle, sent after each batch.
+#[derive(Deserialize)]
+#[packet(PLAY_CHUNK_BATCH_RECEIVED)]
+pub struct SChunkBatchReceived {
+    pub chunks_per_tick: f32,
+}
+
+/// Bit of the movement flags set while the player stands on the ground.
+pub const ON_GROUND: u8 = 0x01;
+
+#[derive(Deserialize)]
+#[packet(PLAY_MOVE_PLAYER_POS)]
+pub struct SMovePlayerPos {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    /// On ground and against a wall bits
+    pub flags: u8,
+}
+
+#[derive(Deserialize)]
+#[packet(PLAY_MOVE_PLAYER_POS_ROT)]
+pub struct SMovePlayerPosRot {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub flags: u8,
+}
+
+#[derive(Deserialize)]
+#[packet(PLAY_MOVE_PLAYER_ROT)]
+pub struct SMovePlayerRot {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub flags: u8,
+}
+
+/// Sent when only the flags changed, e.g. the player landed.
+#[derive(Deserialize)]
+#[packet(PLAY_MOVE_PLAYER_STATUS_ONLY)]
+pub struct SMovePlayerStatusOnly {
+    pub flags: u8,
+}
+
+/// Window id of the player inventory, always open as far as the server is concerned.
+pub const PLAYER_INVENTORY: i32 = 0;
+/// Crafting, armor, main inventory, hotbar then offhand.
+pub const PLAYER_INVENTORY_SLOTS: usize = 46;
+
+/// Selects a hotbar slot.
+#[derive(Serialize)]
+#[packet(PLAY_SET_HELD_SLOT)]
+pub struct CSetHeldSlot {
+    pub slot: VarInt,
+}
+
+/// Only the click itself is read, the slots the client predicted are replaced by what the module
+/// computes.
+#[derive(Deserialize)]
+#[packet(PLAY_CONTAINER_CLICK)]
+pub struct SContainerClick {
+    pub window_id: VarInt,
+    pub state_id: VarInt,
+    pub slot: i16,
+    pub button: i8,
+    pub mode: VarInt,
+}
+
+#[derive(Deserialize)]
+#[packet(PLAY_CONTAINER_CLOSE)]
+pub struct SContainerClose {
+    pub window_id: VarInt,
+}
+
+/// The player selected another hotbar slot.
+#[derive(Deserialize)]
+#[packet(PLAY_SET_CARRIED_ITEM)]
+pub struct SSetCarriedItem {
+    pub slot: i16,
+}
+
+/// Tells how many more messages the client saw, once it saw enough without chatting.
+#[derive(Deserialize)]
+#[packet(PLAY_CHAT_ACK)]
+pub struct SChatAck {
+    pub offset: VarInt,
+}
+
+/// A command the player typed, without its slash. Only commands with signed arguments are sent
+/// signed, none of the registered ones have any.
+#[derive(Deserialize)]
+#[packet(PLAY_CHAT_COMMAND)]
+pub struct SChatCommand {
+    pub command: String,
+}
+
+/// Sent while typing an argument whose suggestions the server provides.
+#[derive(Deserialize)]
+#[packet(PLAY_COMMAND_SUGGESTION)]
+pub struct SCommandSuggestion {
+    pub id: VarInt,
+    /// Everything typed up to the cursor, slash included
+    pub text: String,
+}
@@ -1,40 +1,88 @@
 use crate::actor_ref::ActorRef;
-use crate::client_actor::handshake::HandshakeHandler;
+use crate::client_actor::chat::{verify_profile_key, INVALID_PUBLIC_KEY_SIGNATURE};
+use crate::client_actor::session::{Connection, ConnectionMessage};
+use crate::database::pending::ReducerOutcome;
+use crate::database::{Database, InventoryAction};
 use crate::err::{SendError, TrySendError};
 use crate::module_bindings::autogen::BasicConfiguration;
+use crate::module_bindings::{
+    ChatMessage, ChatSession, Command, PermissionLvl, PlayerInventory, PlayerState, SignedChat,
+    Violation,
+};
+use crate::protocol::chat::SChatSessionUpdate;
+use crate::server_actor::config_diff::ConfigDiff;
 use crate::server_actor::connection_cache::CachedStatus;
 use crate::server_actor::key_store::KeyStore;
+use crate::server_actor::listener::{configured_addresses, Listener};
+use crate::world::generation::GeneratorMessage;
+use crate::world::{Location, World};
 use pumpkin::net::authentication::fetch_mojang_public_keys;
 use pumpkin_config::advanced_config;
 use rsa::RsaPublicKey;
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::default::Default;
-use std::num::Wrapping;
+use std::net::IpAddr;
+use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::net::TcpListener;
 use tokio::select;
 use tokio::signal::unix::{signal, Signal, SignalKind};
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio_util::sync::CancellationToken;
 use tokio_util::task::TaskTracker;
+use uuid::Uuid;
 
+#[derive(Clone)]
 pub struct Server {
     sender: mpsc::Sender<ServerMessage>,
 }
 
 impl Server {
-    pub async fn spawn(basic_configuration: &BasicConfiguration) -> Self {
+    pub async fn spawn(
+        basic_configuration: &BasicConfiguration,
+        database: Database,
+        world: World,
+    ) -> Self {
         let (sender, receiver) = mpsc::channel(16);
+        let server = Self { sender };
+        let relay = ServerRelay::spawn(server.clone());
+        database.watch_kicks(relay.clone());
+        database.watch_operators(relay.clone());
+        database.watch_inventories(relay.clone());
+        database.watch_chat(relay.clone());
+        database.watch_chat_sessions(relay.clone());
+        database.watch_commands(relay.clone());
 
-        let actor = ServerActor::new(basic_configuration, receiver, sender.clone()).await;
+        let actor = ServerActor::new(
+            basic_configuration,
+            database,
+            world,
+            receiver,
+            server.sender.clone(),
+        )
+        .await;
         tokio::spawn(actor.run());
 
-        Self { sender }
+        server
     }
 
-    pub async fn start(&self, address: String, death: oneshot::Sender<()>) {
-        self.send(ServerMessage::StartListener { address, death })
-            .await
-            // If we already fail to send when starting the server, might as well panic
-            .unwrap();
+    /// Starts listening on the configured addresses. The addresses actually bound are published
+    /// on `bound`, every time they change.
+    pub async fn start(
+        &self,
+        address: String,
+        bound: watch::Sender<String>,
+        death: oneshot::Sender<()>,
+    ) {
+        self.send(ServerMessage::StartListener {
+            address,
+            bound,
+            death,
+        })
+        .await
+        // If we already fail to send when starting the server, might as well panic
+        .unwrap();
     }
 
     fn from_address(sender: mpsc::Sender<ServerMessage>) -> Self {
@@ -52,46 +100,212 @@ impl ActorRef<ServerMessage> for Server {
     }
 }
 
+/// Queues messages for the server actor from database callbacks, which run on the connection's
+/// thread and must not block. Messages are delivered in order, waiting for room in the mailbox
+/// rather than being dropped when it is full.
+#[derive(Clone)]
+pub struct ServerRelay {
+    sender: mpsc::UnboundedSender<ServerMessage>,
+}
+
+impl ServerRelay {
+    /// Must be called within the runtime, which the relay forwards from.
+    pub fn spawn(server: Server) -> Self {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(message) = receiver.recv().await {
+                if server.send(message).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Self { sender }
+    }
+
+    pub fn send(&self, message: ServerMessage) {
+        if let Err(e) = self.sender.send(message) {
+            log::error!("Server stopped, dropping : {:?}", e.0);
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ServerMessage {
     Shutdown,
     GetStatus(oneshot::Sender<String>),
     StartListener {
         address: String,
+        bound: watch::Sender<String>,
         death: oneshot::Sender<()>,
     },
+    /// Stops accepting connections and lets the proxy die, as a shutdown signal would
+    StopListening,
     UpdateConfig {
         config: BasicConfiguration,
     },
+    /// Keys fetched in the background after the configuration changed
+    MojangPublicKeys {
+        /// Which refresh fetched them, only the latest one counts
+        generation: u64,
+        keys: Option<Vec<RsaPublicKey>>,
+    },
     CertificatePublicDer(oneshot::Sender<Box<[u8]>>),
     Decrypt {
         data: Box<[u8]>,
         reply_to: oneshot::Sender<Vec<u8>>,
     },
+    /// An authenticated player wants to join, the reply tells whether the module accepted them.
+    PlayerJoin {
+        profile_id: Uuid,
+        username: String,
+        connection_id: u64,
+        /// Checked against IP bans
+        address: IpAddr,
+        connection: Connection,
+        reply_to: oneshot::Sender<ReducerOutcome>,
+    },
+    PlayerLeave {
+        profile_id: Uuid,
+        connection_id: u64,
+    },
+    /// The module asked this proxy to disconnect one of its players
+    Kick {
+        kick_id: u64,
+        connection_id: u64,
+        reason: String,
+    },
+    OpLevelChanged {
+        profile_id: Uuid,
+        level: PermissionLvl,
+    },
+    /// Where a player moved, saved so they come back there once they reconnect
+    PlayerMoved {
+        profile_id: Uuid,
+        connection_id: u64,
+        location: Location,
+    },
+    /// A move of the player was refused and they were set back
+    MovementViolation {
+        profile_id: Uuid,
+        connection_id: u64,
+        violation: Violation,
+        detail: String,
+    },
+    /// A player did something with their inventory, for the module to apply
+    InventoryAction {
+        profile_id: Uuid,
+        connection_id: u64,
+        action: InventoryAction,
+    },
+    InventoryChanged {
+        profile_id: Uuid,
+        inventory: Box<PlayerInventory>,
+    },
+    /// A player said something, for the module to check and share with every proxy
+    SendChat {
+        profile_id: Uuid,
+        connection_id: u64,
+        content: String,
+        /// Only while chat reports are allowed
+        signed: Option<SignedChat>,
+    },
+    /// A client sent the key it signs messages with. The reply is the translation key to
+    /// disconnect it with if the key was not issued by Mojang.
+    StartChatSession {
+        profile_id: Uuid,
+        connection_id: u64,
+        update: Box<SChatSessionUpdate>,
+        reply_to: oneshot::Sender<Result<(), &'static str>>,
+    },
+    /// Told to every player of this proxy, for their clients to verify messages
+    ChatSessionStarted(Box<ChatSession>),
+    ChatSessionEnded(Uuid),
+    /// Delivered to every player of this proxy
+    Chat(Box<ChatMessage>),
+    /// Shown to one player only, e.g. why their message was refused
+    Feedback {
+        profile_id: Uuid,
+        message: String,
+    },
+    /// A player typed a command, for the module to run
+    RunCommand {
+        profile_id: Uuid,
+        connection_id: u64,
+        command: String,
+    },
+    /// The module ran a command of one of this proxy's players
+    CommandFeedback {
+        feedback_id: u64,
+        connection_id: u64,
+        message: String,
+    },
+    /// Online players whose username starts with `prefix`, for tab completion
+    SuggestPlayers {
+        prefix: String,
+        reply_to: oneshot::Sender<Vec<String>>,
+    },
+    /// What a player needs to enter the play state, `None` if they have no session
+    GetJoinInfo {
+        profile_id: Uuid,
+        reply_to: oneshot::Sender<Option<JoinInfo>>,
+    },
+}
+
+#[derive(Debug)]
+pub struct JoinInfo {
+    pub config: BasicConfiguration,
+    pub entity_id: u32,
+    pub op_level: PermissionLvl,
+    /// Where the player logged out, `None` if they never moved
+    pub state: Option<PlayerState>,
+    pub inventory: Option<PlayerInventory>,
+    pub held_slot: u8,
+    /// Usernames and chat sessions of the players who have one
+    pub chat_sessions: Vec<(String, ChatSession)>,
+    /// Every command, the player only sees those their level allows
+    pub commands: Vec<Command>,
+    pub world: World,
 }
 
 /// Actor for the server
 struct ServerActor {
+    config: BasicConfiguration,
     listing: CachedStatus,
     // connections: Vec<Connection>,
     message_receiver: mpsc::Receiver<ServerMessage>,
     auth_client: Option<reqwest::Client>,
     mojang_public_keys: Option<Vec<RsaPublicKey>>,
-    use_whitelist: bool,
+    /// Bumped on each refresh of the keys, so that a slow earlier fetch can not override them
+    mojang_keys_generation: u64,
     tasks: TaskTracker,
     self_addr: mpsc::Sender<ServerMessage>,
     key_store: KeyStore,
+    database: Database,
+    world: World,
+    /// Authenticated connections, keyed by connection id
+    connections: HashMap<u64, Connection>,
+    /// Listeners keyed by their configured address
+    listeners: HashMap<String, Listener>,
+    /// Cancelled on shutdown signals, stopping every listener
+    stop_listening: CancellationToken,
+    /// Tasks of the listeners and the connections they accepted
+    connection_tasks: TaskTracker,
+    next_connection_id: Arc<AtomicUsize>,
+    bound_addresses: Option<watch::Sender<String>>,
 }
 
 impl ServerActor {
     async fn new(
         basic_configuration: &BasicConfiguration,
+        database: Database,
+        world: World,
         message_receiver: mpsc::Receiver<ServerMessage>,
         self_addr: mpsc::Sender<ServerMessage>,
     ) -> Self {
         let auth_client = Self::auth_client(basic_configuration);
 
         Self {
+            config: basic_configuration.clone(),
             listing: CachedStatus::from_config(basic_configuration),
             // connections: Vec::new(),
             message_receiver,
@@ -100,11 +314,19 @@ impl ServerActor {
             } else {
                 None
             },
+            mojang_keys_generation: 0,
             auth_client,
-            use_whitelist: basic_configuration.white_list,
             tasks: TaskTracker::new(),
             self_addr,
             key_store: Default::default(),
+            database,
+            world,
+            connections: HashMap::new(),
+            listeners: HashMap::new(),
+            stop_listening: CancellationToken::new(),
+            connection_tasks: TaskTracker::new(),
+            next_connection_id: Arc::new(AtomicUsize::new(0)),
+            bound_addresses: None,
         }
     }
 
@@ -141,6 +363,31 @@ impl ServerActor {
         }
     }
 
+    /// The id and properly cased name of the Mojang account with this username.
+    async fn fetch_profile(
+        auth_client: &reqwest::Client,
+        username: &str,
+    ) -> Result<(Uuid, String), String> {
+        #[derive(Deserialize)]
+        struct Profile {
+            id: Uuid,
+            name: String,
+        }
+
+        let profile = auth_client
+            .get(format!(
+                "https://api.mojang.com/users/profiles/minecraft/{username}"
+            ))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| e.to_string())?
+            .json::<Profile>()
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok((profile.id, profile.name))
+    }
+
     async fn run(mut self) {
         // TODO : select between message_receiver and packet_receiver
         while let Some(msg) = self.message_receiver.recv().await {
@@ -155,15 +402,20 @@ impl ServerActor {
         match msg {
             ServerMessage::Shutdown => self.shutdown().await,
             ServerMessage::GetStatus(reply_to) => {
+                self.listing.set_online(self.database.online_players());
                 let _ = reply_to.send(self.listing.get_status_string());
             }
-            ServerMessage::StartListener { address, death } => {
-                self.start_listener(address, death).await
-            }
-            ServerMessage::UpdateConfig { config } => {
-                // TODO : this should update rather than override (to keep player count)
-                // Might not even need to be cached at all
-                self.listing = CachedStatus::from_config(&config);
+            ServerMessage::StartListener {
+                address,
+                bound,
+                death,
+            } => self.start_listener(address, bound, death).await,
+            ServerMessage::StopListening => self.stop_listening.cancel(),
+            ServerMessage::UpdateConfig { config } => self.update_config(config).await,
+            ServerMessage::MojangPublicKeys { generation, keys } => {
+                if generation == self.mojang_keys_generation {
+                    self.mojang_public_keys = keys;
+                }
             }
             ServerMessage::CertificatePublicDer(reply_to) => {
                 let _ = reply_to.send(self.key_store.get_public_der().into());
@@ -179,6 +431,253 @@ impl ServerActor {
                     }
                 }
             }
+            ServerMessage::PlayerJoin {
+                profile_id,
+                username,
+                connection_id,
+                address,
+                connection,
+                reply_to,
+            } => {
+                self.connections.insert(connection_id, connection);
+                self.database
+                    .join_player(profile_id, username, connection_id, address, reply_to)
+            }
+            ServerMessage::PlayerLeave {
+                profile_id,
+                connection_id,
+            } => {
+                self.connections.remove(&connection_id);
+                self.database.leave_player(profile_id, connection_id)
+            }
+            ServerMessage::Kick {
+                kick_id,
+                connection_id,
+                reason,
+            } => {
+                if let Some(connection) = self.connections.get(&connection_id).cloned() {
+                    // Waits for room in the connection mailbox, which only closes once it left
+                    self.tasks.spawn(async move {
+                        let _ = connection.send(ConnectionMessage::Kick { reason }).await;
+                    });
+                }
+                self.database.acknowledge_kick(kick_id);
+            }
+            ServerMessage::OpLevelChanged { profile_id, level } => {
+                for connection in self.connections.values() {
+                    if connection.profile_id() == profile_id {
+                        let _ = connection.try_send(ConnectionMessage::SetOpLevel(level));
+                    }
+                }
+            }
+            ServerMessage::PlayerMoved {
+                profile_id,
+                connection_id,
+                location,
+            } => self
+                .database
+                .move_player(profile_id, connection_id, &location),
+            ServerMessage::MovementViolation {
+                profile_id,
+                connection_id,
+                violation,
+                detail,
+            } => self
+                .database
+                .report_violation(profile_id, connection_id, violation, detail),
+            ServerMessage::InventoryAction {
+                profile_id,
+                connection_id,
+                action,
+            } => self
+                .database
+                .inventory_action(profile_id, connection_id, action),
+            ServerMessage::InventoryChanged {
+                profile_id,
+                inventory,
+            } => {
+                for connection in self.connections.values() {
+                    if connection.profile_id() == profile_id {
+                        let _ = connection
+                            .try_send(ConnectionMessage::InventoryChanged(inventory.clone()));
+                    }
+                }
+            }
+            ServerMessage::SendChat {
+                profile_id,
+                connection_id,
+                content,
+                signed,
+            } => self
+                .database
+                .send_chat(profile_id, connection_id, content, signed),
+            ServerMessage::StartChatSession {
+                profile_id,
+                connection_id,
+                update,
+                reply_to,
+            } => {
+                let verified = match &self.mojang_public_keys {
+                    Some(keys) => verify_profile_key(keys, profile_id, &update),
+                    None => Err(INVALID_PUBLIC_KEY_SIGNATURE),
+                };
+                if verified.is_ok() {
+                    self.database
+                        .set_chat_session(profile_id, connection_id, &update);
+                }
+                let _ = reply_to.send(verified);
+            }
+            ServerMessage::ChatSessionStarted(session) => {
+                let profile_id = Uuid::from_u128(session.profile_id);
+                let name = self.database.username(profile_id).unwrap_or_default();
+                for connection in self.connections.values() {
+                    let _ = connection.try_send(ConnectionMessage::ChatSessionStarted {
+                        name: name.clone(),
+                        session: session.clone(),
+                    });
+                }
+            }
+            ServerMessage::ChatSessionEnded(profile_id) => {
+                for connection in self.connections.values() {
+                    let _ = connection.try_send(ConnectionMessage::ChatSessionEnded(profile_id));
+                }
+            }
+            ServerMessage::Chat(message) => {
+                for connection in self.connections.values() {
+                    let _ = connection.try_send(ConnectionMessage::Chat(message.clone()));
+                }
+            }
+            ServerMessage::Feedback {
+                profile_id,
+                message,
+            } => {
+                for connection in self.connections.values() {
+                    if connection.profile_id() == profile_id {
+                        let _ = connection.try_send(ConnectionMessage::Feedback(message.clone()));
+                    }
+                }
+            }
+            ServerMessage::RunCommand {
+                profile_id,
+                connection_id,
+                command,
+            } => {
+                let unknown = whitelisted_name(&command)
+                    .filter(|name| !self.database.is_known_username(name))
+                    .map(str::to_string);
+                match (unknown, self.auth_client.clone()) {
+                    // The module only knows players who joined, vanilla whitelists anyone
+                    (Some(name), Some(client)) => {
+                        let database = self.database.clone();
+                        self.tasks.spawn(async move {
+                            match Self::fetch_profile(&client, &name).await {
+                                Ok((id, name)) => database.add_player(name, id),
+                                Err(e) => log::warn!("Failed to look up player {name} : {e}"),
+                            }
+                            database.run_command(profile_id, connection_id, command);
+                        });
+                    }
+                    _ => self
+                        .database
+                        .run_command(profile_id, connection_id, command),
+                }
+            }
+            ServerMessage::CommandFeedback {
+                feedback_id,
+                connection_id,
+                message,
+            } => {
+                if let Some(connection) = self.connections.get(&connection_id) {
+                    let _ = connection.try_send(ConnectionMessage::CommandFeedback(message));
+                }
+                self.database.acknowledge_command_feedback(feedback_id);
+            }
+            ServerMessage::SuggestPlayers { prefix, reply_to } => {
+                let _ = reply_to.send(self.database.online_usernames(&prefix));
+            }
+            ServerMessage::GetJoinInfo {
+                profile_id,
+                reply_to,
+            } => {
+                let info = self
+                    .database
+                    .entity_id(profile_id)
+                    .map(|entity_id| JoinInfo {
+                        config: self.config.clone(),
+                        entity_id,
+                        op_level: self.database.permission_level(profile_id),
+                        state: self.database.player_state(profile_id),
+                        inventory: self.database.inventory(profile_id),
+                        held_slot: self.database.held_slot(profile_id),
+                        chat_sessions: self
+                            .database
+                            .chat_sessions()
+                            .into_iter()
+                            .map(|session| {
+                                let profile_id = Uuid::from_u128(session.profile_id);
+                                let name = self.database.username(profile_id).unwrap_or_default();
+                                (name, session)
+                            })
+                            .collect(),
+                        commands: self.database.commands(),
+                        world: self.world.clone(),
+                    });
+                let _ = reply_to.send(info);
+            }
+        }
+    }
+
+    /// Applies the settings that changed, leaving everything else (e.g. player count) untouched.
+    async fn update_config(&mut self, config: BasicConfiguration) {
+        let diff = ConfigDiff::between(&self.config, &config);
+        log::debug!("Applying configuration changes : {diff:?}");
+        self.config = config;
+
+        if diff.status {
+            self.listing.update(&self.config);
+        }
+        if diff.online_mode {
+            self.auth_client = Self::auth_client(&self.config);
+        }
+        if diff.online_mode || diff.chat_reports {
+            self.refresh_mojang_public_keys();
+        }
+        if diff.seed {
+            let _ = self
+                .world
+                .generator()
+                .send(GeneratorMessage::SetSeed(self.config.seed.clone()))
+                .await;
+        }
+        if diff.server_address && self.bound_addresses.is_some() {
+            log::info!("Server address changed to {}", self.config.server_address);
+            let address = self.config.server_address.clone();
+            self.bind(&address).await;
+        }
+        if diff.players {
+            for connection in self.connections.values() {
+                let _ = connection.try_send(ConnectionMessage::ConfigUpdated(Box::new(
+                    self.config.clone(),
+                )));
+            }
+        }
+    }
+
+    /// Fetching the keys can take a while, so the answer comes back as a message. Answers of
+    /// earlier refreshes are ignored, whatever order they arrive in.
+    fn refresh_mojang_public_keys(&mut self) {
+        self.mojang_public_keys = None;
+        self.mojang_keys_generation += 1;
+        if let Some(client) = self.auth_client.clone() {
+            let config = self.config.clone();
+            let self_addr = self.self_addr.clone();
+            let generation = self.mojang_keys_generation;
+            self.tasks.spawn(async move {
+                let keys = Self::mojang_pubkeys(&config, &client).await;
+                let _ = self_addr
+                    .send(ServerMessage::MojangPublicKeys { generation, keys })
+                    .await;
+            });
         }
     }
 
@@ -198,61 +697,89 @@ impl ServerActor {
         log::debug!("Closed message receiver");
     }
 
-    async fn start_listener(&self, server_address: String, death: oneshot::Sender<()>) {
-        let tasks = TaskTracker::new();
-        tasks.spawn(Self::run_listener(
-            server_address,
-            tasks.clone(),
-            self.self_addr.clone(),
-            death,
-        ));
-    }
-
-    async fn run_listener(
+    async fn start_listener(
+        &mut self,
         server_address: String,
-        tasks: TaskTracker,
-        self_addr: mpsc::Sender<ServerMessage>,
+        bound: watch::Sender<String>,
         death: oneshot::Sender<()>,
     ) {
-        let mut master_client_id: Wrapping<usize> = Wrapping(0);
-        let (mut sigint, mut sighup, mut sigterm) = Self::signals();
-        let listener = TcpListener::bind(server_address)
+        self.bound_addresses = Some(bound);
+        self.bind(&server_address).await;
+        if self.listeners.is_empty() {
+            log::error!("Could not bind any of {server_address}");
+            self.stop_listening.cancel();
+        }
+
+        let stop_listening = self.stop_listening.clone();
+        tokio::spawn(async move {
+            let (mut sigint, mut sighup, mut sigterm) = Self::signals();
+            select! {
+                _ = sigint.recv() => log::info!("Got interrupt"),
+                _ = sighup.recv() => log::info!("Got hangup"),
+                _ = sigterm.recv() => log::info!("Got terminate"),
+                _ = stop_listening.cancelled() => {}
+            }
+            stop_listening.cancel();
+
+            log::info!("Server actor death");
+            let _ = death.send(());
+        });
+    }
+
+    /// Makes the listeners match the given addresses : new ones are bound, removed ones stop
+    /// accepting, unchanged ones are kept. Connections are never dropped.
+    ///
+    /// Removed listeners only stop once every new address is bound, so that a failed change does
+    /// not leave the proxy unreachable.
+    async fn bind(&mut self, server_address: &str) {
+        let wanted = configured_addresses(server_address);
+        self.listeners.retain(|_, listener| !listener.is_stopped());
+
+        let mut all_bound = true;
+        for address in &wanted {
+            if self.listeners.contains_key(address) {
+                continue;
+            }
+            match Listener::bind(
+                address,
+                self.stop_listening.child_token(),
+                &self.connection_tasks,
+                self.next_connection_id.clone(),
+                Server::from_address(self.self_addr.clone()),
+            )
             .await
-            .expect("Failed to start `TcpListener`");
-
-        loop {
-            match select! {
-                biased;
-                _ = sigint.recv() => None,
-                _ = sighup.recv() => None,
-                _ = sigterm.recv() => None,
-                open = listener.accept() => Some(open),
-            } {
-                Some(Ok((connection, client_addr))) => {
-                    let id = master_client_id.0;
-                    master_client_id += 1;
-                    log::info!("Accepting connection from: {client_addr} (id {id})");
-                    HandshakeHandler::spawn(
-                        connection,
-                        client_addr,
-                        id,
-                        &tasks,
-                        Server::from_address(self_addr.clone()),
-                    );
+            {
+                Ok(listener) => {
+                    self.listeners.insert(address.clone(), listener);
                 }
-                Some(Err(e)) => {
-                    log::error!("Failed to accept connection: {e}");
-                    break;
-                }
-                None => {
-                    log::info!("Got none (interrupt)");
-                    break;
+                Err(e) => {
+                    log::error!("Failed to bind {address}: {e}");
+                    all_bound = false;
                 }
             }
         }
 
-        log::info!("Server actor death");
-        death.send(()).unwrap()
+        if all_bound {
+            self.listeners.retain(|address, listener| {
+                let keep = wanted.contains(address);
+                if !keep {
+                    listener.stop();
+                }
+                keep
+            });
+        } else {
+            log::warn!("Keeping the previous listeners until every address is bound");
+        }
+
+        if let Some(bound) = &self.bound_addresses {
+            let mut addresses = self
+                .listeners
+                .values()
+                .map(|listener| listener.local_addr.to_string())
+                .collect::<Vec<_>>();
+            addresses.sort();
+            bound.send_replace(addresses.join(","));
+        }
     }
 
     // TODO : these are Unix specific, on Win it should be ctrl_c / ctrl_break / ctrl_close / ctrl_shutdown
@@ -264,3 +791,13 @@ impl ServerActor {
         (sigint, sighup, sigterm)
     }
 }
+
+/// The username `/whitelist add` names, unless given a UUID, which no username is as long as.
+fn whitelisted_name(command: &str) -> Option<&str> {
+    let target = command.strip_prefix("whitelist add ")?.trim();
+    let valid = (1..=16).contains(&target.len())
+        && target
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    valid.then_some(target)
+}
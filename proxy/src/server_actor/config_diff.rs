@@ -0,0 +1,31 @@
+use crate::module_bindings::BasicConfiguration;
+
+/// Which parts of the proxy are affected by a configuration change.
+#[derive(Debug, Default)]
+pub struct ConfigDiff {
+    /// MOTD, player limit or secure chat, shown in the server listing
+    pub status: bool,
+    pub online_mode: bool,
+    pub chat_reports: bool,
+    pub server_address: bool,
+    pub seed: bool,
+    /// Settings connected players must be told about
+    pub players: bool,
+}
+
+impl ConfigDiff {
+    pub fn between(old: &BasicConfiguration, new: &BasicConfiguration) -> Self {
+        Self {
+            status: old.motd != new.motd
+                || old.max_players != new.max_players
+                || old.allow_chat_reports != new.allow_chat_reports,
+            online_mode: old.online_mode != new.online_mode,
+            chat_reports: old.allow_chat_reports != new.allow_chat_reports,
+            server_address: old.server_address != new.server_address,
+            seed: old.seed != new.seed,
+            players: old.default_difficulty != new.default_difficulty
+                || old.view_distance != new.view_distance
+                || old.simulation_distance != new.simulation_distance,
+        }
+    }
+}
@@ -74,8 +74,8 @@ impl CachedBranding {
 
 impl CachedStatus {
     #[must_use]
-    pub fn new(max_players: u32, motd: String) -> Self {
-        let status_response = Self::build_response(max_players, motd);
+    pub fn new(max_players: u32, motd: String, enforce_secure_chat: bool) -> Self {
+        let status_response = Self::build_response(max_players, motd, enforce_secure_chat);
         let status_response_json = serde_json::to_string(&status_response)
             .expect("Failed to parse status response into JSON");
 
@@ -86,7 +86,11 @@ impl CachedStatus {
     }
 
     pub fn from_config(config: &BasicConfiguration) -> Self {
-        Self::new(config.max_players, config.motd.clone())
+        Self::new(
+            config.max_players,
+            config.motd.clone(),
+            config.allow_chat_reports,
+        )
     }
 
     pub fn get_status(&self) -> CStatusResponse<'_> {
@@ -132,9 +136,25 @@ impl CachedStatus {
             .expect("Failed to parse status response into JSON");
     }
 
+    /// Sets the number of players online across all proxies.
+    pub fn set_online(&mut self, online: u32) {
+        if let Some(players) = &mut self.status_response.players {
+            if players.online == online {
+                return;
+            }
+            players.online = online;
+        }
+
+        self.status_response_json = serde_json::to_string(&self.status_response)
+            .expect("Failed to parse status response into JSON");
+    }
+
     pub fn update(&mut self, new_config: &BasicConfiguration) {
-        let mut new_response =
-            Self::build_response(new_config.max_players, new_config.motd.clone());
+        let mut new_response = Self::build_response(
+            new_config.max_players,
+            new_config.motd.clone(),
+            new_config.allow_chat_reports,
+        );
 
         if let Some(players) = self.status_response.players.take() {
             new_response.players = Some(Players {
@@ -148,7 +168,13 @@ impl CachedStatus {
             .expect("Failed to parse status response into JSON");
     }
 
-    pub fn build_response(max_players: u32, motd: String) -> StatusResponse {
+    /// Clients warn about unsigned chat unless `enforce_secure_chat` is set, which only holds
+    /// once chat reports are allowed.
+    pub fn build_response(
+        max_players: u32,
+        motd: String,
+        enforce_secure_chat: bool,
+    ) -> StatusResponse {
         let favicon = None; /*if config.use_favicon {
         let icon_path = &config.favicon_path;
         log::debug!("Loading server favicon from '{icon_path}'");
@@ -191,9 +217,7 @@ impl CachedStatus {
             }),
             description: motd,
             favicon,
-            // This should stay true even when reports are disabled.
-            // It prevents the annoying popup when joining the server.
-            enforce_secure_chat: true,
+            enforce_secure_chat,
         }
     }
 }
@@ -0,0 +1,93 @@
+use crate::client_actor::handshake::HandshakeHandler;
+use crate::server_actor::actor::Server;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::select;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+/// Splits the configured `server_address` into the addresses to bind, one listener each. Empty
+/// segments are skipped, as the module does when validating it.
+pub fn configured_addresses(server_address: &str) -> Vec<String> {
+    server_address
+        .split(',')
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// A bound socket accepting connections until stopped.
+///
+/// Stopping a listener only stops accepting, connections it already accepted keep running.
+pub struct Listener {
+    /// The address actually bound, which differs from the configured one when using port 0
+    pub local_addr: SocketAddr,
+    stop: CancellationToken,
+}
+
+impl Listener {
+    /// Binds the address and starts accepting on it. Each accepted connection gets an id from
+    /// `next_id`, shared by all listeners so ids stay unique across them.
+    pub async fn bind(
+        address: &str,
+        stop: CancellationToken,
+        tasks: &TaskTracker,
+        next_id: Arc<AtomicUsize>,
+        server: Server,
+    ) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(address).await?;
+        let local_addr = listener.local_addr()?;
+        tasks.spawn(Self::run(
+            listener,
+            local_addr,
+            stop.clone(),
+            tasks.clone(),
+            next_id,
+            server,
+        ));
+        Ok(Self { local_addr, stop })
+    }
+
+    pub fn stop(&self) {
+        self.stop.cancel();
+    }
+
+    /// Whether the listener was stopped or gave up after an error.
+    pub fn is_stopped(&self) -> bool {
+        self.stop.is_cancelled()
+    }
+
+    async fn run(
+        listener: TcpListener,
+        local_addr: SocketAddr,
+        stop: CancellationToken,
+        tasks: TaskTracker,
+        next_id: Arc<AtomicUsize>,
+        server: Server,
+    ) {
+        log::info!("Listening on {local_addr}");
+        loop {
+            match select! {
+                biased;
+                _ = stop.cancelled() => None,
+                open = listener.accept() => Some(open),
+            } {
+                Some(Ok((connection, client_addr))) => {
+                    let id = next_id.fetch_add(1, Ordering::Relaxed);
+                    log::info!("Accepting connection from: {client_addr} (id {id})");
+                    HandshakeHandler::spawn(connection, client_addr, id, &tasks, server.clone());
+                }
+                Some(Err(e)) => {
+                    log::error!("Failed to accept connection on {local_addr}: {e}");
+                    break;
+                }
+                None => break,
+            }
+        }
+        stop.cancel();
+        log::info!("Stopped listening on {local_addr}");
+    }
+}
@@ -1,5 +1,7 @@
 pub mod actor;
+pub mod config_diff;
 pub mod connection_cache;
 pub mod key_store;
+pub mod listener;
 
 pub const CURRENT_MC_VERSION: &str = "1.21.5";
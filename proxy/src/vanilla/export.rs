@@ -0,0 +1,211 @@
+use crate::module_bindings::{
+    BasicConfiguration, DbConnection, Difficulty, GameMode, OperatorTableAccess, PermissionLvl,
+    PlayerTableAccess, ServerBasicConfigTableAccess, WhitelistTableAccess,
+};
+use crate::vanilla::lists::{
+    self, BannedIpEntry, BannedPlayerEntry, OpEntry, UserCacheEntry, UserEntry, BANNED_IPS,
+    BANNED_PLAYERS, OPS, USER_CACHE, WHITELIST,
+};
+use crate::vanilla::properties::Properties;
+use crate::vanilla::{Bans, SERVER_PROPERTIES};
+use chrono::Utc;
+use serde::Serialize;
+use spacetimedb_sdk::{Table, Timestamp};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Vanilla keeps usercache entries for a month after the player was last seen.
+const USER_CACHE_LIFETIME: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Reason vanilla shows for bans issued without one.
+const DEFAULT_BAN_REASON: &str = "Banned by an operator.";
+
+/// Writes the configuration and lists as a vanilla server would, overwriting files in `dir`.
+pub async fn run(db: &DbConnection, dir: &Path) -> Result<(), String> {
+    let Some(config) = db.db.server_basic_config().iter().next() else {
+        return Err("Missing basic server configuration".into());
+    };
+    let bans = Bans::request(db).await?;
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {} : {e}", dir.display()))?;
+
+    let path = dir.join(SERVER_PROPERTIES);
+    let comments = [
+        "Minecraft server properties".to_string(),
+        format!(
+            "Exported from SpaceTimeMC on {}",
+            Utc::now().format(lists::DATE_FORMAT)
+        ),
+    ];
+    let comments = comments.iter().map(String::as_str).collect::<Vec<_>>();
+    std::fs::write(&path, properties(&config).write(&comments))
+        .map_err(|e| format!("Failed to write {} : {e}", path.display()))?;
+
+    let names = db
+        .db
+        .player()
+        .iter()
+        .filter_map(|player| Some((player.profile_id, player.last_known_username?)))
+        .collect::<HashMap<_, _>>();
+    let name = |profile_id: u128, name: Option<String>| {
+        name.or_else(|| names.get(&profile_id).cloned())
+            .unwrap_or_default()
+    };
+
+    let whitelist = db
+        .db
+        .whitelist()
+        .iter()
+        .map(|entry| UserEntry {
+            uuid: Uuid::from_u128(entry.profile_id),
+            name: name(entry.profile_id, entry.name),
+        })
+        .collect::<Vec<_>>();
+    write_list(dir, WHITELIST, &whitelist)?;
+
+    let ops = db
+        .db
+        .operator()
+        .iter()
+        .map(|op| OpEntry {
+            uuid: Uuid::from_u128(op.profile_id),
+            name: name(op.profile_id, op.name),
+            level: permission_level(&op.level),
+            bypasses_player_limit: op.bypasses_player_limit,
+        })
+        .collect::<Vec<_>>();
+    write_list(dir, OPS, &ops)?;
+
+    let banned_players = bans
+        .players
+        .into_iter()
+        .map(|ban| BannedPlayerEntry {
+            uuid: Uuid::from_u128(ban.profile_id),
+            name: name(ban.profile_id, ban.name),
+            created: lists::format_date(ban.created_at),
+            source: ban.source,
+            expires: lists::format_expiry(ban.expires_at),
+            reason: ban.reason.unwrap_or_else(|| DEFAULT_BAN_REASON.into()),
+        })
+        .collect::<Vec<_>>();
+    write_list(dir, BANNED_PLAYERS, &banned_players)?;
+
+    let banned_ips = bans
+        .ips
+        .into_iter()
+        .map(|ban| BannedIpEntry {
+            ip: ban.ip,
+            created: lists::format_date(ban.created_at),
+            source: ban.source,
+            expires: lists::format_expiry(ban.expires_at),
+            reason: ban.reason.unwrap_or_else(|| DEFAULT_BAN_REASON.into()),
+        })
+        .collect::<Vec<_>>();
+    write_list(dir, BANNED_IPS, &banned_ips)?;
+
+    let user_cache = db
+        .db
+        .player()
+        .iter()
+        .filter_map(|player| {
+            let expires_on = Timestamp::from_micros_since_unix_epoch(
+                player.last_seen.to_micros_since_unix_epoch()
+                    + USER_CACHE_LIFETIME.as_micros() as i64,
+            );
+            Some(UserCacheEntry {
+                name: player.last_known_username?,
+                uuid: Uuid::from_u128(player.profile_id),
+                expires_on: lists::format_date(expires_on),
+            })
+        })
+        .collect::<Vec<_>>();
+    write_list(dir, USER_CACHE, &user_cache)?;
+
+    println!(
+        "Exported the configuration, {} whitelisted, {} ops, {} banned players, {} banned IPs \
+        and {} cached users to {}",
+        whitelist.len(),
+        ops.len(),
+        banned_players.len(),
+        banned_ips.len(),
+        user_cache.len(),
+        dir.display()
+    );
+    Ok(())
+}
+
+fn write_list<T: Serialize>(dir: &Path, file: &str, entries: &[T]) -> Result<(), String> {
+    let path = dir.join(file);
+    lists::write(&path, entries).map_err(|e| format!("Failed to write {} : {e}", path.display()))
+}
+
+/// The settings vanilla knows about, the others (e.g. TPS) have no equivalent.
+fn properties(config: &BasicConfiguration) -> Properties {
+    let mut properties = Properties::default();
+
+    // Vanilla binds a single address, the first one configured
+    let address = config
+        .server_address
+        .split(',')
+        .find_map(|address| address.trim().parse::<SocketAddr>().ok());
+    if let Some(address) = address {
+        if address.ip().is_unspecified() {
+            properties.set("server-ip", "");
+        } else {
+            properties.set("server-ip", address.ip());
+        }
+        properties.set("server-port", address.port());
+    }
+
+    properties.set("level-seed", &config.seed);
+    properties.set("max-players", config.max_players);
+    properties.set("view-distance", config.view_distance);
+    properties.set("simulation-distance", config.simulation_distance);
+    properties.set("difficulty", difficulty(&config.default_difficulty));
+    properties.set(
+        "op-permission-level",
+        permission_level(&config.op_permission_level),
+    );
+    properties.set("allow-nether", config.allow_nether);
+    properties.set("hardcore", config.hardcore);
+    properties.set("online-mode", config.online_mode);
+    properties.set("motd", &config.motd);
+    properties.set("gamemode", game_mode(&config.default_gamemode));
+    properties.set("force-gamemode", config.force_gamemode);
+    properties.set("level-name", &config.default_level_name);
+    properties.set("enforce-secure-profile", config.allow_chat_reports);
+    properties.set("white-list", config.white_list);
+    properties.set("enforce-whitelist", config.enforce_whitelist);
+    properties
+}
+
+fn difficulty(difficulty: &Difficulty) -> &'static str {
+    match difficulty {
+        Difficulty::Peaceful => "peaceful",
+        Difficulty::Easy => "easy",
+        Difficulty::Normal => "normal",
+        Difficulty::Hard => "hard",
+    }
+}
+
+fn game_mode(game_mode: &GameMode) -> &'static str {
+    match game_mode {
+        GameMode::Survival => "survival",
+        GameMode::Creative => "creative",
+        GameMode::Adventure => "adventure",
+        GameMode::Spectator => "spectator",
+    }
+}
+
+fn permission_level(level: &PermissionLvl) -> u8 {
+    match level {
+        PermissionLvl::Zero => 0,
+        PermissionLvl::One => 1,
+        PermissionLvl::Two => 2,
+        PermissionLvl::Three => 3,
+        PermissionLvl::Four => 4,
+    }
+}
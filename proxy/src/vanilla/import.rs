@@ -0,0 +1,513 @@
+use crate::database::pending::PendingCalls;
+use crate::module_bindings::{
+    add_player, ban_ip, ban_player, op_player, update_config, whitelist_add, BasicConfiguration,
+    ConfigurationUpdate, DbConnection, Difficulty, GameMode, OperatorTableAccess, PermissionLvl,
+    ServerBasicConfigTableAccess, WhitelistTableAccess,
+};
+use crate::vanilla::lists::{
+    self, BannedIpEntry, BannedPlayerEntry, OpEntry, UserEntry, BANNED_IPS, BANNED_PLAYERS, OPS,
+    WHITELIST,
+};
+use crate::vanilla::properties::Properties;
+use crate::vanilla::{Bans, SERVER_PROPERTIES};
+use spacetimedb_sdk::{Table, Timestamp};
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::time::timeout;
+use uuid::Uuid;
+
+/// How long to wait for the database to answer each change.
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A reducer call made by the import, calls are sent in order.
+enum Call {
+    /// Makes the username known, so the lists show it
+    AddPlayer {
+        uuid: Uuid,
+        name: String,
+    },
+    UpdateConfig(ConfigurationUpdate),
+    Whitelist(Uuid),
+    Op {
+        uuid: Uuid,
+        level: PermissionLvl,
+        bypasses_player_limit: bool,
+    },
+    BanPlayer {
+        uuid: Uuid,
+        reason: String,
+        expires_at: Option<Timestamp>,
+    },
+    BanIp {
+        ip: String,
+        reason: String,
+        expires_at: Option<Timestamp>,
+    },
+}
+
+impl Call {
+    /// Identifies the call, the reducer callbacks rebuild it from the reducer arguments.
+    fn key(&self) -> String {
+        match self {
+            Call::AddPlayer { uuid, .. } => format!("add_player {uuid}"),
+            Call::UpdateConfig(_) => "update_config".into(),
+            Call::Whitelist(uuid) => format!("whitelist_add {uuid}"),
+            Call::Op { uuid, .. } => format!("op_player {uuid}"),
+            Call::BanPlayer { uuid, .. } => format!("ban_player {uuid}"),
+            Call::BanIp { ip, .. } => format!("ban_ip {ip}"),
+        }
+    }
+
+    fn send(self, db: &DbConnection) -> spacetimedb_sdk::Result<()> {
+        match self {
+            Call::AddPlayer { uuid, name } => db.reducers.add_player(name, uuid.to_string()),
+            Call::UpdateConfig(update) => db.reducers.update_config(update, None),
+            Call::Whitelist(uuid) => db.reducers.whitelist_add(uuid.to_string(), None),
+            Call::Op {
+                uuid,
+                level,
+                bypasses_player_limit,
+            } => db
+                .reducers
+                .op_player(uuid.to_string(), Some(level), bypasses_player_limit, None),
+            Call::BanPlayer {
+                uuid,
+                reason,
+                expires_at,
+            } => db
+                .reducers
+                .ban_player(uuid.to_string(), Some(reason), expires_at, None),
+            Call::BanIp {
+                ip,
+                reason,
+                expires_at,
+            } => db.reducers.ban_ip(ip, Some(reason), expires_at, None),
+        }
+    }
+}
+
+/// What importing the files of a vanilla server changes, computed against the current tables.
+///
+/// Only additions and changes are imported : entries missing from the files are left alone.
+#[derive(Default)]
+pub struct Import {
+    /// Human readable description of each change
+    changes: Vec<String>,
+    calls: Vec<Call>,
+    /// Players already given an `AddPlayer` call
+    known_players: HashSet<Uuid>,
+}
+
+impl Import {
+    /// Reads `server.properties` and the JSON lists from `dir`, missing files are skipped.
+    pub fn plan(db: &DbConnection, bans: &Bans, dir: &Path) -> Result<Self, String> {
+        let mut import = Self::default();
+
+        let path = dir.join(SERVER_PROPERTIES);
+        match std::fs::read_to_string(&path) {
+            Ok(content) => import.plan_config(db, &Properties::parse(&content))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                log::info!("No {}, keeping the current configuration", path.display());
+            }
+            Err(e) => return Err(format!("Failed to read {} : {e}", path.display())),
+        }
+
+        if let Some(entries) = read_list::<UserEntry>(dir, WHITELIST)? {
+            import.plan_whitelist(db, entries);
+        }
+        if let Some(entries) = read_list::<OpEntry>(dir, OPS)? {
+            import.plan_ops(db, entries)?;
+        }
+        if let Some(entries) = read_list::<BannedPlayerEntry>(dir, BANNED_PLAYERS)? {
+            import.plan_banned_players(bans, entries)?;
+        }
+        if let Some(entries) = read_list::<BannedIpEntry>(dir, BANNED_IPS)? {
+            import.plan_banned_ips(bans, entries)?;
+        }
+
+        Ok(import)
+    }
+
+    pub fn changes(&self) -> &[String] {
+        &self.changes
+    }
+
+    /// Sends every change and waits for the database to answer them.
+    pub async fn apply(self, db: &DbConnection) -> Result<(), String> {
+        let pending = PendingCalls::default();
+        register_callbacks(db, &pending);
+
+        let total = self.calls.len();
+        let mut outcomes = Vec::with_capacity(total);
+        for call in self.calls {
+            let key = call.key();
+            let (reply_to, outcome) = oneshot::channel();
+            pending.call(key.clone(), reply_to, || call.send(db));
+            outcomes.push((key, outcome));
+        }
+
+        let mut failed = 0;
+        for (key, outcome) in outcomes {
+            match timeout(ANSWER_TIMEOUT, outcome).await {
+                Ok(Ok(Ok(()))) => log::debug!("{key} done"),
+                Ok(Ok(Err(e))) => {
+                    log::error!("{key} failed : {e}");
+                    failed += 1;
+                }
+                Ok(Err(_)) | Err(_) => {
+                    log::error!("{key} got no answer");
+                    failed += 1;
+                }
+            }
+        }
+
+        if failed == 0 {
+            Ok(())
+        } else {
+            Err(format!("{failed} of {total} calls failed"))
+        }
+    }
+
+    fn plan_config(&mut self, db: &DbConnection, properties: &Properties) -> Result<(), String> {
+        let Some(current) = db.db.server_basic_config().iter().next() else {
+            return Err("Missing basic server configuration".into());
+        };
+        let changed_before = self.changes.len();
+        let update = self.config_update(&current, properties)?;
+        if self.changes.len() > changed_before {
+            self.calls.push(Call::UpdateConfig(update));
+        }
+        Ok(())
+    }
+
+    fn config_update(
+        &mut self,
+        current: &BasicConfiguration,
+        properties: &Properties,
+    ) -> Result<ConfigurationUpdate, String> {
+        let server_address = match (properties.get("server-ip"), properties.get("server-port")) {
+            (None, None) => None,
+            (ip, _) => {
+                let port = properties
+                    .parse_value::<u16>("server-port")?
+                    .unwrap_or(25565);
+                let ip = match ip.map(str::trim).filter(|ip| !ip.is_empty()) {
+                    Some(ip) => ip
+                        .parse::<IpAddr>()
+                        .map_err(|e| format!("Invalid server-ip {ip:?} : {e}"))?,
+                    None => IpAddr::from([0, 0, 0, 0]),
+                };
+                // Formatting a socket address adds the brackets IPv6 needs
+                Some(std::net::SocketAddr::new(ip, port).to_string())
+            }
+        };
+        let default_difficulty = properties.get("difficulty").map(difficulty).transpose()?;
+        let default_gamemode = properties.get("gamemode").map(game_mode).transpose()?;
+        let op_permission_level = properties
+            .parse_value::<u8>("op-permission-level")?
+            .map(permission_level)
+            .transpose()?;
+
+        Ok(ConfigurationUpdate {
+            server_address: self.setting("server-address", &current.server_address, server_address),
+            seed: self.setting(
+                "level-seed",
+                &current.seed,
+                properties.get("level-seed").map(String::from),
+            ),
+            max_players: self.setting(
+                "max-players",
+                &current.max_players,
+                properties.parse_value("max-players")?,
+            ),
+            view_distance: self.setting(
+                "view-distance",
+                &current.view_distance,
+                properties.parse_value("view-distance")?,
+            ),
+            simulation_distance: self.setting(
+                "simulation-distance",
+                &current.simulation_distance,
+                properties.parse_value("simulation-distance")?,
+            ),
+            default_difficulty: self.setting(
+                "difficulty",
+                &current.default_difficulty,
+                default_difficulty,
+            ),
+            op_permission_level: self.setting(
+                "op-permission-level",
+                &current.op_permission_level,
+                op_permission_level,
+            ),
+            allow_nether: self.setting(
+                "allow-nether",
+                &current.allow_nether,
+                properties.parse_value("allow-nether")?,
+            ),
+            hardcore: self.setting(
+                "hardcore",
+                &current.hardcore,
+                properties.parse_value("hardcore")?,
+            ),
+            online_mode: self.setting(
+                "online-mode",
+                &current.online_mode,
+                properties.parse_value("online-mode")?,
+            ),
+            // Vanilla always encrypts online mode connections, no setting for it
+            encryption: None,
+            motd: self.setting(
+                "motd",
+                &current.motd,
+                properties.get("motd").map(String::from),
+            ),
+            tps: None,
+            default_gamemode: self.setting("gamemode", &current.default_gamemode, default_gamemode),
+            force_gamemode: self.setting(
+                "force-gamemode",
+                &current.force_gamemode,
+                properties.parse_value("force-gamemode")?,
+            ),
+            scrub_ips: None,
+            use_favicon: None,
+            favicon_path: None,
+            default_level_name: self.setting(
+                "level-name",
+                &current.default_level_name,
+                properties.get("level-name").map(String::from),
+            ),
+            allow_chat_reports: self.setting(
+                "enforce-secure-profile",
+                &current.allow_chat_reports,
+                properties.parse_value("enforce-secure-profile")?,
+            ),
+            white_list: self.setting(
+                "white-list",
+                &current.white_list,
+                properties.parse_value("white-list")?,
+            ),
+            enforce_whitelist: self.setting(
+                "enforce-whitelist",
+                &current.enforce_whitelist,
+                properties.parse_value("enforce-whitelist")?,
+            ),
+        })
+    }
+
+    /// Keeps the imported value only if it differs from the current one.
+    fn setting<T: PartialEq + Debug>(
+        &mut self,
+        name: &str,
+        current: &T,
+        imported: Option<T>,
+    ) -> Option<T> {
+        let imported = imported.filter(|imported| imported != current)?;
+        self.changes
+            .push(format!("~ {name}: {current:?} -> {imported:?}"));
+        Some(imported)
+    }
+
+    fn plan_whitelist(&mut self, db: &DbConnection, entries: Vec<UserEntry>) {
+        for entry in entries {
+            if db
+                .db
+                .whitelist()
+                .profile_id()
+                .find(&entry.uuid.as_u128())
+                .is_some()
+            {
+                continue;
+            }
+            self.changes
+                .push(format!("+ whitelist: {} ({})", entry.name, entry.uuid));
+            self.add_player(entry.uuid, entry.name);
+            self.calls.push(Call::Whitelist(entry.uuid));
+        }
+    }
+
+    fn plan_ops(&mut self, db: &DbConnection, entries: Vec<OpEntry>) -> Result<(), String> {
+        for entry in entries {
+            let level = permission_level(entry.level)?;
+            if level == PermissionLvl::Zero {
+                continue;
+            }
+            match db.db.operator().profile_id().find(&entry.uuid.as_u128()) {
+                Some(op)
+                    if op.level == level
+                        && op.bypasses_player_limit == entry.bypasses_player_limit =>
+                {
+                    continue
+                }
+                Some(op) => self.changes.push(format!(
+                    "~ op: {} ({}) {:?} -> {level:?}, bypasses player limit: {}",
+                    entry.name, entry.uuid, op.level, entry.bypasses_player_limit
+                )),
+                None => self.changes.push(format!(
+                    "+ op: {} ({}) {level:?}, bypasses player limit: {}",
+                    entry.name, entry.uuid, entry.bypasses_player_limit
+                )),
+            }
+            self.add_player(entry.uuid, entry.name);
+            self.calls.push(Call::Op {
+                uuid: entry.uuid,
+                level,
+                bypasses_player_limit: entry.bypasses_player_limit,
+            });
+        }
+        Ok(())
+    }
+
+    fn plan_banned_players(
+        &mut self,
+        bans: &Bans,
+        entries: Vec<BannedPlayerEntry>,
+    ) -> Result<(), String> {
+        for entry in entries {
+            let profile_id = entry.uuid.as_u128();
+            if bans.players.iter().any(|ban| ban.profile_id == profile_id) {
+                continue;
+            }
+            let expires_at = lists::parse_expiry(&entry.expires)?;
+            self.changes.push(format!(
+                "+ ban: {} ({}) until {} : {}",
+                entry.name, entry.uuid, entry.expires, entry.reason
+            ));
+            self.add_player(entry.uuid, entry.name);
+            self.calls.push(Call::BanPlayer {
+                uuid: entry.uuid,
+                reason: entry.reason,
+                expires_at,
+            });
+        }
+        Ok(())
+    }
+
+    fn plan_banned_ips(&mut self, bans: &Bans, entries: Vec<BannedIpEntry>) -> Result<(), String> {
+        for entry in entries {
+            let ip = entry
+                .ip
+                .parse::<IpAddr>()
+                .map_err(|e| format!("Invalid banned IP {:?} : {e}", entry.ip))?
+                .to_string();
+            if bans.ips.iter().any(|ban| ban.ip == ip) {
+                continue;
+            }
+            let expires_at = lists::parse_expiry(&entry.expires)?;
+            self.changes.push(format!(
+                "+ ban-ip: {ip} until {} : {}",
+                entry.expires, entry.reason
+            ));
+            self.calls.push(Call::BanIp {
+                ip,
+                reason: entry.reason,
+                expires_at,
+            });
+        }
+        Ok(())
+    }
+
+    fn add_player(&mut self, uuid: Uuid, name: String) {
+        if self.known_players.insert(uuid) {
+            self.calls.push(Call::AddPlayer { uuid, name });
+        }
+    }
+}
+
+/// Imports the files in `dir`, printing each change. With `dry_run`, nothing is sent.
+pub async fn run(db: &DbConnection, dir: &Path, dry_run: bool) -> Result<(), String> {
+    let bans = Bans::request(db).await?;
+    let import = Import::plan(db, &bans, dir)?;
+    if import.changes().is_empty() {
+        println!("Nothing to import from {}", dir.display());
+        return Ok(());
+    }
+    for change in import.changes() {
+        println!("{change}");
+    }
+    if dry_run {
+        println!("Dry run, {} changes not applied", import.changes().len());
+        return Ok(());
+    }
+
+    let count = import.changes().len();
+    import.apply(db).await?;
+    println!("Imported {count} changes from {}", dir.display());
+    Ok(())
+}
+
+fn read_list<T: serde::de::DeserializeOwned>(
+    dir: &Path,
+    file: &str,
+) -> Result<Option<Vec<T>>, String> {
+    let path = dir.join(file);
+    lists::read(&path).map_err(|e| format!("Failed to read {} : {e}", path.display()))
+}
+
+fn register_callbacks(db: &DbConnection, pending: &PendingCalls<String>) {
+    let calls = pending.clone();
+    db.reducers
+        .on_add_player(move |ctx, _username, profile_id| {
+            calls.complete_with_status(&format!("add_player {profile_id}"), &ctx.event.status)
+        });
+    let calls = pending.clone();
+    db.reducers.on_update_config(move |ctx, _update, _issuer| {
+        calls.complete_with_status(&"update_config".to_string(), &ctx.event.status)
+    });
+    let calls = pending.clone();
+    db.reducers.on_whitelist_add(move |ctx, target, _issuer| {
+        calls.complete_with_status(&format!("whitelist_add {target}"), &ctx.event.status)
+    });
+    let calls = pending.clone();
+    db.reducers
+        .on_op_player(move |ctx, target, _level, _bypasses, _issuer| {
+            calls.complete_with_status(&format!("op_player {target}"), &ctx.event.status)
+        });
+    let calls = pending.clone();
+    db.reducers
+        .on_ban_player(move |ctx, target, _reason, _expires_at, _issuer| {
+            calls.complete_with_status(&format!("ban_player {target}"), &ctx.event.status)
+        });
+    let calls = pending.clone();
+    db.reducers
+        .on_ban_ip(move |ctx, ip, _reason, _expires_at, _issuer| {
+            calls.complete_with_status(&format!("ban_ip {ip}"), &ctx.event.status)
+        });
+}
+
+/// Vanilla accepts both names and ids.
+fn difficulty(value: &str) -> Result<Difficulty, String> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "peaceful" | "0" => Ok(Difficulty::Peaceful),
+        "easy" | "1" => Ok(Difficulty::Easy),
+        "normal" | "2" => Ok(Difficulty::Normal),
+        "hard" | "3" => Ok(Difficulty::Hard),
+        _ => Err(format!("Invalid difficulty {value:?}")),
+    }
+}
+
+/// Vanilla accepts both names and ids.
+fn game_mode(value: &str) -> Result<GameMode, String> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "survival" | "0" => Ok(GameMode::Survival),
+        "creative" | "1" => Ok(GameMode::Creative),
+        "adventure" | "2" => Ok(GameMode::Adventure),
+        "spectator" | "3" => Ok(GameMode::Spectator),
+        _ => Err(format!("Invalid game mode {value:?}")),
+    }
+}
+
+fn permission_level(level: u8) -> Result<PermissionLvl, String> {
+    match level {
+        0 => Ok(PermissionLvl::Zero),
+        1 => Ok(PermissionLvl::One),
+        2 => Ok(PermissionLvl::Two),
+        3 => Ok(PermissionLvl::Three),
+        4 => Ok(PermissionLvl::Four),
+        _ => Err(format!("Invalid permission level {level}")),
+    }
+}
@@ -0,0 +1,151 @@
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use spacetimedb_sdk::Timestamp;
+use std::io;
+use std::path::Path;
+use uuid::Uuid;
+
+pub const WHITELIST: &str = "whitelist.json";
+pub const OPS: &str = "ops.json";
+pub const BANNED_PLAYERS: &str = "banned-players.json";
+pub const BANNED_IPS: &str = "banned-ips.json";
+pub const USER_CACHE: &str = "usercache.json";
+
+/// How vanilla writes dates in its lists.
+pub const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S %z";
+/// Expiry of permanent bans.
+pub const FOREVER: &str = "forever";
+
+/// An entry of `whitelist.json`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserEntry {
+    pub uuid: Uuid,
+    pub name: String,
+}
+
+/// An entry of `ops.json`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpEntry {
+    pub uuid: Uuid,
+    pub name: String,
+    pub level: u8,
+    #[serde(default)]
+    pub bypasses_player_limit: bool,
+}
+
+/// An entry of `banned-players.json`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BannedPlayerEntry {
+    pub uuid: Uuid,
+    pub name: String,
+    pub created: String,
+    pub source: String,
+    pub expires: String,
+    pub reason: String,
+}
+
+/// An entry of `banned-ips.json`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BannedIpEntry {
+    pub ip: String,
+    pub created: String,
+    pub source: String,
+    pub expires: String,
+    pub reason: String,
+}
+
+/// An entry of `usercache.json`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserCacheEntry {
+    pub name: String,
+    pub uuid: Uuid,
+    #[serde(rename = "expiresOn")]
+    pub expires_on: String,
+}
+
+/// Reads a list, `None` if the file does not exist.
+pub fn read<T: DeserializeOwned>(path: &Path) -> io::Result<Option<Vec<T>>> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Parses a ban expiry, `Ok(None)` for permanent bans.
+pub fn parse_expiry(expires: &str) -> Result<Option<Timestamp>, String> {
+    if expires.eq_ignore_ascii_case(FOREVER) {
+        return Ok(None);
+    }
+    DateTime::parse_from_str(expires, DATE_FORMAT)
+        .map(|date| {
+            Some(Timestamp::from_micros_since_unix_epoch(
+                date.timestamp_micros(),
+            ))
+        })
+        .map_err(|e| format!("Invalid date {expires:?} : {e}"))
+}
+
+/// Writes a list the way vanilla does, pretty printed.
+pub fn write<T: Serialize>(path: &Path, entries: &[T]) -> io::Result<()> {
+    let content = serde_json::to_string_pretty(entries)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    std::fs::write(path, content)
+}
+
+pub fn format_date(timestamp: Timestamp) -> String {
+    DateTime::<Utc>::from_timestamp_micros(timestamp.to_micros_since_unix_epoch())
+        .unwrap_or_default()
+        .format(DATE_FORMAT)
+        .to_string()
+}
+
+/// Formats a ban expiry, [FOREVER] for permanent bans.
+pub fn format_expiry(expires_at: Option<Timestamp>) -> String {
+    expires_at.map_or_else(|| FOREVER.into(), format_date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permanent_bans_have_no_expiry() {
+        assert_eq!(parse_expiry("forever"), Ok(None));
+        assert_eq!(parse_expiry("Forever"), Ok(None));
+        assert_eq!(format_expiry(None), FOREVER);
+    }
+
+    #[test]
+    fn expiry_round_trips() {
+        // Vanilla dates have a precision of one second
+        let expires_at = Timestamp::from_micros_since_unix_epoch(1_750_000_000_000_000);
+        assert_eq!(
+            parse_expiry(&format_expiry(Some(expires_at))),
+            Ok(Some(expires_at))
+        );
+    }
+
+    #[test]
+    fn parses_vanilla_dates_with_offsets() {
+        let expected = Timestamp::from_micros_since_unix_epoch(1_750_000_000_000_000);
+        assert_eq!(
+            parse_expiry("2025-06-15 15:06:40 +0000"),
+            Ok(Some(expected))
+        );
+        assert_eq!(
+            parse_expiry("2025-06-15 17:06:40 +0200"),
+            Ok(Some(expected))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_dates() {
+        assert!(parse_expiry("tomorrow").is_err());
+        assert!(parse_expiry("2025-06-15").is_err());
+    }
+}
@@ -0,0 +1,59 @@
+//! Files of vanilla (and Paper) servers, to move their settings and lists in and out of the
+//! database.
+
+use crate::database::pending::PendingCalls;
+use crate::module_bindings::{
+    request_bans, Ban, BanExcerptTableAccess, BannedIp, BannedPlayer, DbConnection,
+};
+use spacetimedb_sdk::{DbContext, Table};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::time::timeout;
+
+pub mod export;
+pub mod import;
+pub mod lists;
+pub mod properties;
+
+pub const SERVER_PROPERTIES: &str = "server.properties";
+
+/// How long to wait for the database to send the bans.
+const BANS_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The bans, which clients may only read through a copy made for them.
+#[derive(Default)]
+pub struct Bans {
+    pub players: Vec<BannedPlayer>,
+    pub ips: Vec<BannedIp>,
+}
+
+impl Bans {
+    /// Asks the module for a copy of the bans, returning it once it reached the client cache.
+    pub async fn request(db: &DbConnection) -> Result<Self, String> {
+        let pending = PendingCalls::default();
+        let calls = pending.clone();
+        let callback = db.reducers.on_request_bans(move |ctx| {
+            if ctx.try_identity() == Some(ctx.event.caller_identity) {
+                calls.complete_with_status(&(), &ctx.event.status)
+            }
+        });
+        let (reply_to, outcome) = oneshot::channel();
+        pending.call((), reply_to, || db.reducers.request_bans());
+        let outcome = timeout(BANS_TIMEOUT, outcome).await;
+        db.reducers.remove_on_request_bans(callback);
+        match outcome {
+            Ok(Ok(Ok(()))) => {}
+            Ok(Ok(Err(e))) => return Err(format!("Failed to read the bans : {e}")),
+            Ok(Err(_)) | Err(_) => return Err("No answer from the database for the bans".into()),
+        }
+
+        let mut bans = Self::default();
+        for excerpt in db.db.ban_excerpt().iter() {
+            match excerpt.ban {
+                Ban::Player(ban) => bans.players.push(ban),
+                Ban::Ip(ban) => bans.ips.push(ban),
+            }
+        }
+        Ok(bans)
+    }
+}
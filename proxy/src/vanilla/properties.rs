@@ -0,0 +1,248 @@
+use std::str::{Chars, FromStr};
+
+/// The `key=value` pairs of a Java properties file such as `server.properties`, in file order.
+#[derive(Debug, Default)]
+pub struct Properties {
+    entries: Vec<(String, String)>,
+}
+
+impl Properties {
+    pub fn parse(content: &str) -> Self {
+        let mut entries = Vec::new();
+        let mut lines = content.lines();
+        while let Some(line) = lines.next() {
+            let mut line = line.trim_start().to_string();
+            if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+                continue;
+            }
+            // An odd number of trailing backslashes continues the entry on the next line
+            while line.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1 {
+                line.pop();
+                match lines.next() {
+                    Some(next) => line.push_str(next.trim_start()),
+                    None => break,
+                }
+            }
+
+            let (key, value) = split_entry(&line);
+            entries.push((unescape(key), unescape(value.trim_start())));
+        }
+        Self { entries }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Replaces the value of `key`, or appends it if missing.
+    pub fn set(&mut self, key: &str, value: impl ToString) {
+        let value = value.to_string();
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value,
+            None => self.entries.push((key.into(), value)),
+        }
+    }
+
+    /// Serializes the entries after the given comment lines, escaping them as Java does.
+    pub fn write(&self, comments: &[&str]) -> String {
+        let mut content = String::new();
+        for comment in comments {
+            content.push('#');
+            content.push_str(comment);
+            content.push('\n');
+        }
+        for (key, value) in &self.entries {
+            content.push_str(&escape(key, true));
+            content.push('=');
+            content.push_str(&escape(value, false));
+            content.push('\n');
+        }
+        content
+    }
+
+    /// Parses the value of `key`, `None` if it is missing.
+    pub fn parse_value<T: FromStr>(&self, key: &str) -> Result<Option<T>, String> {
+        self.get(key)
+            .map(|value| {
+                value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("Invalid value {value:?} for {key}"))
+            })
+            .transpose()
+    }
+}
+
+/// Splits on the first unescaped `=` or `:`, or whitespace when there is neither.
+fn split_entry(line: &str) -> (&str, &str) {
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '=' | ':' => return (&line[..i], &line[i + 1..]),
+            c if c.is_whitespace() => {
+                let rest = line[i..].trim_start();
+                let rest = rest.strip_prefix(['=', ':']).unwrap_or(rest);
+                return (&line[..i], rest);
+            }
+            _ => {}
+        }
+    }
+    (line, "")
+}
+
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => result.push('\t'),
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('f') => result.push('\u{c}'),
+            Some('u') => {
+                let code = chars.by_ref().take(4).collect::<String>();
+                let Ok(unit) = u16::from_str_radix(&code, 16) else {
+                    result.push_str("\\u");
+                    result.push_str(&code);
+                    continue;
+                };
+                // Characters outside the BMP are escaped as a surrogate pair
+                let low = if (0xD800..0xDC00).contains(&unit) {
+                    low_surrogate(&mut chars)
+                } else {
+                    None
+                };
+                match char::decode_utf16(std::iter::once(unit).chain(low)).next() {
+                    Some(Ok(c)) => result.push(c),
+                    _ => {
+                        result.push_str("\\u");
+                        result.push_str(&code);
+                    }
+                }
+            }
+            Some(c) => result.push(c),
+            None => {}
+        }
+    }
+    result
+}
+
+/// Consumes the `\uXXXX` escape of a low surrogate, if that is what comes next.
+fn low_surrogate(chars: &mut Chars) -> Option<u16> {
+    let mut ahead = chars.clone();
+    if ahead.next() != Some('\\') || ahead.next() != Some('u') {
+        return None;
+    }
+    let code = ahead.by_ref().take(4).collect::<String>();
+    let unit = u16::from_str_radix(&code, 16)
+        .ok()
+        .filter(|unit| (0xDC00..0xE000).contains(unit))?;
+    *chars = ahead;
+    Some(unit)
+}
+
+fn escape(value: &str, is_key: bool) -> String {
+    let mut result = String::with_capacity(value.len());
+    for (i, c) in value.chars().enumerate() {
+        match c {
+            ' ' if is_key || i == 0 => result.push_str("\\ "),
+            '\t' => result.push_str("\\t"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\u{c}' => result.push_str("\\f"),
+            '\\' | '=' | ':' | '#' | '!' => {
+                result.push('\\');
+                result.push(c);
+            }
+            ' '..='~' => result.push(c),
+            c => {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    result.push_str(&format!("\\u{unit:04X}"));
+                }
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_separators_and_comments() {
+        let properties = Properties::parse(
+            "# comment\n! other comment\n\nmotd=Hello\nlevel-name : world\nseed 42\n  pvp=\n",
+        );
+        assert_eq!(properties.get("motd"), Some("Hello"));
+        assert_eq!(properties.get("level-name"), Some("world"));
+        assert_eq!(properties.get("seed"), Some("42"));
+        assert_eq!(properties.get("pvp"), Some(""));
+        assert_eq!(properties.get("comment"), None);
+    }
+
+    #[test]
+    fn parses_continuation_lines() {
+        let properties = Properties::parse("motd=A \\\n    long line\npath=C:\\\\\n");
+        assert_eq!(properties.get("motd"), Some("A long line"));
+        // An even number of backslashes is an escaped backslash
+        assert_eq!(properties.get("path"), Some("C:\\"));
+    }
+
+    #[test]
+    fn unescapes_surrogate_pairs() {
+        assert_eq!(unescape("\\uD83D\\uDE00"), "\u{1F600}");
+        assert_eq!(unescape("\\ud83d\\ude00!"), "\u{1F600}!");
+        assert_eq!(unescape("\\u00e9"), "\u{e9}");
+    }
+
+    #[test]
+    fn keeps_invalid_escapes() {
+        // A lone surrogate is not a character
+        assert_eq!(unescape("\\uD83Dx"), "\\uD83Dx");
+        assert_eq!(unescape("\\uD83D\\u0041"), "\\uD83DA");
+        assert_eq!(unescape("\\uZZZZ"), "\\uZZZZ");
+    }
+
+    #[test]
+    fn escapes_as_java_does() {
+        assert_eq!(escape("\u{1F600}", false), "\\uD83D\\uDE00");
+        assert_eq!(escape(" a b", false), "\\ a b");
+        assert_eq!(escape("a b", true), "a\\ b");
+        assert_eq!(escape("#1=2:3!", false), "\\#1\\=2\\:3\\!");
+    }
+
+    #[test]
+    fn escaped_values_round_trip() {
+        for value in [
+            "plain",
+            " leading space",
+            "tab\tnew line\ncarriage\rfeed\u{c}",
+            "C:\\path=with:separators#!",
+            "caf\u{e9} \u{1F600} \u{4E16}",
+        ] {
+            assert_eq!(unescape(&escape(value, false)), value);
+            assert_eq!(unescape(&escape(value, true)), value);
+        }
+    }
+
+    #[test]
+    fn written_properties_round_trip() {
+        let mut properties = Properties::default();
+        properties.set("motd", "\u{a7}aWelcome \u{1F600}");
+        properties.set("key with spaces", " value: #1");
+        properties.set("empty", "");
+
+        let parsed = Properties::parse(&properties.write(&["Minecraft server properties"]));
+        assert_eq!(parsed.entries, properties.entries);
+    }
+}
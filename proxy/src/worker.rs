@@ -0,0 +1,82 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+use tokio::sync::{oneshot, Semaphore};
+
+/// Jobs queued or running per worker thread before callers have to wait.
+const QUEUED_JOBS_PER_THREAD: usize = 4;
+
+/// Threads dedicated to CPU heavy work, such as generating terrain or encoding chunks, so it never
+/// stalls the tokio runtime handling connections.
+///
+/// The queue is bounded : once it is full, [WorkerPool::run] waits for a slot, which slows down the
+/// actors asking for work instead of piling up jobs.
+#[derive(Clone)]
+pub struct WorkerPool {
+    pool: Arc<rayon::ThreadPool>,
+    slots: Arc<Semaphore>,
+    capacity: usize,
+}
+
+/// How busy the pool is.
+#[derive(Debug, Clone, Copy)]
+pub struct WorkerStats {
+    pub threads: usize,
+    /// Jobs queued or running
+    pub jobs: usize,
+    pub capacity: usize,
+}
+
+impl WorkerPool {
+    /// One thread per core unless `threads` is given.
+    pub fn new(threads: Option<usize>) -> Result<Self, String> {
+        let mut builder =
+            rayon::ThreadPoolBuilder::new().thread_name(|index| format!("chunk-worker-{index}"));
+        if let Some(threads) = threads {
+            builder = builder.num_threads(threads);
+        }
+        let pool = builder
+            .build()
+            .map_err(|e| format!("Failed to start the worker threads : {e}"))?;
+        let capacity = pool.current_num_threads() * QUEUED_JOBS_PER_THREAD;
+
+        Ok(Self {
+            pool: Arc::new(pool),
+            slots: Arc::new(Semaphore::new(capacity)),
+            capacity,
+        })
+    }
+
+    /// Runs the job on a worker thread, once the queue has room for it.
+    pub async fn run<T, F>(&self, job: F) -> Result<T, String>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let slot = self
+            .slots
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| "The worker pool is closed".to_string())?;
+        let (reply_to, result) = oneshot::channel();
+        self.pool.spawn(move || {
+            let result = catch_unwind(AssertUnwindSafe(job));
+            drop(slot);
+            let _ = reply_to.send(result);
+        });
+
+        match result.await {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(_)) => Err("The job panicked".into()),
+            Err(_) => Err("The worker pool dropped the job".into()),
+        }
+    }
+
+    pub fn stats(&self) -> WorkerStats {
+        WorkerStats {
+            threads: self.pool.current_num_threads(),
+            jobs: self.capacity - self.slots.available_permits(),
+            capacity: self.capacity,
+        }
+    }
+}
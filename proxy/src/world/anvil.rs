@@ -0,0 +1,246 @@
+use crate::module_bindings::{BlockEntityData, ChunkData, Heightmaps, SectionData};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use pumpkin_nbt::compound::NbtCompound;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Chunks per region side.
+pub const REGION_SIZE: i32 = 32;
+const SECTOR_SIZE: usize = 4096;
+const HEADER_SIZE: usize = 2 * SECTOR_SIZE;
+/// Set on the compression type when the chunk is stored in its own `.mcc` file.
+const EXTERNAL_FLAG: u8 = 0x80;
+/// Only fully generated chunks are imported, the others are generated again.
+const FULL_STATUS: &str = "minecraft:full";
+
+/// Block entity keys stored in their own columns.
+const BLOCK_ENTITY_KEYS: [&str; 5] = ["id", "x", "y", "z", "keepPacked"];
+
+/// An anvil `.mca` file, holding up to 32x32 chunks.
+pub struct RegionFile {
+    path: PathBuf,
+    data: Vec<u8>,
+}
+
+impl RegionFile {
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let data = std::fs::read(path)?;
+        if !data.is_empty() && data.len() < HEADER_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Region file shorter than its header",
+            ));
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            data,
+        })
+    }
+
+    /// Region coordinates from a `r.<x>.<z>.mca` file name.
+    pub fn coordinates(path: &Path) -> Option<(i32, i32)> {
+        let name = path.file_name()?.to_str()?;
+        let mut parts = name.strip_prefix("r.")?.strip_suffix(".mca")?.split('.');
+        let x = parts.next()?.parse().ok()?;
+        let z = parts.next()?.parse().ok()?;
+        parts.next().is_none().then_some((x, z))
+    }
+
+    /// The uncompressed NBT of every chunk present, in file order.
+    pub fn chunks(&self) -> Vec<Result<Vec<u8>, String>> {
+        if self.data.is_empty() {
+            return Vec::new();
+        }
+        (0..(REGION_SIZE * REGION_SIZE) as usize)
+            .filter_map(|index| self.chunk(index).transpose())
+            .collect()
+    }
+
+    fn chunk(&self, index: usize) -> Result<Option<Vec<u8>>, String> {
+        let location = &self.data[index * 4..index * 4 + 4];
+        let offset = u32::from_be_bytes([0, location[0], location[1], location[2]]) as usize;
+        if offset == 0 || location[3] == 0 {
+            return Ok(None);
+        }
+
+        let start = offset * SECTOR_SIZE;
+        let header = self
+            .data
+            .get(start..start + 5)
+            .ok_or_else(|| format!("Chunk {index} points past the end of the file"))?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let compression = header[4];
+
+        if compression & EXTERNAL_FLAG != 0 {
+            let (x, z) = Self::coordinates(&self.path)
+                .ok_or_else(|| format!("Invalid region file name {}", self.path.display()))?;
+            let local = index as i32;
+            let external = self.path.with_file_name(format!(
+                "c.{}.{}.mcc",
+                x * REGION_SIZE + local % REGION_SIZE,
+                z * REGION_SIZE + local / REGION_SIZE
+            ));
+            let data = std::fs::read(&external)
+                .map_err(|e| format!("Failed to read {} : {e}", external.display()))?;
+            return decompress(compression & !EXTERNAL_FLAG, &data).map(Some);
+        }
+
+        let data = length
+            .checked_sub(1)
+            .and_then(|length| self.data.get(start + 5..start + 5 + length))
+            .ok_or_else(|| format!("Chunk {index} is truncated"))?;
+        decompress(compression, data).map(Some)
+    }
+}
+
+fn decompress(compression: u8, data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut decompressed = Vec::new();
+    let result = match compression {
+        1 => GzDecoder::new(data).read_to_end(&mut decompressed),
+        2 => ZlibDecoder::new(data).read_to_end(&mut decompressed),
+        3 => return Ok(data.to_vec()),
+        other => return Err(format!("Unsupported chunk compression {other}")),
+    };
+    result
+        .map(|_| decompressed)
+        .map_err(|e| format!("Failed to decompress chunk : {e}"))
+}
+
+#[derive(Deserialize)]
+struct ChunkNbt {
+    #[serde(rename = "xPos")]
+    x: i32,
+    #[serde(rename = "zPos")]
+    z: i32,
+    #[serde(rename = "Status")]
+    status: String,
+    #[serde(default)]
+    sections: Vec<SectionNbt>,
+    #[serde(rename = "Heightmaps", default)]
+    heightmaps: HeightmapsNbt,
+    #[serde(default)]
+    block_entities: Vec<NbtCompound>,
+}
+
+#[derive(Deserialize)]
+struct SectionNbt {
+    #[serde(rename = "Y")]
+    y: i8,
+    block_states: Option<PalettedNbt<BlockStateNbt>>,
+    biomes: Option<PalettedNbt<String>>,
+    #[serde(rename = "BlockLight")]
+    block_light: Option<Vec<u8>>,
+    #[serde(rename = "SkyLight")]
+    sky_light: Option<Vec<u8>>,
+}
+
+#[derive(Deserialize)]
+struct PalettedNbt<T> {
+    palette: Vec<T>,
+    #[serde(default)]
+    data: Vec<i64>,
+}
+
+#[derive(Deserialize)]
+struct BlockStateNbt {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "Properties", default)]
+    properties: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Default)]
+struct HeightmapsNbt {
+    #[serde(rename = "MOTION_BLOCKING", default)]
+    motion_blocking: Vec<i64>,
+    #[serde(rename = "WORLD_SURFACE", default)]
+    world_surface: Vec<i64>,
+}
+
+impl BlockStateNbt {
+    /// `name[key=value,...]` with sorted keys, as the `/setblock` syntax
+    fn into_string(self) -> String {
+        if self.properties.is_empty() {
+            return self.name;
+        }
+        let properties = self
+            .properties
+            .into_iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>();
+        format!("{}[{}]", self.name, properties.join(","))
+    }
+}
+
+/// Converts the NBT of a chunk into what the module stores.
+/// Returns `Ok(None)` for chunks that are not fully generated.
+pub fn parse_chunk(dimension: &str, nbt: &[u8]) -> Result<Option<ChunkData>, String> {
+    let chunk: ChunkNbt = pumpkin_nbt::deserializer::from_bytes(nbt)
+        .map_err(|e| format!("Invalid chunk NBT : {e:?}"))?;
+    if chunk.status != FULL_STATUS {
+        return Ok(None);
+    }
+
+    let sections = chunk
+        .sections
+        .into_iter()
+        .filter_map(|section| {
+            // Sections above and below the world only carry light
+            let block_states = section.block_states?;
+            let biomes = section.biomes?;
+            Some(SectionData {
+                y: section.y,
+                block_palette: block_states
+                    .palette
+                    .into_iter()
+                    .map(BlockStateNbt::into_string)
+                    .collect(),
+                block_data: block_states.data,
+                biome_palette: biomes.palette,
+                biome_data: biomes.data,
+                block_light: section.block_light,
+                sky_light: section.sky_light,
+            })
+        })
+        .collect();
+
+    let block_entities = chunk
+        .block_entities
+        .into_iter()
+        .map(block_entity)
+        .collect::<Result<_, _>>()?;
+
+    Ok(Some(ChunkData {
+        dimension: dimension.into(),
+        x: chunk.x,
+        z: chunk.z,
+        heightmaps: Heightmaps {
+            motion_blocking: chunk.heightmaps.motion_blocking,
+            world_surface: chunk.heightmaps.world_surface,
+        },
+        sections,
+        block_entities,
+    }))
+}
+
+fn block_entity(mut compound: NbtCompound) -> Result<BlockEntityData, String> {
+    let (Some(kind), Some(x), Some(y), Some(z)) = (
+        compound.get_string("id").cloned(),
+        compound.get_int("x"),
+        compound.get_int("y"),
+        compound.get_int("z"),
+    ) else {
+        return Err("Block entity without id or position".into());
+    };
+    compound
+        .child_tags
+        .retain(|(key, _)| !BLOCK_ENTITY_KEYS.contains(&key.as_str()));
+
+    let mut nbt = Vec::new();
+    compound
+        .serialize_content(&mut nbt)
+        .map_err(|e| format!("Failed to encode block entity at {x} {y} {z} : {e:?}"))?;
+    Ok(BlockEntityData { x, y, z, kind, nbt })
+}
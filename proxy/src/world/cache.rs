@@ -0,0 +1,148 @@
+use crate::module_bindings::{ChunkTableAccess, DbConnection};
+use crate::world::ChunkPos;
+use bytes::Bytes;
+use lru::LruCache;
+use spacetimedb_sdk::{Event, Table, TableWithPrimaryKey};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Packets kept by default, a few times what a crowded area needs.
+pub const DEFAULT_CAPACITY: usize = 4096;
+
+/// Encoded Chunk Data and Update Light packets, shared by every player of the proxy.
+///
+/// Entries are keyed by chunk revision, so a changed chunk is never served stale. Packets are kept
+/// uncompressed, as connections do not use compression yet.
+#[derive(Clone)]
+pub struct ChunkCache {
+    inner: Arc<Mutex<Inner>>,
+    stats: Arc<Counters>,
+}
+
+struct Inner {
+    packets: LruCache<(ChunkPos, u64), Bytes>,
+    /// Total size of the cached packets
+    bytes: usize,
+}
+
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    invalidations: AtomicU64,
+}
+
+/// What the cache holds and how useful it was since the proxy started.
+#[derive(Debug, Clone, Copy)]
+pub struct CacheStats {
+    pub packets: usize,
+    pub capacity: usize,
+    pub bytes: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub invalidations: u64,
+}
+
+impl CacheStats {
+    /// Share of lookups served from the cache, between 0 and 1.
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+impl ChunkCache {
+    /// Drops the packets of chunks that change or are deleted. Packets of chunks that are only
+    /// unsubscribed stay, for players who come back to them.
+    pub fn follow(connection: &DbConnection, capacity: usize) -> Self {
+        let cache = Self {
+            inner: Arc::new(Mutex::new(Inner {
+                packets: LruCache::new(NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN)),
+                bytes: 0,
+            })),
+            stats: Default::default(),
+        };
+
+        let chunks = connection.db.chunk();
+        let on_update = cache.clone();
+        chunks.on_update(move |_ctx, old, new| {
+            if old.revision != new.revision {
+                on_update.invalidate(
+                    ChunkPos::new(old.dimension.as_str(), old.x, old.z),
+                    old.revision,
+                );
+            }
+        });
+        let on_delete = cache.clone();
+        chunks.on_delete(move |ctx, chunk| {
+            if matches!(ctx.event, Event::UnsubscribeApplied) {
+                return;
+            }
+            on_delete.invalidate(
+                ChunkPos::new(chunk.dimension.as_str(), chunk.x, chunk.z),
+                chunk.revision,
+            );
+        });
+
+        cache
+    }
+
+    pub fn get(&self, position: &ChunkPos, revision: u64) -> Option<Bytes> {
+        let packet = self
+            .lock()
+            .packets
+            .get(&(position.clone(), revision))
+            .cloned();
+        let counter = match packet {
+            Some(_) => &self.stats.hits,
+            None => &self.stats.misses,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        packet
+    }
+
+    pub fn insert(&self, position: ChunkPos, revision: u64, packet: Bytes) {
+        let mut inner = self.lock();
+        inner.bytes += packet.len();
+        let key = (position, revision);
+        if let Some((old_key, old)) = inner.packets.push(key.clone(), packet) {
+            inner.bytes -= old.len();
+            // Otherwise the same chunk was encoded twice concurrently
+            if old_key != key {
+                self.stats.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn invalidate(&self, position: ChunkPos, revision: u64) {
+        let mut inner = self.lock();
+        if let Some(old) = inner.packets.pop(&(position, revision)) {
+            inner.bytes -= old.len();
+            self.stats.invalidations.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let inner = self.lock();
+        CacheStats {
+            packets: inner.packets.len(),
+            capacity: inner.packets.cap().get(),
+            bytes: inner.bytes,
+            hits: self.stats.hits.load(Ordering::Relaxed),
+            misses: self.stats.misses.load(Ordering::Relaxed),
+            evictions: self.stats.evictions.load(Ordering::Relaxed),
+            invalidations: self.stats.invalidations.load(Ordering::Relaxed),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().expect("Chunk cache poisoned")
+    }
+}
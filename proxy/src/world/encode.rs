@@ -0,0 +1,329 @@
+//! Encodes stored chunks into Chunk Data and Update Light packets.
+
+use crate::module_bindings::ChunkSection;
+use crate::protocol::put_var_int;
+use crate::world::{section_range, StoredChunk, AIR};
+use bytes::{BufMut, Bytes, BytesMut};
+use pumpkin_data::block::{get_state_by_state_id, Block, BLOCK_ENTITY_TYPES};
+use pumpkin_data::chunk::Biome;
+use pumpkin_data::packet::clientbound::PLAY_LEVEL_CHUNK_WITH_LIGHT;
+
+const AIRS: [&str; 3] = [AIR, "minecraft:cave_air", "minecraft:void_air"];
+const AIR_STATE: u32 = 0;
+const DEFAULT_BIOME: &str = "minecraft:plains";
+
+/// Heightmap types, as numbered by the protocol
+const WORLD_SURFACE: i32 = 1;
+const MOTION_BLOCKING: i32 = 4;
+
+const NBT_COMPOUND: u8 = 10;
+const LIGHT_ARRAY_SIZE: i32 = 2048;
+
+/// How a paletted container is stored and sent.
+struct Container {
+    entries: usize,
+    /// Fewest bits per entry, even for tiny palettes
+    min_bits: usize,
+    /// Larger palettes are sent as global ids
+    max_indirect_bits: usize,
+    global_bits: usize,
+}
+
+const BLOCKS: Container = Container {
+    entries: 4096,
+    min_bits: 4,
+    max_indirect_bits: 8,
+    global_bits: 15,
+};
+
+const BIOMES: Container = Container {
+    entries: 64,
+    min_bits: 1,
+    max_indirect_bits: 3,
+    global_bits: 7,
+};
+
+impl Container {
+    /// Bits per entry of the anvil format, which packs longs the same way as the protocol.
+    fn storage_bits(&self, palette_size: usize) -> usize {
+        let bits = (usize::BITS - (palette_size - 1).leading_zeros()) as usize;
+        bits.max(self.min_bits)
+    }
+}
+
+/// The whole packet, id included, ready to be framed.
+pub fn chunk_packet(chunk: &StoredChunk) -> Result<Bytes, String> {
+    let row = &chunk.chunk;
+    let (min_section, count) = section_range(&row.dimension);
+    let mut buf = BytesMut::new();
+    put_var_int(&mut buf, PLAY_LEVEL_CHUNK_WITH_LIGHT);
+    buf.put_i32(row.x);
+    buf.put_i32(row.z);
+
+    let heightmaps = [
+        (WORLD_SURFACE, &row.heightmaps.world_surface),
+        (MOTION_BLOCKING, &row.heightmaps.motion_blocking),
+    ];
+    let heightmaps = heightmaps
+        .into_iter()
+        .filter(|(_, data)| !data.is_empty())
+        .collect::<Vec<_>>();
+    put_var_int(&mut buf, heightmaps.len() as i32);
+    for (kind, data) in heightmaps {
+        put_var_int(&mut buf, kind);
+        put_longs(&mut buf, data);
+    }
+
+    let mut data = BytesMut::new();
+    for y in min_section..min_section + count as i8 {
+        match chunk.sections.iter().find(|section| section.y == y) {
+            Some(section) => put_section(&mut data, section)
+                .map_err(|e| format!("Section {y} of chunk {} : {e}", row.key))?,
+            None => put_empty_section(&mut data),
+        }
+    }
+    put_var_int(&mut buf, data.len() as i32);
+    buf.put_slice(&data);
+
+    let block_entities = chunk
+        .block_entities
+        .iter()
+        .filter_map(|block_entity| Some((block_entity, block_entity_type(&block_entity.kind)?)))
+        .collect::<Vec<_>>();
+    put_var_int(&mut buf, block_entities.len() as i32);
+    for (block_entity, kind) in block_entities {
+        buf.put_u8((((block_entity.x & 15) << 4) | (block_entity.z & 15)) as u8);
+        buf.put_i16(block_entity.y as i16);
+        put_var_int(&mut buf, kind);
+        buf.put_u8(NBT_COMPOUND);
+        buf.put_slice(&block_entity.nbt);
+    }
+
+    put_light(&mut buf, &chunk.sections, min_section);
+    Ok(buf.freeze())
+}
+
+fn put_section(buf: &mut BytesMut, section: &ChunkSection) -> Result<(), String> {
+    let blocks = &section.block_palette;
+    let solid = blocks
+        .iter()
+        .map(|entry| !is_air(entry))
+        .collect::<Vec<_>>();
+    let block_count = if blocks.len() == 1 {
+        if solid[0] {
+            BLOCKS.entries
+        } else {
+            0
+        }
+    } else {
+        let bits = BLOCKS.storage_bits(blocks.len());
+        unpack(&section.block_data, bits, BLOCKS.entries)
+            .into_iter()
+            .filter(|&index| solid.get(index).copied().unwrap_or(false))
+            .count()
+    };
+    buf.put_i16(block_count as i16);
+
+    let states = blocks
+        .iter()
+        .map(|entry| block_state_id(entry))
+        .collect::<Vec<_>>();
+    put_container(buf, &states, &section.block_data, &BLOCKS)?;
+    let biomes = section
+        .biome_palette
+        .iter()
+        .map(|name| biome_id(name))
+        .collect::<Vec<_>>();
+    put_container(buf, &biomes, &section.biome_data, &BIOMES)
+}
+
+fn put_empty_section(buf: &mut BytesMut) {
+    buf.put_i16(0);
+    put_container(buf, &[AIR_STATE], &[], &BLOCKS).expect("Single valued container");
+    put_container(buf, &[biome_id(DEFAULT_BIOME)], &[], &BIOMES).expect("Single valued container");
+}
+
+fn put_container(
+    buf: &mut BytesMut,
+    palette: &[u32],
+    data: &[i64],
+    container: &Container,
+) -> Result<(), String> {
+    if palette.is_empty() {
+        return Err("Empty palette".into());
+    }
+    if palette.len() == 1 {
+        buf.put_u8(0);
+        put_var_int(buf, palette[0] as i32);
+        return Ok(());
+    }
+
+    let bits = container.storage_bits(palette.len());
+    let expected = container.entries.div_ceil(64 / bits);
+    if data.len() != expected {
+        return Err(format!(
+            "Expected {expected} longs for {} palette entries, got {}",
+            palette.len(),
+            data.len()
+        ));
+    }
+
+    if bits <= container.max_indirect_bits {
+        buf.put_u8(bits as u8);
+        put_var_int(buf, palette.len() as i32);
+        for &id in palette {
+            put_var_int(buf, id as i32);
+        }
+        data.iter().for_each(|&long| buf.put_i64(long));
+    } else {
+        let ids = unpack(data, bits, container.entries)
+            .into_iter()
+            .map(|index| palette.get(index).copied().unwrap_or(palette[0]))
+            .collect::<Vec<_>>();
+        buf.put_u8(container.global_bits as u8);
+        pack(&ids, container.global_bits)
+            .into_iter()
+            .for_each(|long| buf.put_i64(long));
+    }
+    Ok(())
+}
+
+/// Light of the sections that have some, the masks cover one section below and above the world.
+fn put_light(buf: &mut BytesMut, sections: &[ChunkSection], min_section: i8) {
+    let mut sections = sections.iter().collect::<Vec<_>>();
+    sections.sort_by_key(|section| section.y);
+    let bit = |section: &ChunkSection| (i32::from(section.y) - i32::from(min_section) + 1) as u32;
+
+    let sky = sections
+        .iter()
+        .filter_map(|section| Some((bit(section), section.sky_light.as_ref()?)))
+        .collect::<Vec<_>>();
+    let block = sections
+        .iter()
+        .filter_map(|section| Some((bit(section), section.block_light.as_ref()?)))
+        .collect::<Vec<_>>();
+
+    put_bit_set(buf, sky.iter().fold(0, |mask, (bit, _)| mask | 1 << bit));
+    put_bit_set(buf, block.iter().fold(0, |mask, (bit, _)| mask | 1 << bit));
+    // Nothing is marked empty, sections without stored light are simply left out
+    put_bit_set(buf, 0);
+    put_bit_set(buf, 0);
+    for arrays in [sky, block] {
+        put_var_int(buf, arrays.len() as i32);
+        for (_, light) in arrays {
+            put_var_int(buf, LIGHT_ARRAY_SIZE);
+            buf.put_slice(light);
+        }
+    }
+}
+
+fn put_bit_set(buf: &mut BytesMut, mask: u64) {
+    if mask == 0 {
+        put_var_int(buf, 0);
+    } else {
+        put_var_int(buf, 1);
+        buf.put_u64(mask);
+    }
+}
+
+fn put_longs(buf: &mut BytesMut, longs: &[i64]) {
+    put_var_int(buf, longs.len() as i32);
+    longs.iter().for_each(|&long| buf.put_i64(long));
+}
+
+/// Entries never span two longs, the remaining high bits of each long are unused.
+fn unpack(data: &[i64], bits: usize, entries: usize) -> Vec<usize> {
+    let per_long = 64 / bits;
+    let mask = (1u64 << bits) - 1;
+    (0..entries)
+        .map(|index| {
+            let long = data.get(index / per_long).copied().unwrap_or(0) as u64;
+            ((long >> (index % per_long * bits)) & mask) as usize
+        })
+        .collect()
+}
+
+fn pack(values: &[u32], bits: usize) -> Vec<i64> {
+    let per_long = 64 / bits;
+    values
+        .chunks(per_long)
+        .map(|values| {
+            values.iter().enumerate().fold(0u64, |long, (i, &value)| {
+                long | u64::from(value) << (i * bits)
+            }) as i64
+        })
+        .collect()
+}
+
+/// Palette entry of the block at `x`, `y`, `z` within the section.
+pub fn block_in_section(section: &ChunkSection, x: usize, y: usize, z: usize) -> &str {
+    let blocks = &section.block_palette;
+    let entry = if blocks.len() <= 1 {
+        0
+    } else {
+        let bits = BLOCKS.storage_bits(blocks.len());
+        let per_long = 64 / bits;
+        let index = (y * 16 + z) * 16 + x;
+        let long = section
+            .block_data
+            .get(index / per_long)
+            .copied()
+            .unwrap_or(0) as u64;
+        ((long >> (index % per_long * bits)) & ((1u64 << bits) - 1)) as usize
+    };
+    blocks.get(entry).map_or(AIR, String::as_str)
+}
+
+/// Whether the block fills its whole space, so nothing can stand inside it.
+pub fn is_full_block(entry: &str) -> bool {
+    !is_air(entry)
+        && get_state_by_state_id(block_state_id(entry) as u16)
+            .is_some_and(|state| state.is_full_cube())
+}
+
+pub fn is_air(entry: &str) -> bool {
+    AIRS.contains(&entry.split('[').next().unwrap_or(entry))
+}
+
+fn strip_namespace(name: &str) -> &str {
+    name.strip_prefix("minecraft:").unwrap_or(name)
+}
+
+/// Global id of a `name[key=value,...]` block state, unknown blocks become air.
+fn block_state_id(entry: &str) -> u32 {
+    let (name, properties) = match entry.split_once('[') {
+        Some((name, properties)) => (name, properties.trim_end_matches(']')),
+        None => (entry, ""),
+    };
+    let Some(block) = Block::from_registry_key(strip_namespace(name)) else {
+        return AIR_STATE;
+    };
+    if properties.is_empty() {
+        return u32::from(block.default_state_id);
+    }
+    let properties = properties
+        .split(',')
+        .filter_map(|property| property.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    block
+        .from_properties(properties)
+        .map_or(u32::from(block.default_state_id), |state| {
+            u32::from(state.to_state_id(&block))
+        })
+}
+
+fn biome_id(name: &str) -> u32 {
+    Biome::from_name(strip_namespace(name))
+        .or_else(|| Biome::from_name(strip_namespace(DEFAULT_BIOME)))
+        .map_or(0, |biome| u32::from(biome.id))
+}
+
+/// Unknown block entities are left out, the client renders their block without them.
+fn block_entity_type(kind: &str) -> Option<i32> {
+    let kind = strip_namespace(kind);
+    BLOCK_ENTITY_TYPES
+        .iter()
+        .position(|known| strip_namespace(known) == kind)
+        .map(|id| id as i32)
+}
@@ -0,0 +1,322 @@
+use crate::actor_ref::ActorRef;
+use crate::database::pending::{PendingCalls, ReducerOutcome};
+use crate::database::Database;
+use crate::err::{SendError, TrySendError};
+use crate::module_bindings::{
+    claim_chunk, store_generated_chunk, ChunkClaimTableAccess, ChunkData, ChunkTableAccess,
+};
+use crate::worker::WorkerPool;
+use crate::world::{anvil, ChunkPos, OVERWORLD, THE_END, THE_NETHER};
+use pumpkin_util::math::vector2::Vector2;
+use pumpkin_world::chunk::format::anvil::chunk_to_bytes;
+use pumpkin_world::dimension::Dimension;
+use pumpkin_world::generation::{get_world_gen, Seed, WorldGenerator};
+use spacetimedb_sdk::{DbContext, Event, Table};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::timeout;
+
+/// How long to wait for the module to answer a claim or store a chunk.
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Numeric seeds are used as is, any other text is hashed with Java's `String.hashCode`, as
+/// vanilla does, so a seed copied from a vanilla server gives the same terrain.
+pub fn parse_seed(seed: &str) -> i64 {
+    let seed = seed.trim();
+    seed.parse()
+        .unwrap_or_else(|_| i64::from(java_string_hash(seed)))
+}
+
+fn java_string_hash(value: &str) -> i32 {
+    value.encode_utf16().fold(0i32, |hash, unit| {
+        hash.wrapping_mul(31).wrapping_add(i32::from(unit))
+    })
+}
+
+/// Generates the chunks missing from the module, shared by every player of the proxy.
+#[derive(Clone)]
+pub struct ChunkGenerator {
+    sender: mpsc::Sender<GeneratorMessage>,
+}
+
+impl ChunkGenerator {
+    pub fn spawn(seed: &str, database: Database, workers: WorkerPool) -> Self {
+        let (sender, receiver) = mpsc::channel(256);
+        let actor = GeneratorActor {
+            seed: parse_seed(seed),
+            database,
+            workers,
+            receiver,
+            self_addr: sender.clone(),
+            generators: HashMap::new(),
+            waiting: HashMap::new(),
+            claims: PendingCalls::default(),
+            stores: PendingCalls::default(),
+        };
+        actor.register_callbacks();
+        tokio::spawn(actor.run());
+
+        Self { sender }
+    }
+}
+
+impl ActorRef<GeneratorMessage> for ChunkGenerator {
+    async fn send(&self, msg: GeneratorMessage) -> Result<(), SendError> {
+        self.sender.send(msg).await.map_err(SendError::from)
+    }
+
+    fn try_send(&self, msg: GeneratorMessage) -> Result<(), TrySendError> {
+        self.sender.try_send(msg).map_err(TrySendError::from)
+    }
+}
+
+#[derive(Debug)]
+pub enum GeneratorMessage {
+    /// Replies once the chunk is stored in the module, generating it if needed. The chunk must be
+    /// watched, see [crate::world::World::watch].
+    Ensure {
+        position: ChunkPos,
+        reply_to: oneshot::Sender<ReducerOutcome>,
+    },
+    /// The configured seed changed, only chunks generated afterwards use it
+    SetSeed(String),
+    Claimed {
+        position: ChunkPos,
+        outcome: ReducerOutcome,
+    },
+    Generated {
+        position: ChunkPos,
+        chunk: Result<ChunkData, String>,
+    },
+    Stored {
+        position: ChunkPos,
+        outcome: ReducerOutcome,
+    },
+    /// A chunk was stored, by this proxy or another one
+    ChunkInserted(ChunkPos),
+    /// Another proxy dropped its claim, possibly without storing the chunk
+    ClaimReleased(ChunkPos),
+}
+
+struct GeneratorActor {
+    seed: i64,
+    database: Database,
+    workers: WorkerPool,
+    receiver: mpsc::Receiver<GeneratorMessage>,
+    self_addr: mpsc::Sender<GeneratorMessage>,
+    /// Created on first use, keyed by dimension
+    generators: HashMap<String, Arc<dyn WorldGenerator>>,
+    /// Requests for each missing chunk, only the first one starts generating it
+    waiting: HashMap<ChunkPos, Vec<oneshot::Sender<ReducerOutcome>>>,
+    /// Keyed by chunk key
+    claims: PendingCalls<String>,
+    stores: PendingCalls<String>,
+}
+
+impl GeneratorActor {
+    fn register_callbacks(&self) {
+        let claims = self.claims.clone();
+        self.database
+            .reducers
+            .on_claim_chunk(move |ctx, dimension, x, z| {
+                if ctx.try_identity() == Some(ctx.event.caller_identity) {
+                    let key = ChunkPos::new(dimension.as_str(), *x, *z).key();
+                    claims.complete_with_status(&key, &ctx.event.status);
+                }
+            });
+        let stores = self.stores.clone();
+        self.database
+            .reducers
+            .on_store_generated_chunk(move |ctx, chunk| {
+                if ctx.try_identity() == Some(ctx.event.caller_identity) {
+                    let key = ChunkPos::new(chunk.dimension.as_str(), chunk.x, chunk.z).key();
+                    stores.complete_with_status(&key, &ctx.event.status);
+                }
+            });
+
+        let inserted = self.self_addr.clone();
+        self.database.db.chunk().on_insert(move |_ctx, chunk| {
+            let position = ChunkPos::new(chunk.dimension.as_str(), chunk.x, chunk.z);
+            let _ = inserted.try_send(GeneratorMessage::ChunkInserted(position));
+        });
+        let released = self.self_addr.clone();
+        self.database.db.chunk_claim().on_delete(move |ctx, claim| {
+            // Claims also leave the client cache once their chunk is no longer watched
+            if ctx.try_identity() == Some(claim.proxy)
+                || matches!(ctx.event, Event::UnsubscribeApplied)
+            {
+                return;
+            }
+            let mut parts = claim.key.rsplitn(3, ' ');
+            let (Some(z), Some(x), Some(dimension)) = (parts.next(), parts.next(), parts.next())
+            else {
+                return;
+            };
+            if let (Ok(x), Ok(z)) = (x.parse(), z.parse()) {
+                let _ = released.try_send(GeneratorMessage::ClaimReleased(ChunkPos::new(
+                    dimension, x, z,
+                )));
+            }
+        });
+    }
+
+    async fn run(mut self) {
+        while let Some(msg) = self.receiver.recv().await {
+            self.handle_message(msg);
+        }
+    }
+
+    fn handle_message(&mut self, msg: GeneratorMessage) {
+        match msg {
+            GeneratorMessage::Ensure { position, reply_to } => {
+                if self.database.chunk(&position).is_some() {
+                    let _ = reply_to.send(Ok(()));
+                    return;
+                }
+                let requests = self.waiting.entry(position.clone()).or_default();
+                requests.push(reply_to);
+                if requests.len() == 1 {
+                    self.claim(position);
+                }
+            }
+            GeneratorMessage::SetSeed(seed) => {
+                self.seed = parse_seed(&seed);
+                self.generators.clear();
+                log::info!("World seed changed to {}", self.seed);
+            }
+            GeneratorMessage::Claimed { position, outcome } => match outcome {
+                Ok(()) => self.generate(position),
+                Err(_) if self.database.chunk(&position).is_some() => {
+                    self.complete(&position, Ok(()))
+                }
+                // Another proxy is generating it, its insert or release will let us know
+                Err(_) if self.is_claimed(&position) => {}
+                Err(e) => self.complete(&position, Err(e)),
+            },
+            GeneratorMessage::Generated { position, chunk } => match chunk {
+                Ok(chunk) => self.store(position, chunk),
+                // The claim is left to expire, generating again would most likely fail too
+                Err(e) => {
+                    log::error!("Failed to generate chunk {position} : {e}");
+                    self.complete(&position, Err(e))
+                }
+            },
+            GeneratorMessage::Stored { position, outcome } => {
+                if let Err(e) = &outcome {
+                    log::error!("Failed to store generated chunk {position} : {e}");
+                }
+                self.complete(&position, outcome)
+            }
+            GeneratorMessage::ChunkInserted(position) => self.complete(&position, Ok(())),
+            GeneratorMessage::ClaimReleased(position) => {
+                if self.waiting.contains_key(&position) && self.database.chunk(&position).is_none()
+                {
+                    self.claim(position);
+                }
+            }
+        }
+    }
+
+    fn is_claimed(&self, position: &ChunkPos) -> bool {
+        self.database
+            .db
+            .chunk_claim()
+            .key()
+            .find(&position.key())
+            .is_some()
+    }
+
+    fn claim(&self, position: ChunkPos) {
+        let (reply_to, outcome) = oneshot::channel();
+        self.claims.call(position.key(), reply_to, || {
+            self.database
+                .reducers
+                .claim_chunk(position.dimension.clone(), position.x, position.z)
+        });
+        let self_addr = self.self_addr.clone();
+        tokio::spawn(async move {
+            let outcome = answer(outcome).await;
+            let _ = self_addr
+                .send(GeneratorMessage::Claimed { position, outcome })
+                .await;
+        });
+    }
+
+    fn generate(&mut self, position: ChunkPos) {
+        let generator = match self.generator(&position.dimension) {
+            Ok(generator) => generator,
+            Err(e) => return self.complete(&position, Err(e)),
+        };
+        let workers = self.workers.clone();
+        let self_addr = self.self_addr.clone();
+        // Waiting for a worker here keeps the actor free to handle the chunks being stored
+        tokio::spawn(async move {
+            let task_position = position.clone();
+            let chunk = workers
+                .run(move || generate_chunk(generator.as_ref(), &task_position))
+                .await
+                .unwrap_or_else(|e| Err(format!("Generation of chunk failed : {e}")));
+            let _ = self_addr
+                .send(GeneratorMessage::Generated { position, chunk })
+                .await;
+        });
+    }
+
+    fn store(&self, position: ChunkPos, chunk: ChunkData) {
+        let (reply_to, outcome) = oneshot::channel();
+        self.stores.call(position.key(), reply_to, || {
+            self.database.reducers.store_generated_chunk(chunk)
+        });
+        let self_addr = self.self_addr.clone();
+        tokio::spawn(async move {
+            let outcome = answer(outcome).await;
+            let _ = self_addr
+                .send(GeneratorMessage::Stored { position, outcome })
+                .await;
+        });
+    }
+
+    fn complete(&mut self, position: &ChunkPos, outcome: ReducerOutcome) {
+        for reply_to in self.waiting.remove(position).into_iter().flatten() {
+            let _ = reply_to.send(outcome.clone());
+        }
+    }
+
+    fn generator(&mut self, dimension: &str) -> Result<Arc<dyn WorldGenerator>, String> {
+        if let Some(generator) = self.generators.get(dimension) {
+            return Ok(generator.clone());
+        }
+        let kind = match dimension {
+            OVERWORLD => Dimension::Overworld,
+            THE_NETHER => Dimension::Nether,
+            THE_END => Dimension::End,
+            other => return Err(format!("Can not generate unknown dimension {other}")),
+        };
+        // Same bits as vanilla's signed seed
+        let generator: Arc<dyn WorldGenerator> = get_world_gen(Seed(self.seed as u64), kind).into();
+        self.generators.insert(dimension.into(), generator.clone());
+        Ok(generator)
+    }
+}
+
+async fn answer(outcome: oneshot::Receiver<ReducerOutcome>) -> ReducerOutcome {
+    match timeout(ANSWER_TIMEOUT, outcome).await {
+        Ok(Ok(outcome)) => outcome,
+        Ok(Err(_)) | Err(_) => Err("The database did not answer".into()),
+    }
+}
+
+/// Generates the terrain, then goes through the anvil format to get what the module stores,
+/// exactly as an imported chunk would.
+fn generate_chunk(
+    generator: &dyn WorldGenerator,
+    position: &ChunkPos,
+) -> Result<ChunkData, String> {
+    let chunk = generator.generate_chunk(&Vector2::new(position.x, position.z));
+    let nbt =
+        chunk_to_bytes(&chunk).map_err(|e| format!("Failed to encode chunk {position} : {e}"))?;
+    anvil::parse_chunk(&position.dimension, &nbt)?
+        .ok_or_else(|| format!("Generated chunk {position} is not fully generated"))
+}
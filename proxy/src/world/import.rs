@@ -0,0 +1,190 @@
+use crate::database::pending::PendingCalls;
+use crate::module_bindings::{
+    finish_region_import, upload_chunks, ChunkData, DbConnection, ImportedRegionTableAccess,
+};
+use crate::world::anvil::{self, RegionFile};
+use crate::world::{OVERWORLD, THE_END, THE_NETHER};
+use spacetimedb_sdk::Table;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use tokio::time::timeout;
+
+/// Chunks per `upload_chunks` call, small enough to stay well under message size limits.
+const BATCH_SIZE: usize = 16;
+/// How long to wait for the database to store a batch.
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Where each dimension keeps its region files, relative to the world directory.
+const REGION_DIRECTORIES: [(&str, &str); 3] = [
+    (OVERWORLD, "region"),
+    (THE_NETHER, "DIM-1/region"),
+    (THE_END, "DIM1/region"),
+];
+
+struct Region {
+    dimension: &'static str,
+    x: i32,
+    z: i32,
+    path: PathBuf,
+}
+
+/// Uploads every region of the world directory.
+///
+/// Regions are marked imported once all their chunks are stored, so running the import again
+/// after an interruption skips them and only redoes the region it stopped in.
+pub async fn run(db: &DbConnection, world: &Path) -> Result<(), String> {
+    let imported = db
+        .db
+        .imported_region()
+        .iter()
+        .map(|region| region.key)
+        .collect::<HashSet<_>>();
+    let regions = find_regions(world)?;
+    let total = regions.len();
+    let pending = regions
+        .into_iter()
+        .filter(|region| !imported.contains(&region_key(region)))
+        .collect::<Vec<_>>();
+    if pending.is_empty() {
+        println!("All {total} regions of {} are imported", world.display());
+        return Ok(());
+    }
+    println!(
+        "Importing {} regions of {} ({} already imported)",
+        pending.len(),
+        world.display(),
+        total - pending.len()
+    );
+
+    let calls = PendingCalls::default();
+    register_callbacks(db, &calls);
+
+    let start = Instant::now();
+    let mut chunks = 0;
+    let count = pending.len();
+    for (done, region) in pending.into_iter().enumerate() {
+        let imported = import_region(db, &calls, &region).await?;
+        chunks += imported;
+        println!(
+            "[{}/{count}] {} region {} {} : {imported} chunks ({chunks} total, {}s)",
+            done + 1,
+            region.dimension,
+            region.x,
+            region.z,
+            start.elapsed().as_secs()
+        );
+    }
+    Ok(())
+}
+
+fn region_key(region: &Region) -> String {
+    format!("{} {} {}", region.dimension, region.x, region.z)
+}
+
+fn find_regions(world: &Path) -> Result<Vec<Region>, String> {
+    let mut regions = Vec::new();
+    for (dimension, directory) in REGION_DIRECTORIES {
+        let directory = world.join(directory);
+        let entries = match std::fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("Failed to list {} : {e}", directory.display())),
+        };
+        for entry in entries {
+            let path = entry
+                .map_err(|e| format!("Failed to list {} : {e}", directory.display()))?
+                .path();
+            if let Some((x, z)) = RegionFile::coordinates(&path) {
+                regions.push(Region {
+                    dimension,
+                    x,
+                    z,
+                    path,
+                });
+            }
+        }
+    }
+    if regions.is_empty() {
+        return Err(format!("No region files in {}", world.display()));
+    }
+    regions.sort_by_key(|region| (region.dimension, region.x, region.z));
+    Ok(regions)
+}
+
+/// Returns how many chunks were uploaded
+async fn import_region(
+    db: &DbConnection,
+    calls: &PendingCalls<String>,
+    region: &Region,
+) -> Result<u32, String> {
+    let path = region.path.clone();
+    let dimension = region.dimension;
+    // Decompressing and decoding a whole region takes a while
+    let chunks = tokio::task::spawn_blocking(move || read_region(&path, dimension))
+        .await
+        .map_err(|e| format!("Failed to read {} : {e}", region.path.display()))??;
+    let count = chunks.len() as u32;
+
+    let mut outcomes = Vec::new();
+    let mut chunks = chunks.into_iter().peekable();
+    while chunks.peek().is_some() {
+        let batch = chunks.by_ref().take(BATCH_SIZE).collect::<Vec<_>>();
+        let key = batch_key(&batch);
+        let (reply_to, outcome) = oneshot::channel();
+        calls.call(key.clone(), reply_to, || db.reducers.upload_chunks(batch));
+        outcomes.push((key, outcome));
+    }
+    let key = region_key(region);
+    let (reply_to, outcome) = oneshot::channel();
+    calls.call(key.clone(), reply_to, || {
+        db.reducers
+            .finish_region_import(dimension.into(), region.x, region.z, count)
+    });
+    outcomes.push((key, outcome));
+
+    // Calls are handled in order, so the region is only marked imported if every batch went in
+    for (key, outcome) in outcomes {
+        match timeout(ANSWER_TIMEOUT, outcome).await {
+            Ok(Ok(Ok(()))) => {}
+            Ok(Ok(Err(e))) => return Err(format!("Upload of {key} failed : {e}")),
+            Ok(Err(_)) | Err(_) => return Err(format!("Upload of {key} got no answer")),
+        }
+    }
+    Ok(count)
+}
+
+fn read_region(path: &Path, dimension: &str) -> Result<Vec<ChunkData>, String> {
+    let region =
+        RegionFile::read(path).map_err(|e| format!("Failed to read {} : {e}", path.display()))?;
+    let mut chunks = Vec::new();
+    for chunk in region.chunks() {
+        match chunk.and_then(|nbt| anvil::parse_chunk(dimension, &nbt)) {
+            Ok(Some(chunk)) => chunks.push(chunk),
+            Ok(None) => {}
+            // A corrupted chunk is generated again rather than failing the whole import
+            Err(e) => log::warn!("Skipping a chunk of {} : {e}", path.display()),
+        }
+    }
+    Ok(chunks)
+}
+
+/// Identifies a batch by its first chunk, the callback rebuilds it from the reducer arguments.
+fn batch_key(batch: &[ChunkData]) -> String {
+    batch.first().map_or_else(String::new, |chunk| {
+        format!("chunks {} {} {}", chunk.dimension, chunk.x, chunk.z)
+    })
+}
+
+fn register_callbacks(db: &DbConnection, calls: &PendingCalls<String>) {
+    let pending = calls.clone();
+    db.reducers.on_upload_chunks(move |ctx, chunks| {
+        pending.complete_with_status(&batch_key(chunks), &ctx.event.status)
+    });
+    let pending = calls.clone();
+    db.reducers
+        .on_finish_region_import(move |ctx, dimension, x, z, _chunks| {
+            pending.complete_with_status(&format!("{dimension} {x} {z}"), &ctx.event.status)
+        });
+}
@@ -0,0 +1,130 @@
+use crate::module_bindings::{BlockEntityTableAccess, ChunkSectionTableAccess, DbConnection};
+use spacetimedb_sdk::Table;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Sections and block entities of each chunk, by chunk id.
+///
+/// The client cache only looks rows up by unique columns, so this follows the subscription to
+/// avoid scanning every section whenever a chunk is loaded.
+#[derive(Clone, Default)]
+pub struct ChunkIndex {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Section ids by section y
+    sections: HashMap<u64, BTreeMap<i8, u64>>,
+    block_entities: HashMap<u64, HashSet<u64>>,
+}
+
+impl ChunkIndex {
+    /// Follows the changes of the connection, including the rows it already has.
+    pub fn follow(connection: &DbConnection) -> Self {
+        let index = Self::default();
+
+        let sections = connection.db.chunk_section();
+        let on_insert = index.clone();
+        sections.on_insert(move |_ctx, section| {
+            on_insert
+                .lock()
+                .insert_section(section.chunk_id, section.y, section.id)
+        });
+        let on_delete = index.clone();
+        sections.on_delete(move |_ctx, section| {
+            on_delete
+                .lock()
+                .remove_section(section.chunk_id, section.y, section.id)
+        });
+
+        let block_entities = connection.db.block_entity();
+        let on_insert = index.clone();
+        block_entities.on_insert(move |_ctx, block_entity| {
+            on_insert
+                .lock()
+                .block_entities
+                .entry(block_entity.chunk_id)
+                .or_default()
+                .insert(block_entity.id);
+        });
+        let on_delete = index.clone();
+        block_entities.on_delete(move |_ctx, block_entity| {
+            on_delete
+                .lock()
+                .remove_block_entity(block_entity.chunk_id, block_entity.id)
+        });
+
+        // Registered first, so rows arriving meanwhile are not missed
+        let mut inner = index.lock();
+        for section in sections.iter() {
+            inner.insert_section(section.chunk_id, section.y, section.id);
+        }
+        for block_entity in block_entities.iter() {
+            inner
+                .block_entities
+                .entry(block_entity.chunk_id)
+                .or_default()
+                .insert(block_entity.id);
+        }
+        drop(inner);
+
+        index
+    }
+
+    /// Section ids of the chunk, bottom to top.
+    pub fn sections(&self, chunk_id: u64) -> Vec<u64> {
+        self.lock()
+            .sections
+            .get(&chunk_id)
+            .map(|sections| sections.values().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Id of the section at section `y`, `None` if it has no blocks.
+    pub fn section(&self, chunk_id: u64, y: i8) -> Option<u64> {
+        self.lock()
+            .sections
+            .get(&chunk_id)
+            .and_then(|sections| sections.get(&y).copied())
+    }
+
+    pub fn block_entities(&self, chunk_id: u64) -> Vec<u64> {
+        self.lock()
+            .block_entities
+            .get(&chunk_id)
+            .map(|ids| ids.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().expect("Chunk index poisoned")
+    }
+}
+
+impl Inner {
+    fn insert_section(&mut self, chunk_id: u64, y: i8, id: u64) {
+        self.sections.entry(chunk_id).or_default().insert(y, id);
+    }
+
+    fn remove_section(&mut self, chunk_id: u64, y: i8, id: u64) {
+        if let Some(sections) = self.sections.get_mut(&chunk_id) {
+            // A replacing section may already be indexed
+            if sections.get(&y) == Some(&id) {
+                sections.remove(&y);
+            }
+            if sections.is_empty() {
+                self.sections.remove(&chunk_id);
+            }
+        }
+    }
+
+    fn remove_block_entity(&mut self, chunk_id: u64, id: u64) {
+        if let Some(ids) = self.block_entities.get_mut(&chunk_id) {
+            ids.remove(&id);
+            if ids.is_empty() {
+                self.block_entities.remove(&chunk_id);
+            }
+        }
+    }
+}
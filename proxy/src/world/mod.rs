@@ -0,0 +1,234 @@
+//! Chunks stored in the module : importing them from anvil worlds, generating the missing ones
+//! and sending them to players.
+
+pub mod anvil;
+pub mod cache;
+pub mod encode;
+pub mod generation;
+pub mod import;
+pub mod index;
+pub mod subscription;
+
+use crate::actor_ref::ActorRef;
+use crate::database::Database;
+use crate::module_bindings::{BlockEntity, Chunk, ChunkSection};
+use crate::worker::WorkerPool;
+use crate::world::cache::ChunkCache;
+use crate::world::generation::{ChunkGenerator, GeneratorMessage};
+use crate::world::subscription::{ChunkSubscriptions, ChunkWatch};
+use bytes::Bytes;
+use pumpkin_util::math::vector3::Vector3;
+use std::fmt::{Debug, Display, Formatter};
+
+pub const OVERWORLD: &str = "minecraft:overworld";
+pub const THE_NETHER: &str = "minecraft:the_nether";
+pub const THE_END: &str = "minecraft:the_end";
+
+/// Palette entry of blocks in sections that are not stored.
+pub const AIR: &str = "minecraft:air";
+
+/// Lowest section and number of sections of a dimension, as in the vanilla dimension types.
+pub fn section_range(dimension: &str) -> (i8, u8) {
+    match dimension {
+        OVERWORLD => (-4, 24),
+        _ => (0, 16),
+    }
+}
+
+/// Id of the dimension type in the registry sent during configuration.
+pub fn dimension_type(dimension: &str) -> i32 {
+    match dimension {
+        THE_END => 2,
+        THE_NETHER => 3,
+        _ => 0,
+    }
+}
+
+/// Where a player is and where they look.
+#[derive(Clone, Debug)]
+pub struct Location {
+    pub dimension: String,
+    pub position: Vector3<f64>,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
+}
+
+impl Location {
+    pub fn column(&self) -> (i32, i32) {
+        (
+            (self.position.x.floor() as i32) >> 4,
+            (self.position.z.floor() as i32) >> 4,
+        )
+    }
+}
+
+/// A chunk column of a dimension.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ChunkPos {
+    pub dimension: String,
+    pub x: i32,
+    pub z: i32,
+}
+
+impl ChunkPos {
+    pub fn new(dimension: impl Into<String>, x: i32, z: i32) -> Self {
+        Self {
+            dimension: dimension.into(),
+            x,
+            z,
+        }
+    }
+
+    /// What the module stores the chunk under, `<dimension> <x> <z>`.
+    pub fn key(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Display for ChunkPos {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.dimension, self.x, self.z)
+    }
+}
+
+/// A chunk as stored in the module.
+pub struct StoredChunk {
+    pub chunk: Chunk,
+    /// Bottom to top, sections without blocks are missing
+    pub sections: Vec<ChunkSection>,
+    pub block_entities: Vec<BlockEntity>,
+}
+
+impl StoredChunk {
+    /// Y of the block right above the highest motion blocking one, e.g. where players can stand.
+    /// `x` and `z` are within the chunk.
+    pub fn surface_height(&self, x: usize, z: usize) -> Option<i32> {
+        let (min_section, sections) = section_range(&self.chunk.dimension);
+        let height = u32::from(sections) * 16;
+        // Enough bits for 0 to height included
+        let bits = (u32::BITS - height.leading_zeros()) as usize;
+        let per_long = 64 / bits;
+        let index = z * 16 + x;
+        let long = *self
+            .chunk
+            .heightmaps
+            .motion_blocking
+            .get(index / per_long)?;
+        let value = (long as u64 >> (index % per_long * bits)) & ((1 << bits) - 1);
+        Some(i32::from(min_section) * 16 + value as i32)
+    }
+}
+
+/// Access to the chunks of the module, generating the missing ones.
+///
+/// Only watched chunks are in the client cache : reading a chunk takes a [ChunkWatch] on it.
+#[derive(Clone)]
+pub struct World {
+    database: Database,
+    generator: ChunkGenerator,
+    cache: ChunkCache,
+    workers: WorkerPool,
+    subscriptions: ChunkSubscriptions,
+}
+
+impl World {
+    pub fn new(
+        database: Database,
+        generator: ChunkGenerator,
+        cache: ChunkCache,
+        workers: WorkerPool,
+    ) -> Self {
+        Self {
+            subscriptions: ChunkSubscriptions::new(database.clone()),
+            database,
+            generator,
+            cache,
+            workers,
+        }
+    }
+
+    pub fn generator(&self) -> &ChunkGenerator {
+        &self.generator
+    }
+
+    pub fn cache(&self) -> &ChunkCache {
+        &self.cache
+    }
+
+    pub fn workers(&self) -> &WorkerPool {
+        &self.workers
+    }
+
+    /// Keeps the chunk in the client cache until the watch is dropped.
+    pub async fn watch(&self, position: &ChunkPos) -> Result<ChunkWatch, String> {
+        self.subscriptions.watch(position).await
+    }
+
+    /// Palette entry of a block, `None` if its chunk is not stored yet or not watched.
+    pub fn block_at(&self, dimension: &str, x: i32, y: i32, z: i32) -> Option<String> {
+        self.database.block_at(dimension, x, y, z)
+    }
+
+    /// The chunk at `position`, once generated if it did not exist. The chunk must be watched.
+    pub async fn load(&self, position: &ChunkPos) -> Result<StoredChunk, String> {
+        if let Some(chunk) = self.database.stored_chunk(position) {
+            return Ok(chunk);
+        }
+        self.ensure(position).await?;
+        self.database
+            .stored_chunk(position)
+            .ok_or_else(|| format!("Chunk {position} is missing once stored"))
+    }
+
+    /// The Chunk Data and Update Light packet of the chunk, encoded only if no other player
+    /// needed the same revision recently. The chunk must be watched.
+    pub async fn chunk_packet(&self, position: &ChunkPos) -> Result<Bytes, String> {
+        let revision = match self.database.chunk(position) {
+            Some(chunk) => chunk.revision,
+            None => {
+                self.ensure(position).await?;
+                self.database
+                    .chunk(position)
+                    .ok_or_else(|| format!("Chunk {position} is missing once stored"))?
+                    .revision
+            }
+        };
+        if let Some(packet) = self.cache.get(position, revision) {
+            return Ok(packet);
+        }
+
+        let chunk = self.load(position).await?;
+        // The revision read along with the sections, it may have changed since the lookup
+        let revision = chunk.chunk.revision;
+        let packet = self
+            .workers
+            .run(move || encode::chunk_packet(&chunk))
+            .await
+            .map_err(|e| format!("Encoding of chunk {position} failed : {e}"))??;
+        self.cache
+            .insert(position.clone(), revision, packet.clone());
+        Ok(packet)
+    }
+
+    /// Waits for the generator to store the chunk, unless it already exists.
+    async fn ensure(&self, position: &ChunkPos) -> Result<(), String> {
+        let stored = self
+            .generator
+            .ask(|reply_to| GeneratorMessage::Ensure {
+                position: position.clone(),
+                reply_to,
+            })
+            .await
+            .map_err(|_| "The chunk generator stopped".to_string())?;
+        stored
+            .await
+            .map_err(|_| format!("Chunk {position} was dropped by the generator"))?
+    }
+}
+
+impl Debug for World {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("World").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,127 @@
+use crate::database::Database;
+use crate::module_bindings::autogen::SubscriptionHandle;
+use crate::world::ChunkPos;
+use spacetimedb_sdk::{DbContext, SubscriptionHandle as _};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::watch;
+
+/// Whether the rows of a chunk reached the client cache, `None` while waiting.
+type Applied = Option<Result<(), String>>;
+
+/// Subscriptions to the rows of single chunks, so the proxy only mirrors what its players see.
+///
+/// Chunks are shared between players : a chunk stays subscribed while any [ChunkWatch] on it is
+/// alive.
+#[derive(Clone)]
+pub struct ChunkSubscriptions {
+    database: Database,
+    chunks: Arc<Mutex<HashMap<ChunkPos, Subscription>>>,
+}
+
+struct Subscription {
+    handle: SubscriptionHandle,
+    watchers: usize,
+    applied: watch::Receiver<Applied>,
+}
+
+/// Keeps a chunk subscribed until dropped.
+pub struct ChunkWatch {
+    subscriptions: ChunkSubscriptions,
+    position: ChunkPos,
+}
+
+impl Drop for ChunkWatch {
+    fn drop(&mut self) {
+        self.subscriptions.release(&self.position);
+    }
+}
+
+impl ChunkSubscriptions {
+    pub fn new(database: Database) -> Self {
+        Self {
+            database,
+            chunks: Default::default(),
+        }
+    }
+
+    /// Subscribes to the chunk, its sections, block entities and generation claim, returning once
+    /// they are in the client cache. The chunk may not exist yet, its rows then arrive once stored.
+    pub async fn watch(&self, position: &ChunkPos) -> Result<ChunkWatch, String> {
+        let mut applied = {
+            let mut chunks = self.lock();
+            let subscription = chunks
+                .entry(position.clone())
+                .or_insert_with(|| self.subscribe(position));
+            subscription.watchers += 1;
+            subscription.applied.clone()
+        };
+        // Created right away, so the subscription is released on errors too
+        let guard = ChunkWatch {
+            subscriptions: self.clone(),
+            position: position.clone(),
+        };
+        let applied = applied
+            .wait_for(Option::is_some)
+            .await
+            .map_err(|_| format!("Subscription to chunk {position} was dropped"))?
+            .clone();
+        match applied {
+            Some(Err(e)) => Err(format!("Subscription to chunk {position} failed : {e}")),
+            _ => Ok(guard),
+        }
+    }
+
+    fn subscribe(&self, position: &ChunkPos) -> Subscription {
+        let (applied, receiver) = watch::channel(None);
+        let failed = applied.clone();
+        // Keys only hold a dimension name and numbers, no quote to escape
+        let key = position.key();
+        let handle = self
+            .database
+            .subscription_builder()
+            .on_applied(move |_ctx| {
+                applied.send_replace(Some(Ok(())));
+            })
+            .on_error(move |_ctx, e| {
+                failed.send_replace(Some(Err(e.to_string())));
+            })
+            .subscribe([
+                format!("SELECT * FROM chunk WHERE key = '{key}'"),
+                format!(
+                    "SELECT chunk_section.* FROM chunk_section \
+                    JOIN chunk ON chunk_section.chunk_id = chunk.id WHERE chunk.key = '{key}'"
+                ),
+                format!(
+                    "SELECT block_entity.* FROM block_entity \
+                    JOIN chunk ON block_entity.chunk_id = chunk.id WHERE chunk.key = '{key}'"
+                ),
+                format!("SELECT * FROM chunk_claim WHERE key = '{key}'"),
+            ]);
+        Subscription {
+            handle,
+            watchers: 0,
+            applied: receiver,
+        }
+    }
+
+    fn release(&self, position: &ChunkPos) {
+        let mut chunks = self.lock();
+        let Some(subscription) = chunks.get_mut(position) else {
+            return;
+        };
+        subscription.watchers -= 1;
+        if subscription.watchers > 0 {
+            return;
+        }
+        if let Some(subscription) = chunks.remove(position) {
+            if let Err(e) = subscription.handle.unsubscribe() {
+                log::warn!("Failed to unsubscribe from chunk {position} : {e}");
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<ChunkPos, Subscription>> {
+        self.chunks.lock().expect("Chunk subscriptions poisoned")
+    }
+}
@@ -0,0 +1,166 @@
+use crate::auth::require_privileged;
+use spacetimedb::{
+    Filter, Identity, ReducerContext, ScheduleAt, Table, Timestamp, client_visibility_filter,
+    reducer, table,
+};
+use std::time::Duration;
+
+/// Largest number of entries [print_audit_log] and [request_audit_log] may return at once.
+const MAX_SHOWN_ENTRIES: u32 = 100;
+/// How long entries are kept.
+const RETENTION: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+/// How often entries past [RETENTION] are deleted.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A change made through an administrative reducer. Entries are never updated, and only deleted
+/// once past [RETENTION].
+///
+/// Private, as targets and values may hold player IPs : admins and trusted proxies read it through
+/// [request_audit_log].
+#[table(name = audit_log)]
+pub struct AuditEntry {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    /// Identity that called the reducer, an admin or a proxy.
+    pub caller: Identity,
+    /// Profile id of the player a proxy acted for, if any.
+    pub issuer: Option<String>,
+    pub reducer: String,
+    /// What was changed, e.g. a player or a proxy identity.
+    pub target: Option<String>,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub timestamp: Timestamp,
+}
+
+/// Records a change, to be called once the reducer succeeded. Should the reducer fail later on,
+/// the entry is rolled back along with the change.
+pub fn record(
+    ctx: &ReducerContext,
+    reducer: &str,
+    issuer: Option<&str>,
+    target: Option<String>,
+    old_value: Option<String>,
+    new_value: Option<String>,
+) {
+    ctx.db.audit_log().insert(AuditEntry {
+        id: 0,
+        caller: ctx.sender,
+        issuer: issuer.map(String::from),
+        reducer: reducer.into(),
+        target,
+        old_value,
+        new_value,
+        timestamp: ctx.timestamp,
+    });
+}
+
+/// Entries copied for the caller of [request_audit_log], who is the only client to see them.
+#[table(name = audit_excerpt, public)]
+pub struct AuditExcerpt {
+    #[primary_key]
+    #[auto_inc]
+    id: u64,
+    #[index(btree)]
+    viewer: Identity,
+    entry: AuditEntry,
+}
+
+#[client_visibility_filter]
+const AUDIT_EXCERPT_VIEWER: Filter =
+    Filter::Sql("SELECT * FROM audit_excerpt WHERE viewer = :sender");
+
+#[table(name = audit_prune_schedule, scheduled(prune_audit_log))]
+pub struct AuditPruneSchedule {
+    #[primary_key]
+    #[auto_inc]
+    scheduled_id: u64,
+    scheduled_at: ScheduleAt,
+}
+
+/// Starts pruning old entries, unless already scheduled.
+pub fn schedule_prune(ctx: &ReducerContext) {
+    if ctx.db.audit_prune_schedule().count() == 0 {
+        ctx.db.audit_prune_schedule().insert(AuditPruneSchedule {
+            scheduled_id: 0,
+            scheduled_at: ScheduleAt::Interval(PRUNE_INTERVAL.into()),
+        });
+        log::info!("Scheduled audit log pruning every {PRUNE_INTERVAL:?}");
+    }
+}
+
+/// The `limit` most recent entries, newest first.
+pub fn latest(ctx: &ReducerContext, limit: u32) -> Vec<AuditEntry> {
+    let mut entries = ctx.db.audit_log().iter().collect::<Vec<_>>();
+    entries.sort_unstable_by(|a, b| b.id.cmp(&a.id));
+    entries.truncate(limit as usize);
+    entries
+}
+
+/// Prints the last `limit` entries to the module logs, for admins without a proxy console.
+#[reducer]
+fn print_audit_log(ctx: &ReducerContext, limit: u32) -> Result<(), String> {
+    require_privileged(ctx)?;
+    for entry in latest(ctx, limit.min(MAX_SHOWN_ENTRIES)) {
+        log::info!(
+            "#{} {:?} {} (issuer {:?}) {} {:?} : {:?} -> {:?}",
+            entry.id,
+            entry.timestamp,
+            entry.caller,
+            entry.issuer,
+            entry.reducer,
+            entry.target,
+            entry.old_value,
+            entry.new_value
+        );
+    }
+
+    Ok(())
+}
+
+/// Replaces the excerpt of the caller with the last `limit` entries.
+#[reducer]
+fn request_audit_log(ctx: &ReducerContext, limit: u32) -> Result<(), String> {
+    require_privileged(ctx)?;
+    clear_excerpt(ctx, ctx.sender);
+    for entry in latest(ctx, limit.min(MAX_SHOWN_ENTRIES)) {
+        ctx.db.audit_excerpt().insert(AuditExcerpt {
+            id: 0,
+            viewer: ctx.sender,
+            entry,
+        });
+    }
+    Ok(())
+}
+
+/// Deletes what `viewer` requested, e.g. once it disconnected.
+pub fn clear_excerpt(ctx: &ReducerContext, viewer: Identity) {
+    ctx.db.audit_excerpt().viewer().delete(viewer);
+}
+
+#[reducer]
+fn prune_audit_log(ctx: &ReducerContext, _schedule: AuditPruneSchedule) -> Result<(), String> {
+    if ctx.sender != ctx.identity() {
+        return Err("Only the scheduler may prune the audit log".into());
+    }
+
+    let expired = ctx
+        .db
+        .audit_log()
+        .iter()
+        .filter(|entry| {
+            ctx.timestamp
+                .duration_since(entry.timestamp)
+                .is_some_and(|age| age > RETENTION)
+        })
+        .map(|entry| entry.id)
+        .collect::<Vec<_>>();
+    for id in &expired {
+        ctx.db.audit_log().id().delete(id);
+    }
+    if !expired.is_empty() {
+        log::info!("Pruned {} audit log entries", expired.len());
+    }
+    Ok(())
+}
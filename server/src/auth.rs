@@ -0,0 +1,239 @@
+use crate::audit;
+use crate::operator::permission_level;
+use crate::proxy::{is_registered_proxy, proxy_offline};
+use crate::server::config::PermissionLvl;
+use crate::types_support::UUID;
+use spacetimedb::{Identity, ReducerContext, Table, Timestamp, reducer, table};
+use std::str::FromStr;
+
+/// An identity allowed to administrate the server, e.g. through `spacetime call`.
+#[table(name = admin_identity)]
+pub struct AdminIdentity {
+    #[primary_key]
+    pub identity: Identity,
+    pub granted_by: Identity,
+    pub granted_at: Timestamp,
+}
+
+/// An identity allowed to register as a proxy.
+#[table(name = trusted_proxy)]
+pub struct TrustedProxy {
+    #[primary_key]
+    pub identity: Identity,
+    pub granted_by: Identity,
+    pub granted_at: Timestamp,
+}
+
+/// An identity the database owner allows to become admin through `claim_admin`. Only the owner
+/// can write this private table out of band, e.g.
+/// `spacetime sql <database> "INSERT INTO admin_claim (identity) VALUES (0x...)"`, which makes
+/// it the way to get a first admin on data from before admins existed.
+#[table(name = admin_claim)]
+pub struct AdminClaim {
+    #[primary_key]
+    pub identity: Identity,
+}
+
+/// Makes whoever published the module the first admin. Only `init` may call this, as the sender
+/// is the publisher there but anyone connecting elsewhere.
+pub fn seed_first_admin(ctx: &ReducerContext) {
+    if ctx.db.admin_identity().count() == 0 {
+        ctx.db.admin_identity().insert(AdminIdentity {
+            identity: ctx.sender,
+            granted_by: ctx.sender,
+            granted_at: ctx.timestamp,
+        });
+        log::info!("Granted admin to {}", ctx.sender);
+    }
+}
+
+pub fn is_admin(ctx: &ReducerContext, identity: Identity) -> bool {
+    ctx.db.admin_identity().identity().find(identity).is_some()
+}
+
+pub fn is_trusted_proxy(ctx: &ReducerContext, identity: Identity) -> bool {
+    ctx.db.trusted_proxy().identity().find(identity).is_some()
+}
+
+pub fn require_admin(ctx: &ReducerContext) -> Result<(), String> {
+    if is_admin(ctx, ctx.sender) {
+        Ok(())
+    } else {
+        Err(format!("{} is not an admin", ctx.sender))
+    }
+}
+
+/// Only trusted proxies may register, admins are implicitly trusted.
+pub fn require_trusted_proxy(ctx: &ReducerContext) -> Result<(), String> {
+    if is_trusted_proxy(ctx, ctx.sender) || is_admin(ctx, ctx.sender) {
+        Ok(())
+    } else {
+        Err(format!("{} is not a trusted proxy", ctx.sender))
+    }
+}
+
+/// Registration requires trust, so this also makes sure the caller is still trusted.
+pub fn require_registered_proxy(ctx: &ReducerContext) -> Result<(), String> {
+    if is_registered_proxy(ctx, ctx.sender) {
+        Ok(())
+    } else {
+        Err(format!("{} is not a registered proxy", ctx.sender))
+    }
+}
+
+/// Admins and trusted proxies, i.e. anyone but players and unknown clients.
+/// Proxies do not need to be registered, so that their tools (e.g. importing vanilla files) work
+/// without a running proxy.
+pub fn require_privileged(ctx: &ReducerContext) -> Result<(), String> {
+    require_trusted_proxy(ctx)
+}
+
+/// Checks that the caller may act with at least the `required` permission level, returning the
+/// level it acts with.
+///
+/// Players act through their proxy, which passes their profile id as `issuer`.
+/// Without issuer, admins and trusted proxies (e.g. from their console) act with full
+/// permissions.
+pub fn authorize(
+    ctx: &ReducerContext,
+    issuer: Option<&str>,
+    required: PermissionLvl,
+) -> Result<PermissionLvl, String> {
+    require_privileged(ctx)?;
+
+    let level = match issuer {
+        Some(issuer) => permission_level(ctx, UUID::from_str(issuer)?.as_u128()),
+        None => PermissionLvl::Four,
+    };
+    if level >= required {
+        Ok(level)
+    } else {
+        Err(format!(
+            "Permission level {required:?} required, got {level:?}"
+        ))
+    }
+}
+
+#[reducer]
+fn grant_admin(ctx: &ReducerContext, identity: Identity) -> Result<(), String> {
+    require_admin(ctx)?;
+    if is_admin(ctx, identity) {
+        return Err(format!("{identity} is already an admin"));
+    }
+
+    ctx.db.admin_identity().insert(AdminIdentity {
+        identity,
+        granted_by: ctx.sender,
+        granted_at: ctx.timestamp,
+    });
+    log::info!("{} granted admin to {identity}", ctx.sender);
+    audit::record(
+        ctx,
+        "grant_admin",
+        None,
+        Some(identity.to_string()),
+        None,
+        Some("admin".into()),
+    );
+
+    Ok(())
+}
+
+/// Grants admin to the sender, if the database owner allowed it in [AdminClaim]. Claims are used
+/// up.
+#[reducer]
+fn claim_admin(ctx: &ReducerContext) -> Result<(), String> {
+    if !ctx.db.admin_claim().identity().delete(ctx.sender) {
+        return Err(format!("{} may not claim admin", ctx.sender));
+    }
+    if is_admin(ctx, ctx.sender) {
+        return Err(format!("{} is already an admin", ctx.sender));
+    }
+
+    ctx.db.admin_identity().insert(AdminIdentity {
+        identity: ctx.sender,
+        granted_by: ctx.sender,
+        granted_at: ctx.timestamp,
+    });
+    log::info!("{} claimed admin", ctx.sender);
+    audit::record(
+        ctx,
+        "claim_admin",
+        None,
+        Some(ctx.sender.to_string()),
+        None,
+        Some("admin".into()),
+    );
+
+    Ok(())
+}
+
+#[reducer]
+fn revoke_admin(ctx: &ReducerContext, identity: Identity) -> Result<(), String> {
+    require_admin(ctx)?;
+    if ctx.db.admin_identity().count() == 1 && is_admin(ctx, identity) {
+        return Err("Can not revoke the last admin".into());
+    }
+    if !ctx.db.admin_identity().identity().delete(identity) {
+        return Err(format!("{identity} is not an admin"));
+    }
+    log::info!("{} revoked admin from {identity}", ctx.sender);
+    audit::record(
+        ctx,
+        "revoke_admin",
+        None,
+        Some(identity.to_string()),
+        Some("admin".into()),
+        None,
+    );
+
+    Ok(())
+}
+
+#[reducer]
+fn trust_proxy(ctx: &ReducerContext, identity: Identity) -> Result<(), String> {
+    require_admin(ctx)?;
+    if is_trusted_proxy(ctx, identity) {
+        return Err(format!("{identity} is already trusted"));
+    }
+
+    ctx.db.trusted_proxy().insert(TrustedProxy {
+        identity,
+        granted_by: ctx.sender,
+        granted_at: ctx.timestamp,
+    });
+    log::info!("{} trusted proxy {identity}", ctx.sender);
+    audit::record(
+        ctx,
+        "trust_proxy",
+        None,
+        Some(identity.to_string()),
+        None,
+        Some("trusted".into()),
+    );
+
+    Ok(())
+}
+
+/// Revoking trust also unregisters the proxy, marking its players offline.
+#[reducer]
+fn distrust_proxy(ctx: &ReducerContext, identity: Identity) -> Result<(), String> {
+    require_admin(ctx)?;
+    if !ctx.db.trusted_proxy().identity().delete(identity) {
+        return Err(format!("{identity} is not trusted"));
+    }
+    log::info!("{} distrusted proxy {identity}", ctx.sender);
+    audit::record(
+        ctx,
+        "distrust_proxy",
+        None,
+        Some(identity.to_string()),
+        Some("trusted".into()),
+        None,
+    );
+    if !is_admin(ctx, identity) {
+        proxy_offline(ctx, identity);
+    }
+
+    Ok(())
+}
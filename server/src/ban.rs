@@ -0,0 +1,277 @@
+use crate::audit;
+use crate::auth::{authorize, require_privileged};
+use crate::player::{kick_session, player_session, resolve_profile, session_address};
+use crate::server::config::PermissionLvl;
+use spacetimedb::{
+    Filter, Identity, ReducerContext, SpacetimeType, Table, Timestamp, client_visibility_filter,
+    reducer, table,
+};
+use std::net::IpAddr;
+use std::str::FromStr;
+use uuid::Uuid;
+
+pub const BANNED: &str = "multiplayer.disconnect.banned";
+pub const IP_BANNED: &str = "multiplayer.disconnect.ip_banned";
+
+/// Permission level required to run `/ban`, `/ban-ip` and `/pardon`, as in vanilla.
+pub const BAN_COMMAND_LEVEL: PermissionLvl = PermissionLvl::Three;
+
+/// Source recorded for bans issued without a player, as vanilla does for its console.
+const SERVER_SOURCE: &str = "Server";
+
+/// Private like [BannedIp], admins and trusted proxies read both through [request_bans].
+#[table(name = banned_player)]
+pub struct BannedPlayer {
+    #[primary_key]
+    pub profile_id: u128,
+    /// Username at the time the player was banned, for display purposes only.
+    pub name: Option<String>,
+    pub reason: Option<String>,
+    /// Who issued the ban.
+    pub source: String,
+    pub created_at: Timestamp,
+    /// `None` for permanent bans.
+    pub expires_at: Option<Timestamp>,
+}
+
+/// Private, as the addresses must not reach clients when `scrub_ips` is set, nor players ever.
+#[table(name = banned_ip)]
+pub struct BannedIp {
+    #[primary_key]
+    pub ip: String,
+    pub reason: Option<String>,
+    /// Who issued the ban.
+    pub source: String,
+    pub created_at: Timestamp,
+    /// `None` for permanent bans.
+    pub expires_at: Option<Timestamp>,
+}
+
+#[derive(SpacetimeType)]
+pub enum Ban {
+    Player(BannedPlayer),
+    Ip(BannedIp),
+}
+
+/// Bans copied for the caller of [request_bans], who is the only client to see them.
+#[table(name = ban_excerpt, public)]
+pub struct BanExcerpt {
+    #[primary_key]
+    #[auto_inc]
+    id: u64,
+    #[index(btree)]
+    viewer: Identity,
+    ban: Ban,
+}
+
+#[client_visibility_filter]
+const BAN_EXCERPT_VIEWER: Filter = Filter::Sql("SELECT * FROM ban_excerpt WHERE viewer = :sender");
+
+pub fn in_effect(ctx: &ReducerContext, expires_at: Option<Timestamp>) -> bool {
+    expires_at.is_none_or(|expires_at| expires_at > ctx.timestamp)
+}
+
+pub fn is_banned(ctx: &ReducerContext, profile_id: u128) -> bool {
+    ctx.db
+        .banned_player()
+        .profile_id()
+        .find(profile_id)
+        .is_some_and(|ban| in_effect(ctx, ban.expires_at))
+}
+
+/// `ip` is expected in its canonical form, as produced by [normalize_ip].
+pub fn is_ip_banned(ctx: &ReducerContext, ip: &str) -> bool {
+    ctx.db
+        .banned_ip()
+        .ip()
+        .find(ip.to_string())
+        .is_some_and(|ban| in_effect(ctx, ban.expires_at))
+}
+
+/// Parses the address so equivalent spellings (e.g. of IPv6 addresses) match the same ban.
+pub fn normalize_ip(ip: &str) -> Result<String, String> {
+    IpAddr::from_str(ip.trim())
+        .map(|ip| ip.to_string())
+        .map_err(|e| format!("Invalid IP address {ip:?} : {e}"))
+}
+
+/// Players act through their proxy, bans they issue are attributed to their username.
+pub fn source(ctx: &ReducerContext, issuer: Option<&str>) -> Result<String, String> {
+    let Some(issuer) = issuer else {
+        return Ok(SERVER_SOURCE.into());
+    };
+    let (_, name) = resolve_profile(ctx, issuer)?;
+    Ok(name.unwrap_or_else(|| issuer.into()))
+}
+
+/// Bans `target` (UUID or last known username), kicking them if online.
+/// Without `expires_at` the ban is permanent.
+#[reducer]
+pub fn ban_player(
+    ctx: &ReducerContext,
+    target: String,
+    reason: Option<String>,
+    expires_at: Option<Timestamp>,
+    issuer: Option<String>,
+) -> Result<(), String> {
+    authorize(ctx, issuer.as_deref(), BAN_COMMAND_LEVEL)?;
+    let (profile_id, name) = resolve_profile(ctx, &target)?;
+    let ban = BannedPlayer {
+        profile_id,
+        name,
+        reason,
+        source: source(ctx, issuer.as_deref())?,
+        created_at: ctx.timestamp,
+        expires_at,
+    };
+    let new_value = format!("{:?} until {:?}", ban.reason, ban.expires_at);
+    let old_value = match ctx.db.banned_player().profile_id().find(profile_id) {
+        Some(old) => {
+            ctx.db.banned_player().profile_id().update(ban);
+            Some(format!("{:?} until {:?}", old.reason, old.expires_at))
+        }
+        None => {
+            ctx.db.banned_player().insert(ban);
+            None
+        }
+    };
+    log::info!("Banned {target} ({})", Uuid::from_u128(profile_id));
+    audit::record(
+        ctx,
+        "ban_player",
+        issuer.as_deref(),
+        Some(format!("{target} ({})", Uuid::from_u128(profile_id))),
+        old_value,
+        Some(new_value),
+    );
+
+    if let Some(session) = ctx.db.player_session().profile_id().find(profile_id) {
+        kick_session(ctx, session, BANNED);
+    }
+    Ok(())
+}
+
+#[reducer]
+pub fn pardon_player(
+    ctx: &ReducerContext,
+    target: String,
+    issuer: Option<String>,
+) -> Result<(), String> {
+    authorize(ctx, issuer.as_deref(), BAN_COMMAND_LEVEL)?;
+    let (profile_id, _) = resolve_profile(ctx, &target)?;
+    let Some(ban) = ctx.db.banned_player().profile_id().find(profile_id) else {
+        return Err(format!("{target} is not banned"));
+    };
+    ctx.db.banned_player().profile_id().delete(profile_id);
+    log::info!("Pardoned {target} ({})", Uuid::from_u128(profile_id));
+    audit::record(
+        ctx,
+        "pardon_player",
+        issuer.as_deref(),
+        Some(format!("{target} ({})", Uuid::from_u128(profile_id))),
+        Some(format!("{:?} until {:?}", ban.reason, ban.expires_at)),
+        None,
+    );
+
+    Ok(())
+}
+
+/// Bans an IP address, kicking the players connected from it.
+#[reducer]
+pub fn ban_ip(
+    ctx: &ReducerContext,
+    ip: String,
+    reason: Option<String>,
+    expires_at: Option<Timestamp>,
+    issuer: Option<String>,
+) -> Result<(), String> {
+    authorize(ctx, issuer.as_deref(), BAN_COMMAND_LEVEL)?;
+    let ip = normalize_ip(&ip)?;
+    let ban = BannedIp {
+        ip: ip.clone(),
+        reason,
+        source: source(ctx, issuer.as_deref())?,
+        created_at: ctx.timestamp,
+        expires_at,
+    };
+    let new_value = format!("{:?} until {:?}", ban.reason, ban.expires_at);
+    let old_value = match ctx.db.banned_ip().ip().find(ip.clone()) {
+        Some(old) => {
+            ctx.db.banned_ip().ip().update(ban);
+            Some(format!("{:?} until {:?}", old.reason, old.expires_at))
+        }
+        None => {
+            ctx.db.banned_ip().insert(ban);
+            None
+        }
+    };
+    log::info!("Banned IP {ip}");
+    let sessions = ctx
+        .db
+        .session_address()
+        .ip()
+        .filter(&ip)
+        .filter_map(|address| {
+            ctx.db
+                .player_session()
+                .profile_id()
+                .find(address.profile_id)
+        })
+        .collect::<Vec<_>>();
+    audit::record(
+        ctx,
+        "ban_ip",
+        issuer.as_deref(),
+        Some(ip),
+        old_value,
+        Some(new_value),
+    );
+
+    for session in sessions {
+        kick_session(ctx, session, IP_BANNED);
+    }
+    Ok(())
+}
+
+#[reducer]
+pub fn pardon_ip(ctx: &ReducerContext, ip: String, issuer: Option<String>) -> Result<(), String> {
+    authorize(ctx, issuer.as_deref(), BAN_COMMAND_LEVEL)?;
+    let ip = normalize_ip(&ip)?;
+    let Some(ban) = ctx.db.banned_ip().ip().find(ip.clone()) else {
+        return Err(format!("{ip} is not banned"));
+    };
+    ctx.db.banned_ip().ip().delete(ip.clone());
+    log::info!("Pardoned IP {ip}");
+    audit::record(
+        ctx,
+        "pardon_ip",
+        issuer.as_deref(),
+        Some(ip),
+        Some(format!("{:?} until {:?}", ban.reason, ban.expires_at)),
+        None,
+    );
+
+    Ok(())
+}
+
+/// Replaces the excerpt of the caller with every ban, e.g. to export them.
+#[reducer]
+fn request_bans(ctx: &ReducerContext) -> Result<(), String> {
+    require_privileged(ctx)?;
+    clear_excerpt(ctx, ctx.sender);
+    let players = ctx.db.banned_player().iter().map(Ban::Player);
+    let ips = ctx.db.banned_ip().iter().map(Ban::Ip);
+    for ban in players.chain(ips) {
+        ctx.db.ban_excerpt().insert(BanExcerpt {
+            id: 0,
+            viewer: ctx.sender,
+            ban,
+        });
+    }
+    Ok(())
+}
+
+/// Deletes what `viewer` requested, e.g. once it disconnected.
+pub fn clear_excerpt(ctx: &ReducerContext, viewer: Identity) {
+    ctx.db.ban_excerpt().viewer().delete(viewer);
+}
@@ -0,0 +1,386 @@
+use crate::audit;
+use crate::auth::{authorize, require_registered_proxy};
+use crate::ban::{in_effect, source};
+use crate::player::{require_session, resolve_profile};
+use crate::server::basic_config;
+use crate::server::config::PermissionLvl;
+use crate::types_support::UUID;
+use spacetimedb::{ReducerContext, ScheduleAt, SpacetimeType, Table, Timestamp, reducer, table};
+use std::str::FromStr;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Longest message the vanilla client sends, in characters.
+pub const MAX_MESSAGE_LENGTH: usize = 256;
+
+pub const MUTED: &str = "You are muted";
+/// Vanilla kicks for spamming, here the message is only refused.
+pub const SPAM: &str = "You are sending messages too quickly";
+pub const ILLEGAL_CHARACTERS: &str = "multiplayer.disconnect.illegal_characters";
+pub const MISSING_PROFILE_KEY: &str = "chat.disabled.missingProfileKey";
+pub const EXPIRED_PROFILE_KEY: &str = "chat.disabled.expiredProfileKey";
+
+/// Length of message signatures, made with 2048 bit RSA keys.
+const SIGNATURE_LENGTH: usize = 256;
+/// Same limits as vanilla on the profile keys clients send.
+const MAX_PUBLIC_KEY_LENGTH: usize = 512;
+const MAX_KEY_SIGNATURE_LENGTH: usize = 4096;
+/// Signatures of the messages a client saw, sent along with each of its messages.
+const MAX_LAST_SEEN: usize = 20;
+/// Proxies relay messages as they are inserted, keeping them longer only helps proxies that
+/// lagged behind.
+const MESSAGE_RETENTION: Duration = Duration::from_secs(60);
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Messages accepted per player within [MESSAGE_WINDOW], about the burst vanilla tolerates.
+const MAX_MESSAGES_PER_WINDOW: u32 = 10;
+const MESSAGE_WINDOW: Duration = Duration::from_secs(10);
+
+/// Not a vanilla command, given the same level as banning.
+pub const MUTE_COMMAND_LEVEL: PermissionLvl = PermissionLvl::Three;
+
+/// What players said, delivered by every proxy to the players it holds. Messages are never
+/// updated. Rather than kept forever, they are deleted once past [MESSAGE_RETENTION] : proxies
+/// relay them as they are inserted, and every subscriber would otherwise load the whole history.
+#[table(name = chat_message, public)]
+pub struct ChatMessage {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub sender: u128,
+    /// Username of the sender when they sent it.
+    pub sender_name: String,
+    pub content: String,
+    pub sent_at: Timestamp,
+    /// Only kept while chat reports are allowed, `None` for unsigned messages.
+    pub signed: Option<SignedChat>,
+}
+
+/// What clients need to verify a message, and to report it.
+#[derive(Clone, Debug, SpacetimeType)]
+pub struct SignedChat {
+    /// Position of the message within the chat session of the sender.
+    pub index: i32,
+    /// Milliseconds since the epoch, as signed by the client.
+    pub timestamp: i64,
+    pub salt: i64,
+    pub signature: Vec<u8>,
+    /// Signatures of the messages the sender acknowledged, oldest first.
+    pub last_seen: Vec<Vec<u8>>,
+}
+
+/// The key a player's client signs their messages with, verified by their proxy against Mojang's
+/// keys. Ended along with their session.
+#[table(name = chat_session, public)]
+pub struct ChatSession {
+    #[primary_key]
+    pub profile_id: u128,
+    pub session_id: u128,
+    /// X.509 encoded RSA key.
+    pub public_key: Vec<u8>,
+    /// Mojang's signature of the key.
+    pub key_signature: Vec<u8>,
+    pub expires_at: Timestamp,
+}
+
+/// Messages a player sent within the current window, see [MAX_MESSAGES_PER_WINDOW].
+#[table(name = chat_budget)]
+pub struct ChatBudget {
+    #[primary_key]
+    profile_id: u128,
+    window_start: Timestamp,
+    messages: u32,
+}
+
+/// Players whose messages are refused. They still see what others say.
+#[table(name = muted_player, public)]
+pub struct MutedPlayer {
+    #[primary_key]
+    pub profile_id: u128,
+    /// Username at the time the player was muted, for display purposes only.
+    pub name: Option<String>,
+    pub reason: Option<String>,
+    /// Who issued the mute.
+    pub source: String,
+    pub created_at: Timestamp,
+    /// `None` for permanent mutes.
+    pub expires_at: Option<Timestamp>,
+}
+
+pub fn is_muted(ctx: &ReducerContext, profile_id: u128) -> bool {
+    ctx.db
+        .muted_player()
+        .profile_id()
+        .find(profile_id)
+        .is_some_and(|mute| in_effect(ctx, mute.expires_at))
+}
+
+/// Same rules as vanilla : not empty, not too long, without control characters nor `§`.
+fn validate_message(content: &str) -> Result<(), String> {
+    if content.trim().is_empty() {
+        return Err("Empty chat message".into());
+    }
+    if content.chars().count() > MAX_MESSAGE_LENGTH {
+        return Err(format!(
+            "Chat messages are limited to {MAX_MESSAGE_LENGTH} characters"
+        ));
+    }
+    if content
+        .chars()
+        .any(|c| c == '§' || c < ' ' || c == '\u{7f}')
+    {
+        return Err(ILLEGAL_CHARACTERS.into());
+    }
+    Ok(())
+}
+
+/// Counts one more message, failing once the player sent too many within the window.
+fn spend_message(ctx: &ReducerContext, profile_id: u128) -> Result<(), String> {
+    let existing = ctx.db.chat_budget().profile_id().find(profile_id);
+    let budget = match &existing {
+        Some(budget)
+            if ctx
+                .timestamp
+                .duration_since(budget.window_start)
+                .is_some_and(|elapsed| elapsed < MESSAGE_WINDOW) =>
+        {
+            if budget.messages >= MAX_MESSAGES_PER_WINDOW {
+                return Err(SPAM.into());
+            }
+            ChatBudget {
+                messages: budget.messages + 1,
+                ..*budget
+            }
+        }
+        _ => ChatBudget {
+            profile_id,
+            window_start: ctx.timestamp,
+            messages: 1,
+        },
+    };
+
+    if existing.is_some() {
+        ctx.db.chat_budget().profile_id().update(budget);
+    } else {
+        ctx.db.chat_budget().insert(budget);
+    }
+    Ok(())
+}
+
+fn validate_signed(
+    ctx: &ReducerContext,
+    profile_id: u128,
+    signed: &SignedChat,
+) -> Result<(), String> {
+    let Some(session) = ctx.db.chat_session().profile_id().find(profile_id) else {
+        return Err(MISSING_PROFILE_KEY.into());
+    };
+    if session.expires_at <= ctx.timestamp {
+        return Err(EXPIRED_PROFILE_KEY.into());
+    }
+    if signed.signature.len() != SIGNATURE_LENGTH
+        || signed.last_seen.len() > MAX_LAST_SEEN
+        || signed
+            .last_seen
+            .iter()
+            .any(|signature| signature.len() != SIGNATURE_LENGTH)
+    {
+        return Err("Invalid chat signature".into());
+    }
+    Ok(())
+}
+
+/// Called by the proxy holding the connection once it verified the key the player's client signs
+/// messages with.
+#[reducer]
+fn set_chat_session(
+    ctx: &ReducerContext,
+    profile_id_str: String,
+    connection_id: u64,
+    session_id: String,
+    public_key: Vec<u8>,
+    key_signature: Vec<u8>,
+    expires_at: Timestamp,
+) -> Result<(), String> {
+    require_registered_proxy(ctx)?;
+    let profile_id = UUID::from_str(&profile_id_str)?.as_u128();
+    require_session(ctx, profile_id, connection_id)?;
+    if !basic_config(ctx)?.allow_chat_reports {
+        return Err("Chat reports are not allowed".into());
+    }
+    if public_key.len() > MAX_PUBLIC_KEY_LENGTH || key_signature.len() > MAX_KEY_SIGNATURE_LENGTH {
+        return Err("Invalid profile public key".into());
+    }
+    if expires_at <= ctx.timestamp {
+        return Err(EXPIRED_PROFILE_KEY.into());
+    }
+
+    let session = ChatSession {
+        profile_id,
+        session_id: UUID::from_str(&session_id)?.as_u128(),
+        public_key,
+        key_signature,
+        expires_at,
+    };
+    if ctx
+        .db
+        .chat_session()
+        .profile_id()
+        .find(profile_id)
+        .is_some()
+    {
+        ctx.db.chat_session().profile_id().update(session);
+    } else {
+        ctx.db.chat_session().insert(session);
+    }
+    log::info!("Player {profile_id_str} started a chat session");
+    Ok(())
+}
+
+/// Called by the proxy holding the connection with what the player said. Errors are meant to be
+/// shown to the player, as translation keys or plain text.
+#[reducer]
+fn send_chat(
+    ctx: &ReducerContext,
+    profile_id_str: String,
+    connection_id: u64,
+    content: String,
+    signed: Option<SignedChat>,
+) -> Result<(), String> {
+    require_registered_proxy(ctx)?;
+    let profile_id = UUID::from_str(&profile_id_str)?.as_u128();
+    require_session(ctx, profile_id, connection_id)?;
+    validate_message(&content)?;
+    // Signatures are useless to clients once reports are disabled
+    let signed = match signed {
+        Some(signed) if basic_config(ctx)?.allow_chat_reports => {
+            validate_signed(ctx, profile_id, &signed)?;
+            Some(signed)
+        }
+        _ => None,
+    };
+    if is_muted(ctx, profile_id) {
+        return Err(MUTED.into());
+    }
+    spend_message(ctx, profile_id)?;
+
+    let (_, name) = resolve_profile(ctx, &profile_id_str)?;
+    let message = ctx.db.chat_message().insert(ChatMessage {
+        id: 0,
+        sender: profile_id,
+        sender_name: name.unwrap_or(profile_id_str),
+        content,
+        sent_at: ctx.timestamp,
+        signed,
+    });
+    log::info!("[Chat] <{}> {}", message.sender_name, message.content);
+    Ok(())
+}
+
+/// Mutes `target` (UUID or last known username). Without `expires_at` the mute is permanent.
+#[reducer]
+pub fn mute_player(
+    ctx: &ReducerContext,
+    target: String,
+    reason: Option<String>,
+    expires_at: Option<Timestamp>,
+    issuer: Option<String>,
+) -> Result<(), String> {
+    authorize(ctx, issuer.as_deref(), MUTE_COMMAND_LEVEL)?;
+    let (profile_id, name) = resolve_profile(ctx, &target)?;
+    let mute = MutedPlayer {
+        profile_id,
+        name,
+        reason,
+        source: source(ctx, issuer.as_deref())?,
+        created_at: ctx.timestamp,
+        expires_at,
+    };
+    let new_value = format!("{:?} until {:?}", mute.reason, mute.expires_at);
+    let old_value = match ctx.db.muted_player().profile_id().find(profile_id) {
+        Some(old) => {
+            ctx.db.muted_player().profile_id().update(mute);
+            Some(format!("{:?} until {:?}", old.reason, old.expires_at))
+        }
+        None => {
+            ctx.db.muted_player().insert(mute);
+            None
+        }
+    };
+    log::info!("Muted {target} ({})", Uuid::from_u128(profile_id));
+    audit::record(
+        ctx,
+        "mute_player",
+        issuer.as_deref(),
+        Some(format!("{target} ({})", Uuid::from_u128(profile_id))),
+        old_value,
+        Some(new_value),
+    );
+    Ok(())
+}
+
+#[reducer]
+pub fn unmute_player(
+    ctx: &ReducerContext,
+    target: String,
+    issuer: Option<String>,
+) -> Result<(), String> {
+    authorize(ctx, issuer.as_deref(), MUTE_COMMAND_LEVEL)?;
+    let (profile_id, _) = resolve_profile(ctx, &target)?;
+    let Some(mute) = ctx.db.muted_player().profile_id().find(profile_id) else {
+        return Err(format!("{target} is not muted"));
+    };
+    ctx.db.muted_player().profile_id().delete(profile_id);
+    log::info!("Unmuted {target} ({})", Uuid::from_u128(profile_id));
+    audit::record(
+        ctx,
+        "unmute_player",
+        issuer.as_deref(),
+        Some(format!("{target} ({})", Uuid::from_u128(profile_id))),
+        Some(format!("{:?} until {:?}", mute.reason, mute.expires_at)),
+        None,
+    );
+    Ok(())
+}
+
+#[table(name = chat_prune_schedule, scheduled(prune_chat_messages))]
+pub struct ChatPruneSchedule {
+    #[primary_key]
+    #[auto_inc]
+    scheduled_id: u64,
+    scheduled_at: ScheduleAt,
+}
+
+/// Starts pruning delivered messages, unless already scheduled.
+pub fn schedule_prune(ctx: &ReducerContext) {
+    if ctx.db.chat_prune_schedule().count() == 0 {
+        ctx.db.chat_prune_schedule().insert(ChatPruneSchedule {
+            scheduled_id: 0,
+            scheduled_at: ScheduleAt::Interval(PRUNE_INTERVAL.into()),
+        });
+        log::info!("Scheduled chat message pruning every {PRUNE_INTERVAL:?}");
+    }
+}
+
+#[reducer]
+fn prune_chat_messages(ctx: &ReducerContext, _schedule: ChatPruneSchedule) -> Result<(), String> {
+    if ctx.sender != ctx.identity() {
+        return Err("Only the scheduler may prune chat messages".into());
+    }
+
+    let expired = ctx
+        .db
+        .chat_message()
+        .iter()
+        .filter(|message| {
+            ctx.timestamp
+                .duration_since(message.sent_at)
+                .is_some_and(|age| age > MESSAGE_RETENTION)
+        })
+        .map(|message| message.id)
+        .collect::<Vec<_>>();
+    for id in expired {
+        ctx.db.chat_message().id().delete(id);
+    }
+    Ok(())
+}
@@ -0,0 +1,368 @@
+use crate::auth::require_registered_proxy;
+use crate::ban::{BAN_COMMAND_LEVEL, ban_ip, ban_player, pardon_ip, pardon_player};
+use crate::chat::{MUTE_COMMAND_LEVEL, mute_player, unmute_player};
+use crate::operator::{OP_COMMAND_LEVEL, deop_player, op_player, permission_level};
+use crate::player::{KICK_COMMAND_LEVEL, kick_player, require_session};
+use crate::server::basic_config;
+use crate::server::config::PermissionLvl;
+use crate::types_support::UUID;
+use crate::whitelist::{WHITELIST_COMMAND_LEVEL, set_whitelist, whitelist_add, whitelist_remove};
+use spacetimedb::{Identity, ReducerContext, SpacetimeType, Table, Timestamp, reducer, table};
+use std::str::FromStr;
+
+/// Shown for commands that do not exist or that the player may not run, as vanilla does.
+pub const UNKNOWN_COMMAND: &str = "command.unknown.command";
+
+#[derive(Clone, Copy, Debug, PartialEq, SpacetimeType)]
+pub enum ArgumentType {
+    /// Username or UUID of a player, who may be offline.
+    Player,
+    /// A single word.
+    Word,
+    /// Everything up to the end of the line.
+    GreedyString,
+}
+
+#[derive(Clone, Debug, SpacetimeType)]
+pub struct CommandArgument {
+    pub name: String,
+    pub kind: ArgumentType,
+    /// Optional arguments all come last.
+    pub optional: bool,
+}
+
+/// Commands players may run, along with the level they need. Proxies build the command tree each
+/// player sees from it, leaving out what their level does not allow.
+#[table(name = command, public)]
+pub struct Command {
+    /// Literals leading to the arguments, e.g. `whitelist add`.
+    #[primary_key]
+    pub name: String,
+    pub description: String,
+    pub arguments: Vec<CommandArgument>,
+    pub level: PermissionLvl,
+}
+
+/// What a command did, for the proxy holding the issuer's connection to tell them. The proxy
+/// deletes it once handled.
+#[table(name = command_feedback, public)]
+pub struct CommandFeedback {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    #[index(btree)]
+    pub proxy: Identity,
+    pub connection_id: u64,
+    pub profile_id: u128,
+    pub message: String,
+    pub created_at: Timestamp,
+}
+
+fn required(name: &str, kind: ArgumentType) -> CommandArgument {
+    CommandArgument {
+        name: name.into(),
+        kind,
+        optional: false,
+    }
+}
+
+fn optional(name: &str, kind: ArgumentType) -> CommandArgument {
+    CommandArgument {
+        name: name.into(),
+        kind,
+        optional: true,
+    }
+}
+
+fn command(
+    name: &str,
+    description: &str,
+    level: PermissionLvl,
+    arguments: Vec<CommandArgument>,
+) -> Command {
+    Command {
+        name: name.into(),
+        description: description.into(),
+        arguments,
+        level,
+    }
+}
+
+/// Every command [execute] knows how to run.
+pub fn builtin() -> Vec<Command> {
+    use ArgumentType::{GreedyString, Player, Word};
+    vec![
+        command(
+            "ban",
+            "Bans a player",
+            BAN_COMMAND_LEVEL,
+            vec![required("target", Player), optional("reason", GreedyString)],
+        ),
+        command(
+            "ban-ip",
+            "Bans an IP address",
+            BAN_COMMAND_LEVEL,
+            vec![required("target", Word), optional("reason", GreedyString)],
+        ),
+        command(
+            "pardon",
+            "Lifts the ban of a player",
+            BAN_COMMAND_LEVEL,
+            vec![required("target", Player)],
+        ),
+        command(
+            "pardon-ip",
+            "Lifts the ban of an IP address",
+            BAN_COMMAND_LEVEL,
+            vec![required("target", Word)],
+        ),
+        command(
+            "kick",
+            "Disconnects a player",
+            KICK_COMMAND_LEVEL,
+            vec![required("target", Player), optional("reason", GreedyString)],
+        ),
+        command(
+            "op",
+            "Makes a player an operator",
+            OP_COMMAND_LEVEL,
+            vec![required("target", Player)],
+        ),
+        command(
+            "deop",
+            "Takes operator rights away from a player",
+            OP_COMMAND_LEVEL,
+            vec![required("target", Player)],
+        ),
+        command(
+            "mute",
+            "Refuses the chat messages of a player",
+            MUTE_COMMAND_LEVEL,
+            vec![required("target", Player), optional("reason", GreedyString)],
+        ),
+        command(
+            "unmute",
+            "Lets a player chat again",
+            MUTE_COMMAND_LEVEL,
+            vec![required("target", Player)],
+        ),
+        command(
+            "whitelist add",
+            "Adds a player to the whitelist",
+            WHITELIST_COMMAND_LEVEL,
+            vec![required("target", Player)],
+        ),
+        command(
+            "whitelist remove",
+            "Removes a player from the whitelist",
+            WHITELIST_COMMAND_LEVEL,
+            vec![required("target", Player)],
+        ),
+        command(
+            "whitelist on",
+            "Enables the whitelist",
+            WHITELIST_COMMAND_LEVEL,
+            vec![],
+        ),
+        command(
+            "whitelist off",
+            "Disables the whitelist",
+            WHITELIST_COMMAND_LEVEL,
+            vec![],
+        ),
+    ]
+}
+
+/// Replaces the registered commands with [builtin] ones, through a migration step each time they
+/// change.
+pub fn sync(ctx: &ReducerContext) -> Result<(), String> {
+    let registered = ctx
+        .db
+        .command()
+        .iter()
+        .map(|command| command.name)
+        .collect::<Vec<_>>();
+    for name in stale(&registered) {
+        ctx.db.command().name().delete(name);
+    }
+    for command in builtin() {
+        if ctx.db.command().name().find(&command.name).is_some() {
+            ctx.db.command().name().update(command);
+        } else {
+            ctx.db.command().insert(command);
+        }
+    }
+    log::info!("Registered {} commands", ctx.db.command().count());
+    Ok(())
+}
+
+/// The registered commands that are no longer [builtin].
+pub fn stale(registered: &[String]) -> Vec<String> {
+    let commands = builtin();
+    registered
+        .iter()
+        .filter(|name| !commands.iter().any(|c| &c.name == *name))
+        .cloned()
+        .collect()
+}
+
+fn usage(command: &Command) -> String {
+    let arguments = command.arguments.iter().map(|argument| {
+        if argument.optional {
+            format!(" [<{}>]", argument.name)
+        } else {
+            format!(" <{}>", argument.name)
+        }
+    });
+    format!("/{}{}", command.name, arguments.collect::<String>())
+}
+
+/// Splits `line` into the registered command it starts with, the longest one if several do, and
+/// its arguments.
+fn parse(ctx: &ReducerContext, line: &str) -> Result<(Command, Vec<Option<String>>), String> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    let command = ctx
+        .db
+        .command()
+        .iter()
+        .filter(|command| {
+            let name = command.name.split(' ').collect::<Vec<_>>();
+            words.len() >= name.len() && words[..name.len()] == name[..]
+        })
+        .max_by_key(|command| command.name.split(' ').count())
+        .ok_or_else(|| UNKNOWN_COMMAND.to_string())?;
+
+    let mut rest = words[command.name.split(' ').count()..].iter();
+    let mut arguments = Vec::with_capacity(command.arguments.len());
+    for argument in &command.arguments {
+        let value = match argument.kind {
+            ArgumentType::GreedyString => {
+                let words = rest.by_ref().copied().collect::<Vec<_>>();
+                (!words.is_empty()).then(|| words.join(" "))
+            }
+            ArgumentType::Player | ArgumentType::Word => rest.next().map(|word| word.to_string()),
+        };
+        if value.is_none() && !argument.optional {
+            return Err(format!("Usage : {}", usage(&command)));
+        }
+        arguments.push(value);
+    }
+    if rest.next().is_some() {
+        return Err(format!("Usage : {}", usage(&command)));
+    }
+    Ok((command, arguments))
+}
+
+/// Runs the command through the reducer behind it, on behalf of `issuer`. Returns what to tell
+/// them.
+fn execute(
+    ctx: &ReducerContext,
+    command: &str,
+    arguments: Vec<Option<String>>,
+    issuer: String,
+) -> Result<String, String> {
+    let mut arguments = arguments.into_iter();
+    let mut next = || arguments.next().flatten();
+    // Present whenever required, [parse] made sure of it
+    let target = next().unwrap_or_default();
+    let issuer = Some(issuer);
+    match command {
+        "ban" => {
+            ban_player(ctx, target.clone(), next(), None, issuer)?;
+            Ok(format!("Banned {target}"))
+        }
+        "ban-ip" => {
+            ban_ip(ctx, target.clone(), next(), None, issuer)?;
+            Ok(format!("Banned IP {target}"))
+        }
+        "pardon" => {
+            pardon_player(ctx, target.clone(), issuer)?;
+            Ok(format!("Unbanned {target}"))
+        }
+        "pardon-ip" => {
+            pardon_ip(ctx, target.clone(), issuer)?;
+            Ok(format!("Unbanned IP {target}"))
+        }
+        "kick" => {
+            kick_player(ctx, target.clone(), next(), issuer)?;
+            Ok(format!("Kicked {target}"))
+        }
+        "op" => {
+            op_player(ctx, target.clone(), None, false, issuer)?;
+            Ok(format!("Made {target} a server operator"))
+        }
+        "deop" => {
+            deop_player(ctx, target.clone(), issuer)?;
+            Ok(format!("Made {target} no longer a server operator"))
+        }
+        "mute" => {
+            mute_player(ctx, target.clone(), next(), None, issuer)?;
+            Ok(format!("Muted {target}"))
+        }
+        "unmute" => {
+            unmute_player(ctx, target.clone(), issuer)?;
+            Ok(format!("Unmuted {target}"))
+        }
+        "whitelist add" => {
+            whitelist_add(ctx, target.clone(), issuer)?;
+            Ok(format!("Added {target} to the whitelist"))
+        }
+        "whitelist remove" => {
+            whitelist_remove(ctx, target.clone(), issuer)?;
+            Ok(format!("Removed {target} from the whitelist"))
+        }
+        "whitelist on" | "whitelist off" => {
+            let enabled = command == "whitelist on";
+            let enforced = basic_config(ctx)?.enforce_whitelist;
+            set_whitelist(ctx, enabled, enforced, issuer)?;
+            Ok(format!(
+                "Whitelist is now {}",
+                if enabled { "turned on" } else { "turned off" }
+            ))
+        }
+        other => Err(format!("Command {other} is registered but can not be run")),
+    }
+}
+
+/// Called by the proxy holding the connection with the command the player typed, without its
+/// slash. Errors are meant to be shown to the player, as translation keys or plain text.
+#[reducer]
+fn run_command(
+    ctx: &ReducerContext,
+    profile_id_str: String,
+    connection_id: u64,
+    line: String,
+) -> Result<(), String> {
+    require_registered_proxy(ctx)?;
+    let profile_id = UUID::from_str(&profile_id_str)?.as_u128();
+    require_session(ctx, profile_id, connection_id)?;
+
+    let (command, arguments) = parse(ctx, &line)?;
+    if permission_level(ctx, profile_id) < command.level {
+        return Err(UNKNOWN_COMMAND.into());
+    }
+    log::info!("Player {profile_id_str} ran /{line}");
+    let message = execute(ctx, &command.name, arguments, profile_id_str)?;
+    ctx.db.command_feedback().insert(CommandFeedback {
+        id: 0,
+        proxy: ctx.sender,
+        connection_id,
+        profile_id,
+        message,
+        created_at: ctx.timestamp,
+    });
+    Ok(())
+}
+
+#[reducer]
+fn acknowledge_command_feedback(ctx: &ReducerContext, id: u64) -> Result<(), String> {
+    require_registered_proxy(ctx)?;
+    match ctx.db.command_feedback().id().find(id) {
+        Some(feedback) if feedback.proxy == ctx.sender => {
+            ctx.db.command_feedback().id().delete(id);
+            Ok(())
+        }
+        Some(_) => Err(format!("Feedback {id} is not addressed to {}", ctx.sender)),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,413 @@
+use crate::auth::require_registered_proxy;
+use crate::player::require_session;
+use crate::player_state::player_state;
+use crate::server::config::GameMode;
+use crate::types_support::UUID;
+use spacetimedb::{ReducerContext, SpacetimeType, Table, reducer, table};
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Slots of the player inventory, numbered as the protocol does : the crafting result, the
+/// crafting grid, the armor, the main inventory, the hotbar then the offhand.
+pub const INVENTORY_SLOTS: usize = 46;
+pub const ENDER_CHEST_SLOTS: usize = 27;
+const CRAFTING_SLOTS: std::ops::Range<usize> = 0..5;
+const MAIN_SLOTS: std::ops::Range<usize> = 9..36;
+const HOTBAR_SLOTS: std::ops::Range<usize> = 36..45;
+const OFFHAND_SLOT: usize = 45;
+
+/// Clicking outside the window, which drops the carried stack.
+const OUTSIDE_WINDOW: i16 = -999;
+/// Swap button of the offhand, the hotbar ones being 0 to 8.
+const OFFHAND_BUTTON: i8 = 40;
+
+/// Click modes of the Click Container packet.
+const PICKUP: u8 = 0;
+const QUICK_MOVE: u8 = 1;
+const SWAP: u8 = 2;
+const CLONE: u8 = 3;
+
+/// Armor slots, top to bottom, with what their items are named after.
+const ARMOR: [(usize, &[&str]); 4] = [
+    (
+        5,
+        &[
+            "_helmet",
+            ":carved_pumpkin",
+            "_head",
+            "_skull",
+            ":turtle_helmet",
+        ],
+    ),
+    (6, &["_chestplate", ":elytra"]),
+    (7, &["_leggings"]),
+    (8, &["_boots"]),
+];
+
+#[derive(Clone, Debug, PartialEq, SpacetimeType)]
+pub struct ItemStack {
+    /// Item key, e.g. `minecraft:diamond_sword`.
+    pub item: String,
+    pub count: u8,
+    /// As set by the proxy that created the stack, the module does not know items.
+    pub max_stack_size: u8,
+    /// Data component patch, as the protocol encodes it right after the item id.
+    pub components: Vec<u8>,
+}
+
+impl ItemStack {
+    fn stacks_with(&self, other: &ItemStack) -> bool {
+        self.item == other.item && self.components == other.components
+    }
+
+    fn with_count(&self, count: u8) -> ItemStack {
+        ItemStack {
+            count,
+            ..self.clone()
+        }
+    }
+}
+
+/// The items of a player, along with the stack they carry with the cursor.
+#[table(name = player_inventory, public)]
+pub struct PlayerInventory {
+    #[primary_key]
+    pub profile_id: u128,
+    /// [INVENTORY_SLOTS] slots
+    pub slots: Vec<Option<ItemStack>>,
+    pub carried: Option<ItemStack>,
+}
+
+#[table(name = ender_chest, public)]
+pub struct EnderChest {
+    #[primary_key]
+    pub profile_id: u128,
+    /// [ENDER_CHEST_SLOTS] slots
+    pub slots: Vec<Option<ItemStack>>,
+}
+
+/// Which hotbar slot the player holds, 0 to 8.
+#[table(name = held_slot, public)]
+pub struct HeldSlot {
+    #[primary_key]
+    pub profile_id: u128,
+    pub slot: u8,
+}
+
+/// Gives a joining player empty containers, the first time only.
+pub fn on_join(ctx: &ReducerContext, profile_id: u128) {
+    if ctx
+        .db
+        .player_inventory()
+        .profile_id()
+        .find(profile_id)
+        .is_none()
+    {
+        ctx.db.player_inventory().insert(PlayerInventory {
+            profile_id,
+            slots: vec![None; INVENTORY_SLOTS],
+            carried: None,
+        });
+    }
+    if ctx.db.ender_chest().profile_id().find(profile_id).is_none() {
+        ctx.db.ender_chest().insert(EnderChest {
+            profile_id,
+            slots: vec![None; ENDER_CHEST_SLOTS],
+        });
+    }
+    if ctx.db.held_slot().profile_id().find(profile_id).is_none() {
+        ctx.db.held_slot().insert(HeldSlot {
+            profile_id,
+            slot: 0,
+        });
+    }
+}
+
+fn inventory(ctx: &ReducerContext, profile_id: u128) -> Result<PlayerInventory, String> {
+    let mut inventory = ctx
+        .db
+        .player_inventory()
+        .profile_id()
+        .find(profile_id)
+        .ok_or_else(|| format!("Player {} has no inventory", Uuid::from_u128(profile_id)))?;
+    inventory.slots.resize(INVENTORY_SLOTS, None);
+    Ok(inventory)
+}
+
+fn game_mode(ctx: &ReducerContext, profile_id: u128) -> Option<GameMode> {
+    ctx.db
+        .player_state()
+        .profile_id()
+        .find(profile_id)
+        .map(|state| state.game_mode)
+}
+
+fn validate_stack(stack: &ItemStack) -> Result<(), String> {
+    if stack.count == 0 || stack.max_stack_size == 0 || stack.count > stack.max_stack_size {
+        return Err(format!(
+            "Invalid stack of {} {} out of {}",
+            stack.count, stack.item, stack.max_stack_size
+        ));
+    }
+    Ok(())
+}
+
+/// Whether the stack may be put in the slot, armor slots only take their own kind of armor.
+fn fits(slot: usize, stack: &ItemStack) -> bool {
+    if CRAFTING_SLOTS.contains(&slot) {
+        return false;
+    }
+    match ARMOR.iter().find(|(armor_slot, _)| *armor_slot == slot) {
+        Some((_, suffixes)) => {
+            stack.count == 1 && suffixes.iter().any(|suffix| stack.item.ends_with(suffix))
+        }
+        None => true,
+    }
+}
+
+fn slot_index(slot: i16) -> Result<usize, String> {
+    usize::try_from(slot)
+        .ok()
+        .filter(|&slot| slot < INVENTORY_SLOTS)
+        .ok_or_else(|| format!("Invalid slot {slot}"))
+}
+
+/// Left or right click on a slot, with or without a carried stack.
+fn pickup(inventory: &mut PlayerInventory, slot: usize, right: bool) -> Result<(), String> {
+    if CRAFTING_SLOTS.contains(&slot) {
+        return Err("Crafting is not supported yet".into());
+    }
+    let current = inventory.slots[slot].take();
+    let carried = inventory.carried.take();
+    let (slot_stack, carried) = match (current, carried) {
+        (None, None) => (None, None),
+        // Right click takes half, rounded up
+        (Some(stack), None) if right => {
+            let taken = stack.count.div_ceil(2);
+            let left = (stack.count > taken).then(|| stack.with_count(stack.count - taken));
+            (left, Some(stack.with_count(taken)))
+        }
+        (Some(stack), None) => (None, Some(stack)),
+        (None, Some(carried)) if !fits(slot, &carried.with_count(1)) => (None, Some(carried)),
+        // Right click places one, as do slots that only hold one, e.g. armor
+        (None, Some(carried)) => {
+            let placed = if right || !fits(slot, &carried) {
+                1
+            } else {
+                carried.count
+            };
+            let rest = (carried.count > placed).then(|| carried.with_count(carried.count - placed));
+            (Some(carried.with_count(placed)), rest)
+        }
+        (Some(stack), Some(carried)) if stack.stacks_with(&carried) => {
+            let room = stack.max_stack_size.saturating_sub(stack.count);
+            let moved = if right {
+                1.min(room)
+            } else {
+                room.min(carried.count)
+            };
+            let rest = (carried.count > moved).then(|| carried.with_count(carried.count - moved));
+            (Some(stack.with_count(stack.count + moved)), rest)
+        }
+        (Some(stack), Some(carried)) if fits(slot, &carried) => (Some(carried), Some(stack)),
+        (Some(stack), Some(carried)) => (Some(stack), Some(carried)),
+    };
+    inventory.slots[slot] = slot_stack;
+    inventory.carried = carried;
+    Ok(())
+}
+
+/// Shift click : moves the stack to the main inventory from the hotbar, and the other way around.
+/// Armor and offhand stacks go to the main inventory first.
+fn quick_move(inventory: &mut PlayerInventory, slot: usize) -> Result<(), String> {
+    if CRAFTING_SLOTS.contains(&slot) {
+        return Err("Crafting is not supported yet".into());
+    }
+    let Some(mut stack) = inventory.slots[slot].take() else {
+        return Ok(());
+    };
+    let targets = if MAIN_SLOTS.contains(&slot) {
+        HOTBAR_SLOTS.collect::<Vec<_>>()
+    } else if HOTBAR_SLOTS.contains(&slot) {
+        MAIN_SLOTS.collect()
+    } else {
+        MAIN_SLOTS.chain(HOTBAR_SLOTS).collect()
+    };
+
+    // Filling stacks of the same item first, then empty slots
+    for &target in &targets {
+        match &mut inventory.slots[target] {
+            Some(existing) if existing.stacks_with(&stack) => {
+                let moved = existing
+                    .max_stack_size
+                    .saturating_sub(existing.count)
+                    .min(stack.count);
+                existing.count += moved;
+                stack.count -= moved;
+            }
+            _ => {}
+        }
+        if stack.count == 0 {
+            return Ok(());
+        }
+    }
+    if let Some(&target) = targets
+        .iter()
+        .find(|&&target| inventory.slots[target].is_none())
+    {
+        inventory.slots[target] = Some(stack);
+    } else {
+        inventory.slots[slot] = Some(stack);
+    }
+    Ok(())
+}
+
+/// Number key or offhand key over a slot, swapping it with that hotbar or offhand slot.
+fn swap(inventory: &mut PlayerInventory, slot: usize, button: i8) -> Result<(), String> {
+    let other = match button {
+        0..=8 => HOTBAR_SLOTS.start + button as usize,
+        OFFHAND_BUTTON => OFFHAND_SLOT,
+        _ => return Err(format!("Invalid swap button {button}")),
+    };
+    let (a, b) = (
+        inventory.slots[slot].clone(),
+        inventory.slots[other].clone(),
+    );
+    if a.as_ref().is_some_and(|stack| !fits(other, stack))
+        || b.as_ref().is_some_and(|stack| !fits(slot, stack))
+    {
+        return Err(format!("Slots {slot} and {other} can not be swapped"));
+    }
+    inventory.slots.swap(slot, other);
+    Ok(())
+}
+
+/// Middle click in creative mode, carrying a full stack of the item.
+fn clone_stack(inventory: &mut PlayerInventory, slot: usize) -> Result<(), String> {
+    if inventory.carried.is_some() {
+        return Ok(());
+    }
+    inventory.carried = inventory.slots[slot]
+        .as_ref()
+        .map(|stack| stack.with_count(stack.max_stack_size));
+    Ok(())
+}
+
+/// Called by the proxy holding the connection when the player clicks in their inventory.
+/// The result is computed here, the client's prediction only lasts until the row changes.
+#[reducer]
+fn click_container(
+    ctx: &ReducerContext,
+    profile_id_str: String,
+    connection_id: u64,
+    slot: i16,
+    button: i8,
+    mode: u8,
+) -> Result<(), String> {
+    require_registered_proxy(ctx)?;
+    let profile_id = UUID::from_str(&profile_id_str)?.as_u128();
+    require_session(ctx, profile_id, connection_id)?;
+    let mut inventory = inventory(ctx, profile_id)?;
+
+    if slot == OUTSIDE_WINDOW {
+        return Err("Dropping items is not supported yet".into());
+    }
+    let slot = slot_index(slot)?;
+    match mode {
+        PICKUP if button == 0 || button == 1 => pickup(&mut inventory, slot, button == 1)?,
+        QUICK_MOVE => quick_move(&mut inventory, slot)?,
+        SWAP => swap(&mut inventory, slot, button)?,
+        CLONE if game_mode(ctx, profile_id) == Some(GameMode::Creative) => {
+            clone_stack(&mut inventory, slot)?
+        }
+        _ => {
+            return Err(format!(
+                "Click mode {mode} with button {button} is not supported"
+            ));
+        }
+    }
+    ctx.db.player_inventory().profile_id().update(inventory);
+    Ok(())
+}
+
+/// Creative players set any slot to any stack, as vanilla trusts them to.
+#[reducer]
+fn set_creative_slot(
+    ctx: &ReducerContext,
+    profile_id_str: String,
+    connection_id: u64,
+    slot: i16,
+    stack: Option<ItemStack>,
+) -> Result<(), String> {
+    require_registered_proxy(ctx)?;
+    let profile_id = UUID::from_str(&profile_id_str)?.as_u128();
+    require_session(ctx, profile_id, connection_id)?;
+    if game_mode(ctx, profile_id) != Some(GameMode::Creative) {
+        return Err(format!("Player {profile_id_str} is not in creative mode"));
+    }
+    if let Some(stack) = &stack {
+        validate_stack(stack)?;
+    }
+    // Creative players delete items by dropping them outside the window
+    if slot == OUTSIDE_WINDOW {
+        return Ok(());
+    }
+
+    let slot = slot_index(slot)?;
+    if stack.as_ref().is_some_and(|stack| !fits(slot, stack)) {
+        return Err(format!("Slot {slot} can not hold this stack"));
+    }
+    let mut inventory = inventory(ctx, profile_id)?;
+    inventory.slots[slot] = stack;
+    ctx.db.player_inventory().profile_id().update(inventory);
+    Ok(())
+}
+
+/// Puts the carried stack back in the inventory once the player closes it. What does not fit stays
+/// carried, as dropping items is not supported yet.
+#[reducer]
+fn close_container(
+    ctx: &ReducerContext,
+    profile_id_str: String,
+    connection_id: u64,
+) -> Result<(), String> {
+    require_registered_proxy(ctx)?;
+    let profile_id = UUID::from_str(&profile_id_str)?.as_u128();
+    require_session(ctx, profile_id, connection_id)?;
+    let mut inventory = inventory(ctx, profile_id)?;
+    let Some(carried) = inventory.carried.take() else {
+        return Ok(());
+    };
+
+    let slot = HOTBAR_SLOTS
+        .chain(MAIN_SLOTS)
+        .find(|&slot| inventory.slots[slot].is_none());
+    match slot {
+        Some(slot) => inventory.slots[slot] = Some(carried),
+        None => inventory.carried = Some(carried),
+    }
+    ctx.db.player_inventory().profile_id().update(inventory);
+    Ok(())
+}
+
+#[reducer]
+fn set_held_slot(
+    ctx: &ReducerContext,
+    profile_id_str: String,
+    connection_id: u64,
+    slot: u8,
+) -> Result<(), String> {
+    require_registered_proxy(ctx)?;
+    let profile_id = UUID::from_str(&profile_id_str)?.as_u128();
+    require_session(ctx, profile_id, connection_id)?;
+    if usize::from(slot) >= HOTBAR_SLOTS.len() {
+        return Err(format!("Invalid hotbar slot {slot}"));
+    }
+    let held = HeldSlot { profile_id, slot };
+    if ctx.db.held_slot().profile_id().find(profile_id).is_some() {
+        ctx.db.held_slot().profile_id().update(held);
+    } else {
+        ctx.db.held_slot().insert(held);
+    }
+    Ok(())
+}
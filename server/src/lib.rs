@@ -1,21 +1,46 @@
+mod audit;
+mod auth;
+mod ban;
+mod chat;
+mod command;
+mod inventory;
+mod migration;
+mod operator;
 mod player;
+mod player_state;
+mod proxy;
 mod server;
 mod types_support;
+mod violation;
+mod whitelist;
+mod world;
 
 use spacetimedb::{ReducerContext, reducer};
 
 #[reducer(init)]
-pub fn init(ctx: &ReducerContext) {
+pub fn init(ctx: &ReducerContext) -> Result<(), String> {
     log::info!("Initialized : {}", ctx.sender);
-    server::init(ctx);
+    auth::seed_first_admin(ctx);
+    migration::run(ctx)?;
+    Ok(())
 }
 
+/// Publishing an update does not call `init`, so pending migrations also run when the first
+/// client (usually a proxy) connects afterwards. Admins can run them sooner with `migrate`.
+/// Clients are refused while the data can not be migrated.
 #[reducer(client_connected)]
-pub fn client_connected(ctx: &ReducerContext) {
+pub fn client_connected(ctx: &ReducerContext) -> Result<(), String> {
     log::info!("Client connected : {}", ctx.sender);
+    if migration::version(ctx) != migration::CURRENT_VERSION {
+        migration::run(ctx)?;
+    }
+    Ok(())
 }
 
 #[reducer(client_disconnected)]
 pub fn client_disconnected(ctx: &ReducerContext) {
     log::info!("Client disconnected : {}", ctx.sender);
+    audit::clear_excerpt(ctx, ctx.sender);
+    ban::clear_excerpt(ctx, ctx.sender);
+    proxy::proxy_offline(ctx, ctx.sender);
 }
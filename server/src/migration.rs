@@ -0,0 +1,262 @@
+use crate::auth::{admin_identity, require_admin};
+use crate::proxy::schedule_sweep;
+use crate::server::config::{BasicConfiguration, server_basic_config};
+use crate::{audit, chat, command};
+use spacetimedb::{ReducerContext, Table, Timestamp, reducer, table};
+use std::ops::Range;
+
+/// A migration step, upgrading the data from one schema version to the next.
+type Step = fn(&ReducerContext) -> Result<(), String>;
+
+/// Every migration, in order : the step at index `i` upgrades version `i` to version `i + 1`.
+///
+/// New steps go at the end, and into `steps_keep_their_versions` : inserting one would make
+/// databases skip it, or run another one twice. Once published, a step must not change either,
+/// as databases already past it will never run it again. Steps must also cope with data created
+/// before versioning existed, which is treated as version 0.
+const MIGRATIONS: &[(&str, Step)] = &[
+    ("default basic configuration", default_basic_config),
+    ("random world seed", random_world_seed),
+    ("command registry", command_registry),
+    ("first admin", first_admin),
+    ("dead proxy sweep", dead_proxy_sweep),
+    ("audit log retention", audit_log_retention),
+    ("chat message pruning", chat_message_pruning),
+];
+
+/// The schema version this module expects.
+pub const CURRENT_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Single row tracking which migrations the data went through.
+#[table(name = schema_version)]
+pub struct SchemaVersion {
+    #[primary_key]
+    id: u8,
+    version: u32,
+    migrated_at: Timestamp,
+}
+
+pub fn version(ctx: &ReducerContext) -> u32 {
+    ctx.db
+        .schema_version()
+        .id()
+        .find(0)
+        .map_or(0, |row| row.version)
+}
+
+/// Indices in [MIGRATIONS] of the steps data at version `from` did not go through yet, in order.
+fn pending(from: u32) -> Result<Range<usize>, String> {
+    if from > CURRENT_VERSION {
+        return Err(format!(
+            "Data is at schema version {from}, newer than this module's {CURRENT_VERSION}"
+        ));
+    }
+    Ok(from as usize..MIGRATIONS.len())
+}
+
+/// Runs the migrations the data did not go through yet, returning the resulting version.
+/// Either all of them apply or, since the calling reducer then fails, none do.
+pub fn run(ctx: &ReducerContext) -> Result<u32, String> {
+    let from = version(ctx);
+    for version in pending(from)? {
+        let (name, step) = MIGRATIONS[version];
+        log::info!("Migrating to schema version {} : {name}", version + 1);
+        step(ctx).map_err(|e| format!("Migration to version {} failed : {e}", version + 1))?;
+    }
+
+    if from != CURRENT_VERSION {
+        let row = SchemaVersion {
+            id: 0,
+            version: CURRENT_VERSION,
+            migrated_at: ctx.timestamp,
+        };
+        if ctx.db.schema_version().id().find(0).is_some() {
+            ctx.db.schema_version().id().update(row);
+        } else {
+            ctx.db.schema_version().insert(row);
+        }
+        log::info!("Migrated schema from version {from} to {CURRENT_VERSION}");
+    }
+    Ok(CURRENT_VERSION)
+}
+
+/// Runs pending migrations, e.g. right after publishing an update of the module.
+#[reducer]
+fn migrate(ctx: &ReducerContext) -> Result<(), String> {
+    require_admin(ctx)?;
+    run(ctx).map(|_| ())
+}
+
+/// Version 1 : the configuration row every other part of the module relies on.
+fn default_basic_config(ctx: &ReducerContext) -> Result<(), String> {
+    let existing = ctx.db.server_basic_config().id().find(0);
+    if let Some(config) = missing_config(existing.as_ref()) {
+        ctx.db.server_basic_config().insert(config);
+        log::info!("Stored default basic configuration");
+    }
+    Ok(())
+}
+
+/// The configuration to store, unless one already is.
+fn missing_config(existing: Option<&BasicConfiguration>) -> Option<BasicConfiguration> {
+    existing.is_none().then(BasicConfiguration::default)
+}
+
+/// Version 2 : an empty seed means a random one in vanilla. Picking it once here keeps the terrain
+/// generated by every proxy consistent.
+fn random_world_seed(ctx: &ReducerContext) -> Result<(), String> {
+    let Some(mut config) = ctx.db.server_basic_config().id().find(0) else {
+        return Err("Did not find a basic config !".into());
+    };
+    if pick_seed(&mut config, || ctx.random::<i64>()) {
+        log::info!("Picked world seed {}", config.seed);
+        ctx.db.server_basic_config().id().update(config);
+    }
+    Ok(())
+}
+
+/// Sets a seed from `random` if the configured one is empty, returning whether it did.
+fn pick_seed(config: &mut BasicConfiguration, random: impl FnOnce() -> i64) -> bool {
+    if config.seed.trim().is_empty() {
+        config.seed = random().to_string();
+        true
+    } else {
+        false
+    }
+}
+
+/// Version 3 : the commands proxies offer to players. Changing the built-in commands takes a new
+/// step calling [command::sync] again.
+fn command_registry(ctx: &ReducerContext) -> Result<(), String> {
+    command::sync(ctx)
+}
+
+/// Version 4 : someone to manage the module. Fresh data gets the publisher from `init`, while
+/// data from before admins existed gets none here, as the sender may be anyone connecting. The
+/// database owner then allows an identity to `claim_admin` through [crate::auth::AdminClaim].
+fn first_admin(ctx: &ReducerContext) -> Result<(), String> {
+    if ctx.db.admin_identity().count() == 0 {
+        log::warn!(
+            "No admin : insert the identity to grant into admin_claim with `spacetime sql`, \
+            then call claim_admin with it"
+        );
+    }
+    Ok(())
+}
+
+/// Version 5 : the schedule ending the sessions of proxies that stopped without telling.
+fn dead_proxy_sweep(ctx: &ReducerContext) -> Result<(), String> {
+    schedule_sweep(ctx);
+    Ok(())
+}
+
+/// Version 6 : the schedule deleting audit log entries once they are too old.
+fn audit_log_retention(ctx: &ReducerContext) -> Result<(), String> {
+    audit::schedule_prune(ctx);
+    Ok(())
+}
+
+/// Version 7 : the schedule deleting chat messages once every proxy relayed them.
+fn chat_message_pruning(ctx: &ReducerContext) -> Result<(), String> {
+    chat::schedule_prune(ctx);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pending_steps_follow_the_version() {
+        assert_eq!(pending(0), Ok(0..MIGRATIONS.len()));
+        assert_eq!(pending(2), Ok(2..MIGRATIONS.len()));
+        assert!(pending(CURRENT_VERSION + 1).is_err());
+    }
+
+    #[test]
+    fn nothing_is_pending_once_migrated() {
+        assert!(pending(CURRENT_VERSION).unwrap().is_empty());
+    }
+
+    #[test]
+    fn steps_keep_their_versions() {
+        let names = MIGRATIONS.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "default basic configuration",
+                "random world seed",
+                "command registry",
+                "first admin",
+                "dead proxy sweep",
+                "audit log retention",
+                "chat message pruning",
+            ]
+        );
+    }
+
+    #[test]
+    fn step_names_are_unique() {
+        for (index, (name, _)) in MIGRATIONS.iter().enumerate() {
+            assert!(
+                MIGRATIONS[..index].iter().all(|(other, _)| other != name),
+                "Step {name} appears twice"
+            );
+        }
+    }
+
+    #[test]
+    fn default_config_only_fills_a_missing_row() {
+        let stored = missing_config(None).expect("A default configuration");
+        assert_eq!(stored.id, 0);
+        assert!(stored.validate().is_ok());
+
+        let mut existing = BasicConfiguration::default();
+        existing.motd = "Kept".into();
+        assert!(missing_config(Some(&existing)).is_none());
+    }
+
+    #[test]
+    fn seed_is_picked_for_unversioned_data() {
+        // Rows stored before versioning have an empty or blank seed
+        for blank in ["", "  "] {
+            let mut config = BasicConfiguration {
+                seed: blank.into(),
+                ..Default::default()
+            };
+            assert!(pick_seed(&mut config, || 42));
+            assert_eq!(config.seed, "42");
+
+            // Running the step again keeps it
+            assert!(!pick_seed(&mut config, || 7));
+            assert_eq!(config.seed, "42");
+        }
+    }
+
+    #[test]
+    fn configured_seed_is_kept() {
+        let mut config = BasicConfiguration {
+            seed: "minecraft".into(),
+            ..Default::default()
+        };
+        assert!(!pick_seed(&mut config, || 42));
+        assert_eq!(config.seed, "minecraft");
+    }
+
+    #[test]
+    fn command_registry_drops_stale_commands() {
+        let registered = ["ban", "whitelist", "removed"].map(String::from);
+        let mut stale = command::stale(&registered);
+        stale.sort();
+        assert_eq!(stale, ["removed", "whitelist"]);
+    }
+
+    #[test]
+    fn command_registry_is_idempotent() {
+        let registered = command::builtin()
+            .into_iter()
+            .map(|command| command.name)
+            .collect::<Vec<_>>();
+        assert!(command::stale(&registered).is_empty());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::audit;
+use crate::auth::authorize;
+use crate::player::resolve_profile;
+use crate::server::basic_config;
+use crate::server::config::PermissionLvl;
+use spacetimedb::{ReducerContext, Table, reducer, table};
+use uuid::Uuid;
+
+/// Permission level required to run `/op` and `/deop`, as in vanilla.
+pub const OP_COMMAND_LEVEL: PermissionLvl = PermissionLvl::Three;
+
+#[table(name = operator, public)]
+pub struct Operator {
+    #[primary_key]
+    pub profile_id: u128,
+    /// Username at the time the player was opped, for display purposes only.
+    pub name: Option<String>,
+    pub level: PermissionLvl,
+    /// Whether the operator may join when the server is full.
+    pub bypasses_player_limit: bool,
+}
+
+pub fn permission_level(ctx: &ReducerContext, profile_id: u128) -> PermissionLvl {
+    ctx.db
+        .operator()
+        .profile_id()
+        .find(profile_id)
+        .map_or(PermissionLvl::Zero, |op| op.level)
+}
+
+/// Makes `target` (UUID or last known username) an operator.
+/// Without `level`, the configured `op_permission_level` is used.
+#[reducer]
+pub fn op_player(
+    ctx: &ReducerContext,
+    target: String,
+    level: Option<PermissionLvl>,
+    bypasses_player_limit: bool,
+    issuer: Option<String>,
+) -> Result<(), String> {
+    let issuer_level = authorize(ctx, issuer.as_deref(), OP_COMMAND_LEVEL)?;
+    let level = match level {
+        Some(level) => level,
+        None => basic_config(ctx)?.op_permission_level,
+    };
+    if level == PermissionLvl::Zero {
+        return Err("Operators need a permission level above zero, use deop instead".into());
+    }
+    if level > issuer_level {
+        return Err(format!("Can not grant {level:?} with {issuer_level:?}"));
+    }
+
+    let (profile_id, name) = resolve_profile(ctx, &target)?;
+    let op = Operator {
+        profile_id,
+        name,
+        level,
+        bypasses_player_limit,
+    };
+    let previous = ctx.db.operator().profile_id().find(profile_id);
+    let old_value = previous.as_ref().map(describe);
+    let new_value = describe(&op);
+    if previous.is_some() {
+        ctx.db.operator().profile_id().update(op);
+    } else {
+        ctx.db.operator().insert(op);
+    }
+    log::info!(
+        "Opped {target} ({}) with {level:?}",
+        Uuid::from_u128(profile_id)
+    );
+    audit::record(
+        ctx,
+        "op_player",
+        issuer.as_deref(),
+        Some(format!("{target} ({})", Uuid::from_u128(profile_id))),
+        old_value,
+        Some(new_value),
+    );
+
+    Ok(())
+}
+
+#[reducer]
+pub fn deop_player(
+    ctx: &ReducerContext,
+    target: String,
+    issuer: Option<String>,
+) -> Result<(), String> {
+    let issuer_level = authorize(ctx, issuer.as_deref(), OP_COMMAND_LEVEL)?;
+    let (profile_id, _) = resolve_profile(ctx, &target)?;
+    let level = permission_level(ctx, profile_id);
+    if level > issuer_level {
+        return Err(format!("Can not deop {level:?} with {issuer_level:?}"));
+    }
+    let Some(op) = ctx.db.operator().profile_id().find(profile_id) else {
+        return Err(format!("{target} is not an operator"));
+    };
+    ctx.db.operator().profile_id().delete(profile_id);
+    log::info!("Deopped {target} ({})", Uuid::from_u128(profile_id));
+    audit::record(
+        ctx,
+        "deop_player",
+        issuer.as_deref(),
+        Some(format!("{target} ({})", Uuid::from_u128(profile_id))),
+        Some(describe(&op)),
+        None,
+    );
+
+    Ok(())
+}
+
+/// How an operator shows up in the audit log.
+fn describe(op: &Operator) -> String {
+    format!(
+        "{:?}, bypasses player limit: {}",
+        op.level, op.bypasses_player_limit
+    )
+}
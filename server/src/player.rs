@@ -1,10 +1,27 @@
+use crate::audit;
+use crate::auth::{authorize, require_privileged, require_registered_proxy};
+use crate::ban::{BANNED, IP_BANNED, is_banned, is_ip_banned, normalize_ip};
+use crate::chat::chat_session;
+use crate::operator::operator;
+use crate::server::basic_config;
+use crate::server::config::PermissionLvl;
 use crate::types_support::UUID;
-use spacetimedb::{ReducerContext, Table, Timestamp, reducer, table};
+use crate::whitelist::{NOT_WHITELISTED, may_join};
+use crate::{inventory, player_state};
+use spacetimedb::{Identity, ReducerContext, Table, Timestamp, reducer, table};
 use std::fmt::{Debug, Formatter};
 use std::str::FromStr;
 use uuid::Uuid;
 
-#[table(name = player)]
+pub const SERVER_FULL: &str = "multiplayer.disconnect.server_full";
+pub const DUPLICATE_LOGIN: &str = "multiplayer.disconnect.duplicate_login";
+pub const KICKED: &str = "multiplayer.disconnect.kicked";
+
+/// Permission level required to run `/kick`, as in vanilla.
+pub const KICK_COMMAND_LEVEL: PermissionLvl = PermissionLvl::Three;
+
+/// Every player who ever joined or was added, public so proxies can export them.
+#[table(name = player, public)]
 pub struct Player {
     #[primary_key]
     #[auto_inc]
@@ -13,9 +30,51 @@ pub struct Player {
     #[unique]
     profile_id: u128,
     online: bool,
+    /// When the player joined or, once offline, when they were last known to be connected.
     last_seen: Timestamp,
 }
 
+/// A player currently connected through one of the proxies.
+#[table(name = player_session, public, index(name = by_proxy, btree(columns = [proxy])))]
+pub struct PlayerSession {
+    #[primary_key]
+    pub profile_id: u128,
+    /// The proxy holding the player's connection.
+    pub proxy: Identity,
+    /// Identifies the connection within its proxy.
+    pub connection_id: u64,
+    /// The player's entity id, stable across sessions.
+    pub entity_id: u32,
+    pub started_at: Timestamp,
+}
+
+/// The address a player connects from, kept apart from [PlayerSession] as it must never reach
+/// clients. Deleted along with the session.
+#[table(name = session_address)]
+pub struct SessionAddress {
+    #[primary_key]
+    pub profile_id: u128,
+    /// Canonical form, as produced by [normalize_ip].
+    #[index(btree)]
+    pub ip: String,
+}
+
+/// A request for a proxy to disconnect one of its players.
+/// The session is already ended when this is inserted, the proxy deletes it once handled.
+#[table(name = player_kick, public)]
+pub struct PlayerKick {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    #[index(btree)]
+    pub proxy: Identity,
+    pub connection_id: u64,
+    pub profile_id: u128,
+    /// Translation key or plain text shown to the player.
+    pub reason: String,
+    pub created_at: Timestamp,
+}
+
 impl Player {
     pub fn new(username: String, profile_id: u128, now: Timestamp) -> Self {
         Self {
@@ -46,19 +105,305 @@ fn add_player(
     username: String,
     profile_id_str: String,
 ) -> Result<(), String> {
+    require_privileged(ctx)?;
+    let profile_id = UUID::from_str(&profile_id_str)?.as_u128();
+    let player = upsert_player(ctx, username, profile_id, false);
+    log::info!("Upserted player: {:?}", player);
+
+    Ok(())
+}
+
+/// Called by the proxy holding the connection once the player is authenticated.
+/// `address` is the IP address the player connects from, checked against IP bans and kept
+/// private.
+#[reducer]
+fn player_join(
+    ctx: &ReducerContext,
+    username: String,
+    profile_id_str: String,
+    connection_id: u64,
+    address: String,
+) -> Result<(), String> {
+    require_registered_proxy(ctx)?;
+    let profile_id = UUID::from_str(&profile_id_str)?.as_u128();
+    validate_username(&username)?;
+    if is_banned(ctx, profile_id) {
+        return Err(BANNED.into());
+    }
+    let ip = normalize_ip(&address)?;
+    if is_ip_banned(ctx, &ip) {
+        return Err(IP_BANNED.into());
+    }
+    let config = basic_config(ctx)?;
+    if !may_join(ctx, &config, profile_id) {
+        return Err(NOT_WHITELISTED.into());
+    }
+    reserve_slot(ctx, profile_id)?;
+
+    if let Some(session) = ctx.db.player_session().profile_id().find(profile_id) {
+        log::info!(
+            "Player {} joined through {} while still online through {}",
+            profile_id_str,
+            ctx.sender,
+            session.proxy
+        );
+        kick_session(ctx, session, DUPLICATE_LOGIN);
+    }
+
+    let player = upsert_player(ctx, username, profile_id, true);
+    player_state::on_join(ctx, profile_id, &config);
+    inventory::on_join(ctx, profile_id);
+    ctx.db.player_session().insert(PlayerSession {
+        profile_id,
+        proxy: ctx.sender,
+        connection_id,
+        entity_id: player.entity_id,
+        started_at: ctx.timestamp,
+    });
+    ctx.db
+        .session_address()
+        .insert(SessionAddress { profile_id, ip });
+    log::info!("Player joined through {}: {:?}", ctx.sender, player);
+
+    Ok(())
+}
+
+/// Same rules as vanilla : 1 to 16 characters out of ASCII letters, digits and underscores.
+fn validate_username(username: &str) -> Result<(), String> {
+    let valid_length = (1..=16).contains(&username.len());
+    if valid_length
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        Ok(())
+    } else {
+        Err(format!("Invalid username {username:?}"))
+    }
+}
+
+/// Checks there is room for one more player across all proxies, the session inserted by the
+/// caller then takes the slot. Operators allowed to bypass the limit always get in.
+fn reserve_slot(ctx: &ReducerContext, profile_id: u128) -> Result<(), String> {
+    let max_players = basic_config(ctx)?.max_players;
+    if max_players == 0 {
+        return Ok(());
+    }
+
+    let mut occupied = ctx.db.player_session().count();
+    if ctx
+        .db
+        .player_session()
+        .profile_id()
+        .find(profile_id)
+        .is_some()
+    {
+        // Their previous session is about to be replaced
+        occupied -= 1;
+    }
+    if occupied < u64::from(max_players) {
+        return Ok(());
+    }
+
+    let bypasses = ctx
+        .db
+        .operator()
+        .profile_id()
+        .find(profile_id)
+        .is_some_and(|op| op.bypasses_player_limit);
+    if bypasses {
+        log::info!(
+            "Operator {} bypassed the player limit ({occupied}/{max_players})",
+            Uuid::from_u128(profile_id)
+        );
+        Ok(())
+    } else {
+        Err(SERVER_FULL.into())
+    }
+}
+
+/// Called by the proxy holding the connection once the player disconnected.
+#[reducer]
+fn player_leave(
+    ctx: &ReducerContext,
+    profile_id_str: String,
+    connection_id: u64,
+) -> Result<(), String> {
+    require_registered_proxy(ctx)?;
     let profile_id = UUID::from_str(&profile_id_str)?.as_u128();
-    let player = if let Some(player) = ctx.db.player().profile_id().find(profile_id) {
+    match ctx.db.player_session().profile_id().find(profile_id) {
+        Some(session) if session.proxy == ctx.sender && session.connection_id == connection_id => {
+            end_session(ctx, session);
+        }
+        // Already ended, e.g. kicked or replaced by a newer session
+        _ => log::debug!("Player {profile_id_str} left an already ended session"),
+    }
+    Ok(())
+}
+
+/// Fails unless the caller holds the given connection of the player.
+pub fn require_session(
+    ctx: &ReducerContext,
+    profile_id: u128,
+    connection_id: u64,
+) -> Result<(), String> {
+    match ctx.db.player_session().profile_id().find(profile_id) {
+        Some(session) if session.proxy == ctx.sender && session.connection_id == connection_id => {
+            Ok(())
+        }
+        _ => Err(format!(
+            "Player {} has no session on {}",
+            Uuid::from_u128(profile_id),
+            ctx.sender
+        )),
+    }
+}
+
+/// Disconnects `target` (UUID or last known username), who may join again right away.
+#[reducer]
+pub fn kick_player(
+    ctx: &ReducerContext,
+    target: String,
+    reason: Option<String>,
+    issuer: Option<String>,
+) -> Result<(), String> {
+    authorize(ctx, issuer.as_deref(), KICK_COMMAND_LEVEL)?;
+    let (profile_id, _) = resolve_profile(ctx, &target)?;
+    let Some(session) = ctx.db.player_session().profile_id().find(profile_id) else {
+        return Err(format!("{target} is not online"));
+    };
+    let reason = reason.unwrap_or_else(|| KICKED.into());
+    audit::record(
+        ctx,
+        "kick_player",
+        issuer.as_deref(),
+        Some(format!("{target} ({})", Uuid::from_u128(profile_id))),
+        None,
+        Some(reason.clone()),
+    );
+    kick_session(ctx, session, &reason);
+    Ok(())
+}
+
+#[reducer]
+fn acknowledge_kick(ctx: &ReducerContext, id: u64) -> Result<(), String> {
+    require_registered_proxy(ctx)?;
+    match ctx.db.player_kick().id().find(id) {
+        Some(kick) if kick.proxy == ctx.sender => {
+            ctx.db.player_kick().id().delete(id);
+            Ok(())
+        }
+        Some(_) => Err(format!("Kick {id} is not addressed to {}", ctx.sender)),
+        None => Ok(()),
+    }
+}
+
+/// Resolves a player from either their UUID or their last known username.
+/// Returns the profile id along with the last known username, if any.
+pub fn resolve_profile(
+    ctx: &ReducerContext,
+    target: &str,
+) -> Result<(u128, Option<String>), String> {
+    if let Ok(uuid) = UUID::from_str(target) {
+        let profile_id = uuid.as_u128();
+        let username = ctx
+            .db
+            .player()
+            .profile_id()
+            .find(profile_id)
+            .and_then(|player| player.last_known_username);
+        return Ok((profile_id, username));
+    }
+
+    ctx.db
+        .player()
+        .iter()
+        .find(|player| {
+            player
+                .last_known_username
+                .as_deref()
+                .is_some_and(|name| name.eq_ignore_ascii_case(target))
+        })
+        .map(|player| (player.profile_id, player.last_known_username))
+        .ok_or_else(|| format!("Unknown player {target}, use their UUID instead"))
+}
+
+fn upsert_player(ctx: &ReducerContext, username: String, profile_id: u128, online: bool) -> Player {
+    if let Some(player) = ctx.db.player().profile_id().find(profile_id) {
         ctx.db.player().entity_id().update(Player {
             last_known_username: Some(username),
+            online: online || player.online,
             last_seen: ctx.timestamp,
             ..player
         })
     } else {
-        ctx.db
-            .player()
-            .insert(Player::new(username, profile_id, ctx.timestamp))
-    };
-    log::info!("Upserted player: {:?}", player);
+        ctx.db.player().insert(Player {
+            online,
+            ..Player::new(username, profile_id, ctx.timestamp)
+        })
+    }
+}
 
-    Ok(())
+/// Ends the session of a player, marking them offline.
+pub fn end_session(ctx: &ReducerContext, session: PlayerSession) {
+    end_session_at(ctx, session, ctx.timestamp);
+}
+
+/// Ends the session of a player last known to be connected at `last_seen`.
+fn end_session_at(ctx: &ReducerContext, session: PlayerSession, last_seen: Timestamp) {
+    ctx.db
+        .player_session()
+        .profile_id()
+        .delete(session.profile_id);
+    ctx.db
+        .session_address()
+        .profile_id()
+        .delete(session.profile_id);
+    ctx.db
+        .chat_session()
+        .profile_id()
+        .delete(session.profile_id);
+    if let Some(player) = ctx.db.player().profile_id().find(session.profile_id) {
+        let player = ctx.db.player().entity_id().update(Player {
+            online: false,
+            last_seen,
+            ..player
+        });
+        log::info!("Player went offline: {:?}", player);
+    }
+}
+
+/// Ends the session of a player and asks their proxy to disconnect them.
+pub fn kick_session(ctx: &ReducerContext, session: PlayerSession, reason: &str) {
+    log::info!(
+        "Kicking {} from {} : {reason}",
+        Uuid::from_u128(session.profile_id),
+        session.proxy
+    );
+    ctx.db.player_kick().insert(PlayerKick {
+        id: 0,
+        proxy: session.proxy,
+        connection_id: session.connection_id,
+        profile_id: session.profile_id,
+        reason: reason.into(),
+        created_at: ctx.timestamp,
+    });
+    end_session(ctx, session);
+}
+
+/// Ends the sessions of all players connected through the given proxy, which was last known to be
+/// alive at `last_seen`.
+pub fn end_proxy_sessions(ctx: &ReducerContext, proxy: Identity, last_seen: Timestamp) {
+    // Nobody is left to handle them
+    ctx.db.player_kick().proxy().delete(&proxy);
+
+    let sessions = ctx
+        .db
+        .player_session()
+        .by_proxy()
+        .filter(&proxy)
+        .collect::<Vec<_>>();
+    for session in sessions {
+        end_session_at(ctx, session, last_seen);
+    }
 }
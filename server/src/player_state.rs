@@ -0,0 +1,195 @@
+use crate::auth::require_registered_proxy;
+use crate::player::require_session;
+use crate::server::basic_config;
+use crate::server::config::{BasicConfiguration, GameMode};
+use crate::types_support::UUID;
+use spacetimedb::{ReducerContext, Table, Timestamp, reducer, table};
+use std::str::FromStr;
+use std::time::Duration;
+use uuid::Uuid;
+
+pub const OVERWORLD: &str = "minecraft:overworld";
+pub const THE_NETHER: &str = "minecraft:the_nether";
+pub const THE_END: &str = "minecraft:the_end";
+
+/// Moves accepted per player within [MOVE_WINDOW], well above the rate proxies save them at.
+const MAX_MOVES_PER_WINDOW: u32 = 40;
+const MOVE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Where a player is and how they are doing, kept while they are offline so they come back where
+/// they logged out.
+///
+/// Created when players first join, so their game mode applies right away. Proxies place them at
+/// spawn until they moved.
+#[table(name = player_state, public)]
+pub struct PlayerState {
+    #[primary_key]
+    pub profile_id: u128,
+    /// Dimension key, e.g. `minecraft:overworld`.
+    pub dimension: String,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
+    /// Whether the position is where the player was, rather than a placeholder until they moved.
+    pub placed: bool,
+    pub game_mode: GameMode,
+    pub health: f32,
+    pub food: i32,
+    pub saturation: f32,
+    pub xp_level: i32,
+    /// Progress towards the next level, between 0 and 1.
+    pub xp_progress: f32,
+    pub xp_total: i32,
+    pub updated_at: Timestamp,
+}
+
+/// Moves a player made within the current window, see [MAX_MOVES_PER_WINDOW].
+#[table(name = move_budget)]
+pub struct MoveBudget {
+    #[primary_key]
+    profile_id: u128,
+    window_start: Timestamp,
+    moves: u32,
+}
+
+impl PlayerState {
+    /// A fresh player, as vanilla spawns them.
+    fn new(profile_id: u128, config: &BasicConfiguration, now: Timestamp) -> Self {
+        Self {
+            profile_id,
+            dimension: OVERWORLD.into(),
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            yaw: 0.0,
+            pitch: 0.0,
+            on_ground: false,
+            placed: false,
+            game_mode: config.default_gamemode,
+            health: 20.0,
+            food: 20,
+            saturation: 5.0,
+            xp_level: 0,
+            xp_progress: 0.0,
+            xp_total: 0,
+            updated_at: now,
+        }
+    }
+}
+
+/// Gives a new player the default game mode, and puts a returning one back in it if the
+/// configuration forces it.
+pub fn on_join(ctx: &ReducerContext, profile_id: u128, config: &BasicConfiguration) {
+    let Some(state) = ctx.db.player_state().profile_id().find(profile_id) else {
+        ctx.db
+            .player_state()
+            .insert(PlayerState::new(profile_id, config, ctx.timestamp));
+        return;
+    };
+    if config.force_gamemode && state.game_mode != config.default_gamemode {
+        ctx.db.player_state().profile_id().update(PlayerState {
+            game_mode: config.default_gamemode,
+            ..state
+        });
+    }
+}
+
+/// Counts one more move, failing once the player moved too often within the window.
+fn spend_move(ctx: &ReducerContext, profile_id: u128) -> Result<(), String> {
+    let existing = ctx.db.move_budget().profile_id().find(profile_id);
+    let budget = match &existing {
+        Some(budget)
+            if ctx
+                .timestamp
+                .duration_since(budget.window_start)
+                .is_some_and(|elapsed| elapsed < MOVE_WINDOW) =>
+        {
+            if budget.moves >= MAX_MOVES_PER_WINDOW {
+                return Err(format!(
+                    "Player {} moved more than {MAX_MOVES_PER_WINDOW} times within {MOVE_WINDOW:?}",
+                    Uuid::from_u128(profile_id)
+                ));
+            }
+            MoveBudget {
+                moves: budget.moves + 1,
+                ..*budget
+            }
+        }
+        _ => MoveBudget {
+            profile_id,
+            window_start: ctx.timestamp,
+            moves: 1,
+        },
+    };
+
+    if existing.is_some() {
+        ctx.db.move_budget().profile_id().update(budget);
+    } else {
+        ctx.db.move_budget().insert(budget);
+    }
+    Ok(())
+}
+
+fn validate_dimension(dimension: &str, config: &BasicConfiguration) -> Result<(), String> {
+    match dimension {
+        OVERWORLD | THE_END => Ok(()),
+        THE_NETHER if config.allow_nether => Ok(()),
+        THE_NETHER => Err("The nether is disabled".into()),
+        other => Err(format!("Unknown dimension {other}")),
+    }
+}
+
+/// Called by the proxy holding the connection with where the player moved, a few times per second
+/// at most.
+#[reducer]
+#[allow(clippy::too_many_arguments)]
+fn move_player(
+    ctx: &ReducerContext,
+    profile_id_str: String,
+    connection_id: u64,
+    dimension: String,
+    x: f64,
+    y: f64,
+    z: f64,
+    yaw: f32,
+    pitch: f32,
+    on_ground: bool,
+) -> Result<(), String> {
+    require_registered_proxy(ctx)?;
+    let profile_id = UUID::from_str(&profile_id_str)?.as_u128();
+    require_session(ctx, profile_id, connection_id)?;
+    let config = basic_config(ctx)?;
+    validate_dimension(&dimension, &config)?;
+    if ![x, y, z].iter().all(|coordinate| coordinate.is_finite())
+        || !yaw.is_finite()
+        || !pitch.is_finite()
+    {
+        return Err(format!("Invalid position for player {profile_id_str}"));
+    }
+    spend_move(ctx, profile_id)?;
+
+    let existing = ctx.db.player_state().profile_id().find(profile_id);
+    let exists = existing.is_some();
+    let state = existing.unwrap_or_else(|| PlayerState::new(profile_id, &config, ctx.timestamp));
+    let state = PlayerState {
+        dimension,
+        x,
+        y,
+        z,
+        yaw,
+        pitch: pitch.clamp(-90.0, 90.0),
+        on_ground,
+        placed: true,
+        updated_at: ctx.timestamp,
+        ..state
+    };
+    if exists {
+        ctx.db.player_state().profile_id().update(state);
+    } else {
+        ctx.db.player_state().insert(state);
+    }
+    Ok(())
+}
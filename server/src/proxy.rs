@@ -0,0 +1,137 @@
+use crate::auth::{require_registered_proxy, require_trusted_proxy};
+use crate::player::end_proxy_sessions;
+use crate::world::generation::release_claims;
+use spacetimedb::{Identity, ReducerContext, ScheduleAt, Table, Timestamp, reducer, table};
+use std::time::Duration;
+
+/// How often the dead proxy sweep runs.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+/// A proxy that did not send a heartbeat for this long is considered dead.
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A proxy process sharing this database, keyed by its connection identity.
+#[table(name = proxy_instance, public)]
+pub struct ProxyInstance {
+    #[primary_key]
+    pub identity: Identity,
+    /// The address players can reach this proxy on.
+    pub address: String,
+    /// Version of the proxy, as reported at registration.
+    pub version: String,
+    pub started_at: Timestamp,
+    pub last_heartbeat: Timestamp,
+}
+
+#[table(name = proxy_sweep_schedule, scheduled(sweep_dead_proxies))]
+pub struct ProxySweepSchedule {
+    #[primary_key]
+    #[auto_inc]
+    scheduled_id: u64,
+    scheduled_at: ScheduleAt,
+}
+
+/// Starts sweeping dead proxies, unless already scheduled.
+pub fn schedule_sweep(ctx: &ReducerContext) {
+    if ctx.db.proxy_sweep_schedule().count() == 0 {
+        ctx.db.proxy_sweep_schedule().insert(ProxySweepSchedule {
+            scheduled_id: 0,
+            scheduled_at: ScheduleAt::Interval(SWEEP_INTERVAL.into()),
+        });
+        log::info!("Scheduled dead proxy sweep every {SWEEP_INTERVAL:?}");
+    }
+}
+
+/// Whether the given identity belongs to a registered, live proxy.
+pub fn is_registered_proxy(ctx: &ReducerContext, identity: Identity) -> bool {
+    ctx.db.proxy_instance().identity().find(identity).is_some()
+}
+
+/// Removes the proxy from the registry, marks all of its players offline and releases the chunks
+/// it was generating.
+pub fn proxy_offline(ctx: &ReducerContext, identity: Identity) {
+    take_offline(ctx, identity, ctx.timestamp);
+}
+
+/// As [proxy_offline], for a proxy last known to be alive at `last_seen`.
+fn take_offline(ctx: &ReducerContext, identity: Identity, last_seen: Timestamp) {
+    if ctx.db.proxy_instance().identity().delete(identity) {
+        log::info!("Proxy {identity} went offline");
+    }
+    end_proxy_sessions(ctx, identity, last_seen);
+    release_claims(ctx, identity);
+}
+
+#[reducer]
+fn register_proxy(ctx: &ReducerContext, address: String, version: String) -> Result<(), String> {
+    require_trusted_proxy(ctx)?;
+    let proxy = ProxyInstance {
+        identity: ctx.sender,
+        address,
+        version,
+        started_at: ctx.timestamp,
+        last_heartbeat: ctx.timestamp,
+    };
+    if let Some(existing) = ctx.db.proxy_instance().identity().find(ctx.sender) {
+        log::info!(
+            "Proxy {} re-registered on {} (was {})",
+            ctx.sender,
+            proxy.address,
+            existing.address
+        );
+        ctx.db.proxy_instance().identity().update(ProxyInstance {
+            started_at: existing.started_at,
+            ..proxy
+        });
+    } else {
+        log::info!(
+            "Proxy {} registered on {} ({})",
+            ctx.sender,
+            proxy.address,
+            proxy.version
+        );
+        ctx.db.proxy_instance().insert(proxy);
+    }
+
+    Ok(())
+}
+
+/// Only the proxy row is refreshed : its players are as alive as it is, see [sweep_dead_proxies].
+#[reducer]
+fn proxy_heartbeat(ctx: &ReducerContext) -> Result<(), String> {
+    require_registered_proxy(ctx)?;
+    if let Some(proxy) = ctx.db.proxy_instance().identity().find(ctx.sender) {
+        ctx.db.proxy_instance().identity().update(ProxyInstance {
+            last_heartbeat: ctx.timestamp,
+            ..proxy
+        });
+    }
+
+    Ok(())
+}
+
+/// Players of a dead proxy were last seen at its last heartbeat.
+#[reducer]
+fn sweep_dead_proxies(ctx: &ReducerContext, _schedule: ProxySweepSchedule) -> Result<(), String> {
+    if ctx.sender != ctx.identity() {
+        return Err("Only the scheduler may sweep dead proxies".into());
+    }
+
+    let dead = ctx
+        .db
+        .proxy_instance()
+        .iter()
+        .filter(|proxy| {
+            ctx.timestamp
+                .duration_since(proxy.last_heartbeat)
+                .is_some_and(|elapsed| elapsed > HEARTBEAT_TIMEOUT)
+        })
+        .map(|proxy| (proxy.identity, proxy.last_heartbeat))
+        .collect::<Vec<_>>();
+
+    for (identity, last_heartbeat) in dead {
+        log::warn!("Proxy {identity} missed its heartbeat, sweeping it");
+        take_offline(ctx, identity, last_heartbeat);
+    }
+
+    Ok(())
+}
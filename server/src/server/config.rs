@@ -1,4 +1,12 @@
+use crate::audit;
+use crate::auth::{authorize, require_privileged};
+use crate::whitelist;
 use spacetimedb::{ReducerContext, SpacetimeType, reducer};
+use std::net::SocketAddr;
+use std::ops::RangeInclusive;
+
+/// Allowed view and simulation distances, in chunks.
+const DISTANCE_RANGE: RangeInclusive<u8> = 2..=32;
 
 #[derive(PartialEq, Clone, Debug, SpacetimeType)]
 pub enum Difficulty {
@@ -8,7 +16,7 @@ pub enum Difficulty {
     Hard,
 }
 
-#[derive(Clone, Copy, Default, PartialEq, Eq, SpacetimeType)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, SpacetimeType)]
 pub enum PermissionLvl {
     #[default]
     Zero = 0,
@@ -26,13 +34,16 @@ pub enum GameMode {
     Spectator,
 }
 
+#[derive(Clone)]
 #[spacetimedb::table(name = server_basic_config, public)]
 pub struct BasicConfiguration {
     #[primary_key]
     pub id: u8,
-    /// The address to bind the server to.
+    /// The addresses to bind the server to, separated by commas (e.g. `0.0.0.0:25565,[::]:25565`).
+    /// Port `0` lets the system pick a free port.
     pub server_address: String,
-    /// The seed for world generation.
+    /// The seed for world generation. Numbers are used as is, any other text is hashed as
+    /// vanilla does. Changing it only affects chunks generated afterwards.
     pub seed: String,
     /// The maximum number of players allowed on the server. Specifying `0` disables the limit.
     pub max_players: u32,
@@ -108,12 +119,356 @@ impl Default for BasicConfiguration {
 
 #[reducer]
 fn update_motd(ctx: &ReducerContext, motd: String) -> Result<(), String> {
+    require_privileged(ctx)?;
     match ctx.db.server_basic_config().id().find(0) {
         Some(mut config) => {
-            config.motd = motd;
+            let old_motd = std::mem::replace(&mut config.motd, motd.clone());
             ctx.db.server_basic_config().id().update(config);
+            audit::record(ctx, "update_motd", None, None, Some(old_motd), Some(motd));
             Ok(())
         }
         None => Err("Did not find a basic config !".into()),
     }
 }
+
+/// Partial update of the [BasicConfiguration], only the fields set are changed.
+#[derive(Default, SpacetimeType)]
+pub struct ConfigurationUpdate {
+    pub server_address: Option<String>,
+    pub seed: Option<String>,
+    pub max_players: Option<u32>,
+    pub view_distance: Option<u8>,
+    pub simulation_distance: Option<u8>,
+    pub default_difficulty: Option<Difficulty>,
+    pub op_permission_level: Option<PermissionLvl>,
+    pub allow_nether: Option<bool>,
+    pub hardcore: Option<bool>,
+    pub online_mode: Option<bool>,
+    pub encryption: Option<bool>,
+    pub motd: Option<String>,
+    pub tps: Option<f32>,
+    pub default_gamemode: Option<GameMode>,
+    pub force_gamemode: Option<bool>,
+    pub scrub_ips: Option<bool>,
+    pub use_favicon: Option<bool>,
+    pub favicon_path: Option<String>,
+    pub default_level_name: Option<String>,
+    pub allow_chat_reports: Option<bool>,
+    pub white_list: Option<bool>,
+    pub enforce_whitelist: Option<bool>,
+}
+
+impl ConfigurationUpdate {
+    pub fn apply(self, config: BasicConfiguration) -> BasicConfiguration {
+        BasicConfiguration {
+            id: config.id,
+            server_address: self.server_address.unwrap_or(config.server_address),
+            seed: self.seed.unwrap_or(config.seed),
+            max_players: self.max_players.unwrap_or(config.max_players),
+            view_distance: self.view_distance.unwrap_or(config.view_distance),
+            simulation_distance: self
+                .simulation_distance
+                .unwrap_or(config.simulation_distance),
+            default_difficulty: self.default_difficulty.unwrap_or(config.default_difficulty),
+            op_permission_level: self
+                .op_permission_level
+                .unwrap_or(config.op_permission_level),
+            allow_nether: self.allow_nether.unwrap_or(config.allow_nether),
+            hardcore: self.hardcore.unwrap_or(config.hardcore),
+            online_mode: self.online_mode.unwrap_or(config.online_mode),
+            encryption: self.encryption.unwrap_or(config.encryption),
+            motd: self.motd.unwrap_or(config.motd),
+            tps: self.tps.unwrap_or(config.tps),
+            default_gamemode: self.default_gamemode.unwrap_or(config.default_gamemode),
+            force_gamemode: self.force_gamemode.unwrap_or(config.force_gamemode),
+            scrub_ips: self.scrub_ips.unwrap_or(config.scrub_ips),
+            use_favicon: self.use_favicon.unwrap_or(config.use_favicon),
+            favicon_path: self.favicon_path.unwrap_or(config.favicon_path),
+            default_level_name: self.default_level_name.unwrap_or(config.default_level_name),
+            allow_chat_reports: self.allow_chat_reports.unwrap_or(config.allow_chat_reports),
+            white_list: self.white_list.unwrap_or(config.white_list),
+            enforce_whitelist: self.enforce_whitelist.unwrap_or(config.enforce_whitelist),
+        }
+    }
+}
+
+impl BasicConfiguration {
+    /// Describes the settings that differ in `new`, as `(old values, new values)`.
+    pub fn describe_changes(&self, new: &Self) -> (String, String) {
+        let mut old_values = Vec::new();
+        let mut new_values = Vec::new();
+        macro_rules! compare {
+            ($($field:ident),*) => {$(
+                if self.$field != new.$field {
+                    old_values.push(format!("{}: {:?}", stringify!($field), self.$field));
+                    new_values.push(format!("{}: {:?}", stringify!($field), new.$field));
+                }
+            )*};
+        }
+        compare!(
+            server_address,
+            seed,
+            max_players,
+            view_distance,
+            simulation_distance,
+            default_difficulty,
+            op_permission_level,
+            allow_nether,
+            hardcore,
+            online_mode,
+            encryption,
+            motd,
+            tps,
+            default_gamemode,
+            force_gamemode,
+            scrub_ips,
+            use_favicon,
+            favicon_path,
+            default_level_name,
+            allow_chat_reports,
+            white_list,
+            enforce_whitelist
+        );
+        (old_values.join(", "), new_values.join(", "))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        // Empty segments are skipped, as proxies do when binding
+        let mut addresses = self
+            .server_address
+            .split(',')
+            .map(str::trim)
+            .filter(|address| !address.is_empty())
+            .peekable();
+        if addresses.peek().is_none() {
+            return Err("At least one server address is required".into());
+        }
+        for address in addresses {
+            if let Err(e) = address.parse::<SocketAddr>() {
+                return Err(format!("Invalid server address {address:?} : {e}"));
+            }
+        }
+        if !DISTANCE_RANGE.contains(&self.view_distance) {
+            return Err(format!(
+                "View distance must be within {DISTANCE_RANGE:?}, got {}",
+                self.view_distance
+            ));
+        }
+        if !DISTANCE_RANGE.contains(&self.simulation_distance) {
+            return Err(format!(
+                "Simulation distance must be within {DISTANCE_RANGE:?}, got {}",
+                self.simulation_distance
+            ));
+        }
+        if !(self.tps.is_finite() && self.tps > 0.0) {
+            return Err(format!("TPS must be positive, got {}", self.tps));
+        }
+        if self.online_mode && !self.encryption {
+            return Err("Encryption is required when online mode is enabled".into());
+        }
+        if self.op_permission_level == PermissionLvl::Zero {
+            return Err("The op permission level must be above zero".into());
+        }
+        if self.default_level_name.is_empty()
+            || self.default_level_name.contains(['/', '\\'])
+            || self.default_level_name.contains("..")
+        {
+            return Err(format!("Invalid level name {:?}", self.default_level_name));
+        }
+        Ok(())
+    }
+}
+
+/// Changes any number of settings at once, rejecting the whole update if the result is invalid.
+#[reducer]
+fn update_config(
+    ctx: &ReducerContext,
+    update: ConfigurationUpdate,
+    issuer: Option<String>,
+) -> Result<(), String> {
+    authorize(ctx, issuer.as_deref(), PermissionLvl::Four)?;
+    let Some(old) = ctx.db.server_basic_config().id().find(0) else {
+        return Err("Did not find a basic config !".into());
+    };
+    let config = update.apply(old.clone());
+    config.validate()?;
+    let (old_value, new_value) = old.describe_changes(&config);
+    ctx.db.server_basic_config().id().update(config);
+    log::info!(
+        "Configuration updated by {} (issuer {issuer:?})",
+        ctx.sender
+    );
+    audit::record(
+        ctx,
+        "update_config",
+        issuer.as_deref(),
+        None,
+        Some(old_value),
+        Some(new_value),
+    );
+
+    whitelist::enforce(ctx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_address(server_address: &str) -> BasicConfiguration {
+        BasicConfiguration {
+            server_address: server_address.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        assert!(BasicConfiguration::default().validate().is_ok());
+    }
+
+    #[test]
+    fn addresses_are_parsed() {
+        for valid in [
+            "0.0.0.0:25565",
+            "0.0.0.0:25565,[::]:25565",
+            " 127.0.0.1:0 , ",
+            ",[::1]:25566",
+        ] {
+            assert!(with_address(valid).validate().is_ok(), "{valid}");
+        }
+        for invalid in [
+            "",
+            " , ",
+            "localhost:25565",
+            "0.0.0.0",
+            "0.0.0.0:70000",
+            "0.0.0.0:25565,nope",
+        ] {
+            assert!(with_address(invalid).validate().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn distances_are_bounded() {
+        for distance in [2, 10, 32] {
+            let config = BasicConfiguration {
+                view_distance: distance,
+                simulation_distance: distance,
+                ..Default::default()
+            };
+            assert!(config.validate().is_ok(), "{distance}");
+        }
+        for distance in [0, 1, 33, u8::MAX] {
+            let view = BasicConfiguration {
+                view_distance: distance,
+                ..Default::default()
+            };
+            assert!(view.validate().is_err(), "{distance}");
+            let simulation = BasicConfiguration {
+                simulation_distance: distance,
+                ..Default::default()
+            };
+            assert!(simulation.validate().is_err(), "{distance}");
+        }
+    }
+
+    #[test]
+    fn online_mode_requires_encryption() {
+        for (online_mode, encryption, valid) in [
+            (true, true, true),
+            (true, false, false),
+            (false, true, true),
+            (false, false, true),
+        ] {
+            let config = BasicConfiguration {
+                online_mode,
+                encryption,
+                ..Default::default()
+            };
+            assert_eq!(
+                config.validate().is_ok(),
+                valid,
+                "online mode {online_mode}, encryption {encryption}"
+            );
+        }
+    }
+
+    #[test]
+    fn level_names_stay_in_the_server_directory() {
+        for valid in ["world", "my world", "world.old", "nether_2"] {
+            let config = BasicConfiguration {
+                default_level_name: valid.into(),
+                ..Default::default()
+            };
+            assert!(config.validate().is_ok(), "{valid}");
+        }
+        for invalid in ["", "worlds/world", "C:\\world", "..", "../world", "a..b"] {
+            let config = BasicConfiguration {
+                default_level_name: invalid.into(),
+                ..Default::default()
+            };
+            assert!(config.validate().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn tps_and_op_level_are_checked() {
+        for tps in [0.0, -20.0, f32::NAN, f32::INFINITY] {
+            let config = BasicConfiguration {
+                tps,
+                ..Default::default()
+            };
+            assert!(config.validate().is_err(), "{tps}");
+        }
+        let config = BasicConfiguration {
+            op_permission_level: PermissionLvl::Zero,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn empty_update_changes_nothing() {
+        let config = BasicConfiguration {
+            seed: "minecraft".into(),
+            motd: "Kept".into(),
+            ..Default::default()
+        };
+        let (old, new) =
+            config.describe_changes(&ConfigurationUpdate::default().apply(config.clone()));
+        assert_eq!((old.as_str(), new.as_str()), ("", ""));
+    }
+
+    #[test]
+    fn update_only_changes_the_fields_set() {
+        let config = BasicConfiguration {
+            seed: "minecraft".into(),
+            ..Default::default()
+        };
+        let update = ConfigurationUpdate {
+            motd: Some("Updated".into()),
+            view_distance: Some(12),
+            white_list: Some(true),
+            ..Default::default()
+        };
+        let updated = update.apply(config.clone());
+        assert_eq!(updated.motd, "Updated");
+        assert_eq!(updated.view_distance, 12);
+        assert!(updated.white_list);
+
+        let (old, new) = config.describe_changes(&updated);
+        assert_eq!(
+            old,
+            format!(
+                "view_distance: 10, motd: {:?}, white_list: false",
+                config.motd
+            )
+        );
+        assert_eq!(
+            new,
+            "view_distance: 12, motd: \"Updated\", white_list: true"
+        );
+        assert_eq!(updated.id, config.id);
+        assert_eq!(updated.seed, "minecraft");
+    }
+}
@@ -1,14 +1,12 @@
 pub mod config;
 use config::*;
 
-use spacetimedb::{ReducerContext, Table};
+use spacetimedb::ReducerContext;
 
-pub fn init(ctx: &ReducerContext) {
-    log::info!("Initializing basic configuration from {}", ctx.sender);
-    if ctx.db.server_basic_config().count() == 0 {
-        ctx.db
-            .server_basic_config()
-            .insert(BasicConfiguration::default());
-        log::info!("Stored default basic configuration");
-    }
+pub fn basic_config(ctx: &ReducerContext) -> Result<BasicConfiguration, String> {
+    ctx.db
+        .server_basic_config()
+        .id()
+        .find(0)
+        .ok_or_else(|| "Did not find a basic config !".into())
 }
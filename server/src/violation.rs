@@ -0,0 +1,136 @@
+use crate::audit;
+use crate::auth::{authorize, require_registered_proxy};
+use crate::player::{require_session, resolve_profile};
+use crate::server::config::PermissionLvl;
+use crate::types_support::UUID;
+use spacetimedb::{ReducerContext, SpacetimeType, Table, Timestamp, reducer, table};
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Same level as kicking or banning a player.
+const CLEAR_VIOLATIONS_LEVEL: PermissionLvl = PermissionLvl::Three;
+
+/// Why a proxy refused a move and set the player back.
+#[derive(Clone, Copy, Debug, PartialEq, SpacetimeType)]
+pub enum Violation {
+    /// Moved faster than their game mode allows over the last second.
+    Speed,
+    /// Moved too far within a single packet.
+    Teleport,
+    /// Stayed in the air without falling outside creative and spectator.
+    Flying,
+    /// Moved into a solid block.
+    InsideBlock,
+}
+
+/// How many moves of a player were refused, by violation, until an operator clears them.
+#[table(name = movement_violation, public)]
+pub struct MovementViolations {
+    #[primary_key]
+    pub profile_id: u128,
+    pub speed: u32,
+    pub teleport: u32,
+    pub flying: u32,
+    pub inside_block: u32,
+    pub last_violation: Violation,
+    /// What the proxy measured, e.g. the distance moved.
+    pub last_detail: String,
+    pub last_at: Timestamp,
+}
+
+impl MovementViolations {
+    fn new(profile_id: u128, violation: Violation, detail: String, now: Timestamp) -> Self {
+        Self {
+            profile_id,
+            speed: 0,
+            teleport: 0,
+            flying: 0,
+            inside_block: 0,
+            last_violation: violation,
+            last_detail: detail,
+            last_at: now,
+        }
+    }
+
+    fn count(&mut self, violation: Violation) {
+        let counter = match violation {
+            Violation::Speed => &mut self.speed,
+            Violation::Teleport => &mut self.teleport,
+            Violation::Flying => &mut self.flying,
+            Violation::InsideBlock => &mut self.inside_block,
+        };
+        *counter = counter.saturating_add(1);
+    }
+
+    pub fn total(&self) -> u32 {
+        self.speed
+            .saturating_add(self.teleport)
+            .saturating_add(self.flying)
+            .saturating_add(self.inside_block)
+    }
+}
+
+/// Called by the proxy holding the connection once it set the player back.
+#[reducer]
+fn report_violation(
+    ctx: &ReducerContext,
+    profile_id_str: String,
+    connection_id: u64,
+    violation: Violation,
+    detail: String,
+) -> Result<(), String> {
+    require_registered_proxy(ctx)?;
+    let profile_id = UUID::from_str(&profile_id_str)?.as_u128();
+    require_session(ctx, profile_id, connection_id)?;
+    log::info!(
+        "Player {profile_id_str} set back through {} : {violation:?} ({detail})",
+        ctx.sender
+    );
+
+    let existing = ctx.db.movement_violation().profile_id().find(profile_id);
+    let exists = existing.is_some();
+    let mut violations = existing.map_or_else(
+        || MovementViolations::new(profile_id, violation, detail.clone(), ctx.timestamp),
+        |violations| MovementViolations {
+            last_violation: violation,
+            last_detail: detail.clone(),
+            last_at: ctx.timestamp,
+            ..violations
+        },
+    );
+    violations.count(violation);
+    if exists {
+        ctx.db.movement_violation().profile_id().update(violations);
+    } else {
+        ctx.db.movement_violation().insert(violations);
+    }
+    Ok(())
+}
+
+/// Resets the counters of `target` (UUID or last known username), e.g. once they were looked into.
+#[reducer]
+fn clear_violations(
+    ctx: &ReducerContext,
+    target: String,
+    issuer: Option<String>,
+) -> Result<(), String> {
+    authorize(ctx, issuer.as_deref(), CLEAR_VIOLATIONS_LEVEL)?;
+    let (profile_id, _) = resolve_profile(ctx, &target)?;
+    let Some(violations) = ctx.db.movement_violation().profile_id().find(profile_id) else {
+        return Err(format!("{target} has no movement violations"));
+    };
+    ctx.db.movement_violation().profile_id().delete(profile_id);
+    log::info!(
+        "Cleared the movement violations of {target} ({})",
+        Uuid::from_u128(profile_id)
+    );
+    audit::record(
+        ctx,
+        "clear_violations",
+        issuer.as_deref(),
+        Some(format!("{target} ({})", Uuid::from_u128(profile_id))),
+        Some(format!("{} violations", violations.total())),
+        None,
+    );
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use crate::audit;
+use crate::auth::authorize;
+use crate::operator::permission_level;
+use crate::player::{kick_session, player_session, resolve_profile};
+use crate::server::basic_config;
+use crate::server::config::{BasicConfiguration, PermissionLvl, server_basic_config};
+use spacetimedb::{ReducerContext, Table, Timestamp, reducer, table};
+use uuid::Uuid;
+
+pub const NOT_WHITELISTED: &str = "multiplayer.disconnect.not_whitelisted";
+
+/// Permission level required to run `/whitelist`, as in vanilla.
+pub const WHITELIST_COMMAND_LEVEL: PermissionLvl = PermissionLvl::Three;
+
+#[table(name = whitelist, public)]
+pub struct WhitelistEntry {
+    #[primary_key]
+    pub profile_id: u128,
+    /// Username at the time the player was added, for display purposes only.
+    pub name: Option<String>,
+    pub added_at: Timestamp,
+}
+
+pub fn is_whitelisted(ctx: &ReducerContext, profile_id: u128) -> bool {
+    ctx.db.whitelist().profile_id().find(profile_id).is_some()
+}
+
+/// Whether the whitelist lets the player in. Operators are always let in, as in vanilla.
+pub fn may_join(ctx: &ReducerContext, config: &BasicConfiguration, profile_id: u128) -> bool {
+    !config.white_list
+        || is_whitelisted(ctx, profile_id)
+        || permission_level(ctx, profile_id) != PermissionLvl::Zero
+}
+
+/// Kicks every online player missing from the whitelist, if it is both enabled and enforced.
+pub fn enforce(ctx: &ReducerContext) -> Result<(), String> {
+    let config = basic_config(ctx)?;
+    if !(config.white_list && config.enforce_whitelist) {
+        return Ok(());
+    }
+
+    let sessions = ctx
+        .db
+        .player_session()
+        .iter()
+        .filter(|session| !may_join(ctx, &config, session.profile_id))
+        .collect::<Vec<_>>();
+    for session in sessions {
+        kick_session(ctx, session, NOT_WHITELISTED);
+    }
+
+    Ok(())
+}
+
+/// Adds a player to the whitelist, `target` being either their UUID or their last known username.
+#[reducer]
+pub fn whitelist_add(
+    ctx: &ReducerContext,
+    target: String,
+    issuer: Option<String>,
+) -> Result<(), String> {
+    authorize(ctx, issuer.as_deref(), WHITELIST_COMMAND_LEVEL)?;
+    let (profile_id, name) = resolve_profile(ctx, &target)?;
+    if is_whitelisted(ctx, profile_id) {
+        return Err(format!("{target} is already whitelisted"));
+    }
+
+    ctx.db.whitelist().insert(WhitelistEntry {
+        profile_id,
+        name,
+        added_at: ctx.timestamp,
+    });
+    log::info!("Whitelisted {target} ({})", Uuid::from_u128(profile_id));
+    audit::record(
+        ctx,
+        "whitelist_add",
+        issuer.as_deref(),
+        Some(format!("{target} ({})", Uuid::from_u128(profile_id))),
+        None,
+        Some("whitelisted".into()),
+    );
+
+    Ok(())
+}
+
+/// Removes a player from the whitelist, `target` being either their UUID or their last known username.
+#[reducer]
+pub fn whitelist_remove(
+    ctx: &ReducerContext,
+    target: String,
+    issuer: Option<String>,
+) -> Result<(), String> {
+    authorize(ctx, issuer.as_deref(), WHITELIST_COMMAND_LEVEL)?;
+    let (profile_id, _) = resolve_profile(ctx, &target)?;
+    if !ctx.db.whitelist().profile_id().delete(profile_id) {
+        return Err(format!("{target} is not whitelisted"));
+    }
+    log::info!(
+        "Removed {target} ({}) from the whitelist",
+        Uuid::from_u128(profile_id)
+    );
+    audit::record(
+        ctx,
+        "whitelist_remove",
+        issuer.as_deref(),
+        Some(format!("{target} ({})", Uuid::from_u128(profile_id))),
+        Some("whitelisted".into()),
+        None,
+    );
+
+    enforce(ctx)
+}
+
+/// Toggles the whitelist. Enforcing it kicks online players who are not on it.
+#[reducer]
+pub fn set_whitelist(
+    ctx: &ReducerContext,
+    enabled: bool,
+    enforced: bool,
+    issuer: Option<String>,
+) -> Result<(), String> {
+    authorize(ctx, issuer.as_deref(), WHITELIST_COMMAND_LEVEL)?;
+    let config = basic_config(ctx)?;
+    let old_value = format!(
+        "enabled: {}, enforced: {}",
+        config.white_list, config.enforce_whitelist
+    );
+    ctx.db
+        .server_basic_config()
+        .id()
+        .update(BasicConfiguration {
+            white_list: enabled,
+            enforce_whitelist: enforced,
+            ..config
+        });
+    log::info!("Whitelist enabled: {enabled}, enforced: {enforced}");
+    audit::record(
+        ctx,
+        "set_whitelist",
+        issuer.as_deref(),
+        None,
+        Some(old_value),
+        Some(format!("enabled: {enabled}, enforced: {enforced}")),
+    );
+
+    enforce(ctx)
+}
@@ -0,0 +1,190 @@
+use spacetimedb::{ReducerContext, SpacetimeType, Table, Timestamp, table};
+
+/// A chunk column of a dimension, its blocks live in [ChunkSection] rows.
+#[table(name = chunk, public)]
+pub struct Chunk {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    /// See [chunk_key], unique so clients can look chunks up by position.
+    #[unique]
+    pub key: String,
+    /// Dimension key, e.g. `minecraft:overworld`.
+    pub dimension: String,
+    pub x: i32,
+    pub z: i32,
+    /// Bumped on every change, so proxies know when what they cached is stale.
+    pub revision: u64,
+    pub heightmaps: Heightmaps,
+    pub updated_at: Timestamp,
+}
+
+/// Heightmaps as packed longs, in the anvil layout.
+#[derive(Clone, Default, SpacetimeType)]
+pub struct Heightmaps {
+    pub motion_blocking: Vec<i64>,
+    pub world_surface: Vec<i64>,
+}
+
+/// A 16x16x16 section of a chunk.
+///
+/// Palettes hold block state and biome names (e.g. `minecraft:oak_stairs[facing=east]`) rather
+/// than ids, which change between versions. The data arrays are the packed palette indices of
+/// the anvil format, empty when the palette has a single entry.
+#[table(name = chunk_section, public, index(name = by_chunk, btree(columns = [chunk_id])))]
+pub struct ChunkSection {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub chunk_id: u64,
+    /// Section index, e.g. -4 for the bottom of the overworld.
+    pub y: i8,
+    pub block_palette: Vec<String>,
+    pub block_data: Vec<i64>,
+    pub biome_palette: Vec<String>,
+    pub biome_data: Vec<i64>,
+    /// Nibble arrays of 2048 bytes, missing when the section has no light data.
+    pub block_light: Option<Vec<u8>>,
+    pub sky_light: Option<Vec<u8>>,
+}
+
+#[table(name = block_entity, public, index(name = by_chunk, btree(columns = [chunk_id])))]
+pub struct BlockEntity {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub chunk_id: u64,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    /// Block entity type, e.g. `minecraft:chest`.
+    pub kind: String,
+    /// Remaining NBT compound, without name.
+    pub nbt: Vec<u8>,
+}
+
+/// Everything about a chunk, as uploaded by proxies.
+#[derive(SpacetimeType)]
+pub struct ChunkData {
+    pub dimension: String,
+    pub x: i32,
+    pub z: i32,
+    pub heightmaps: Heightmaps,
+    pub sections: Vec<SectionData>,
+    pub block_entities: Vec<BlockEntityData>,
+}
+
+#[derive(SpacetimeType)]
+pub struct SectionData {
+    pub y: i8,
+    pub block_palette: Vec<String>,
+    pub block_data: Vec<i64>,
+    pub biome_palette: Vec<String>,
+    pub biome_data: Vec<i64>,
+    pub block_light: Option<Vec<u8>>,
+    pub sky_light: Option<Vec<u8>>,
+}
+
+#[derive(SpacetimeType)]
+pub struct BlockEntityData {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub kind: String,
+    pub nbt: Vec<u8>,
+}
+
+/// `<dimension> <x> <z>`
+pub fn chunk_key(dimension: &str, x: i32, z: i32) -> String {
+    format!("{dimension} {x} {z}")
+}
+
+pub fn find_chunk(ctx: &ReducerContext, dimension: &str, x: i32, z: i32) -> Option<Chunk> {
+    ctx.db.chunk().key().find(chunk_key(dimension, x, z))
+}
+
+/// Inserts the chunk, replacing any previous version of it. Returns the stored chunk.
+pub fn store_chunk(ctx: &ReducerContext, data: ChunkData) -> Result<Chunk, String> {
+    validate(&data)?;
+
+    let chunk = match find_chunk(ctx, &data.dimension, data.x, data.z) {
+        Some(existing) => {
+            ctx.db.chunk_section().by_chunk().delete(&existing.id);
+            ctx.db.block_entity().by_chunk().delete(&existing.id);
+            ctx.db.chunk().id().update(Chunk {
+                revision: existing.revision + 1,
+                heightmaps: data.heightmaps,
+                updated_at: ctx.timestamp,
+                ..existing
+            })
+        }
+        None => ctx.db.chunk().insert(Chunk {
+            id: 0,
+            key: chunk_key(&data.dimension, data.x, data.z),
+            dimension: data.dimension,
+            x: data.x,
+            z: data.z,
+            revision: 0,
+            heightmaps: data.heightmaps,
+            updated_at: ctx.timestamp,
+        }),
+    };
+
+    for section in data.sections {
+        ctx.db.chunk_section().insert(ChunkSection {
+            id: 0,
+            chunk_id: chunk.id,
+            y: section.y,
+            block_palette: section.block_palette,
+            block_data: section.block_data,
+            biome_palette: section.biome_palette,
+            biome_data: section.biome_data,
+            block_light: section.block_light,
+            sky_light: section.sky_light,
+        });
+    }
+    for block_entity in data.block_entities {
+        ctx.db.block_entity().insert(BlockEntity {
+            id: 0,
+            chunk_id: chunk.id,
+            x: block_entity.x,
+            y: block_entity.y,
+            z: block_entity.z,
+            kind: block_entity.kind,
+            nbt: block_entity.nbt,
+        });
+    }
+
+    Ok(chunk)
+}
+
+fn validate(data: &ChunkData) -> Result<(), String> {
+    let position = format!("{} {} {}", data.dimension, data.x, data.z);
+    for section in &data.sections {
+        if section.block_palette.is_empty() || section.biome_palette.is_empty() {
+            return Err(format!(
+                "Section {} of {position} has an empty palette",
+                section.y
+            ));
+        }
+        let light = [&section.block_light, &section.sky_light];
+        if light
+            .iter()
+            .any(|light| light.as_ref().is_some_and(|l| l.len() != 2048))
+        {
+            return Err(format!(
+                "Section {} of {position} has invalid light",
+                section.y
+            ));
+        }
+    }
+    for block_entity in &data.block_entities {
+        if block_entity.x.div_euclid(16) != data.x || block_entity.z.div_euclid(16) != data.z {
+            return Err(format!(
+                "Block entity at {} {} {} is outside of {position}",
+                block_entity.x, block_entity.y, block_entity.z
+            ));
+        }
+    }
+    Ok(())
+}
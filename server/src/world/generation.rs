@@ -0,0 +1,82 @@
+use crate::auth::require_registered_proxy;
+use crate::world::chunk::{ChunkData, chunk_key, find_chunk, store_chunk};
+use spacetimedb::{Identity, ReducerContext, Table, Timestamp, reducer, table};
+use std::time::Duration;
+
+/// A claim older than this is considered abandoned, e.g. the proxy crashed while generating.
+const CLAIM_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A missing chunk a proxy is generating from the seed.
+///
+/// Proxies claim a chunk before generating it, so two of them never generate the same chunk
+/// concurrently : the others wait for it to be stored instead.
+#[table(name = chunk_claim, public)]
+pub struct ChunkClaim {
+    /// See [chunk_key]
+    #[primary_key]
+    pub key: String,
+    #[index(btree)]
+    pub proxy: Identity,
+    pub claimed_at: Timestamp,
+}
+
+fn is_expired(ctx: &ReducerContext, claim: &ChunkClaim) -> bool {
+    ctx.timestamp
+        .duration_since(claim.claimed_at)
+        .is_some_and(|elapsed| elapsed > CLAIM_TIMEOUT)
+}
+
+/// Drops the claims of a proxy going offline, so others can generate those chunks.
+pub fn release_claims(ctx: &ReducerContext, proxy: Identity) {
+    ctx.db.chunk_claim().proxy().delete(&proxy);
+}
+
+/// Fails if the chunk exists or another proxy is generating it.
+#[reducer]
+fn claim_chunk(ctx: &ReducerContext, dimension: String, x: i32, z: i32) -> Result<(), String> {
+    require_registered_proxy(ctx)?;
+    let key = chunk_key(&dimension, x, z);
+    if find_chunk(ctx, &dimension, x, z).is_some() {
+        return Err(format!("Chunk {key} already exists"));
+    }
+
+    let claim = ChunkClaim {
+        key,
+        proxy: ctx.sender,
+        claimed_at: ctx.timestamp,
+    };
+    match ctx.db.chunk_claim().key().find(&claim.key) {
+        Some(existing) if existing.proxy != ctx.sender && !is_expired(ctx, &existing) => {
+            Err(format!(
+                "Chunk {} is being generated by {}",
+                claim.key, existing.proxy
+            ))
+        }
+        Some(_) => {
+            ctx.db.chunk_claim().key().update(claim);
+            Ok(())
+        }
+        None => {
+            ctx.db.chunk_claim().insert(claim);
+            Ok(())
+        }
+    }
+}
+
+/// Stores a chunk generated by the proxy holding its claim.
+#[reducer]
+fn store_generated_chunk(ctx: &ReducerContext, chunk: ChunkData) -> Result<(), String> {
+    require_registered_proxy(ctx)?;
+    let key = chunk_key(&chunk.dimension, chunk.x, chunk.z);
+    match ctx.db.chunk_claim().key().find(&key) {
+        Some(claim) if claim.proxy == ctx.sender => {}
+        _ => return Err(format!("Chunk {key} is not claimed by {}", ctx.sender)),
+    }
+    ctx.db.chunk_claim().key().delete(&key);
+
+    // Imported in the meantime, which wins over generated terrain
+    if find_chunk(ctx, &chunk.dimension, chunk.x, chunk.z).is_some() {
+        return Ok(());
+    }
+    store_chunk(ctx, chunk).map(|_| ())
+}
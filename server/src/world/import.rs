@@ -0,0 +1,61 @@
+use crate::auth::require_privileged;
+use crate::world::chunk::{ChunkData, store_chunk};
+use spacetimedb::{ReducerContext, Table, Timestamp, reducer, table};
+
+/// Largest number of chunks a single upload may carry.
+const MAX_BATCH_SIZE: usize = 64;
+
+/// A region file fully imported, so an interrupted import can skip it when resumed.
+#[table(name = imported_region, public)]
+pub struct ImportedRegion {
+    /// `<dimension> <region x> <region z>`
+    #[primary_key]
+    pub key: String,
+    pub chunks: u32,
+    pub imported_at: Timestamp,
+}
+
+pub fn region_key(dimension: &str, region_x: i32, region_z: i32) -> String {
+    format!("{dimension} {region_x} {region_z}")
+}
+
+/// Stores a batch of imported chunks, replacing the ones that already exist.
+#[reducer]
+fn upload_chunks(ctx: &ReducerContext, chunks: Vec<ChunkData>) -> Result<(), String> {
+    require_privileged(ctx)?;
+    if chunks.len() > MAX_BATCH_SIZE {
+        return Err(format!(
+            "At most {MAX_BATCH_SIZE} chunks per upload, got {}",
+            chunks.len()
+        ));
+    }
+
+    for chunk in chunks {
+        store_chunk(ctx, chunk)?;
+    }
+    Ok(())
+}
+
+/// Called once every chunk of a region was uploaded.
+#[reducer]
+fn finish_region_import(
+    ctx: &ReducerContext,
+    dimension: String,
+    region_x: i32,
+    region_z: i32,
+    chunks: u32,
+) -> Result<(), String> {
+    require_privileged(ctx)?;
+    let region = ImportedRegion {
+        key: region_key(&dimension, region_x, region_z),
+        chunks,
+        imported_at: ctx.timestamp,
+    };
+    log::info!("Imported region {} ({chunks} chunks)", region.key);
+    if ctx.db.imported_region().key().find(&region.key).is_some() {
+        ctx.db.imported_region().key().update(region);
+    } else {
+        ctx.db.imported_region().insert(region);
+    }
+    Ok(())
+}
@@ -0,0 +1,3 @@
+pub mod chunk;
+pub mod generation;
+pub mod import;